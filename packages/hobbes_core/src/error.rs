@@ -0,0 +1,109 @@
+//! Errors reported by language model providers.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+
+/// Why a request to a provider failed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LlmError {
+    /// The API key is missing or invalid, or doesn't have access to the model (401/403).
+    Auth { message: String },
+    /// The rate limit or quota was exceeded (429).
+    Quota { message: String, retry_after: Option<Duration> },
+    /// The provider failed or is overloaded (5xx).
+    Server { status: u16, message: String },
+    /// The request took too long.
+    Timeout { message: String },
+    /// The provider could not be reached, or the connection dropped mid-response.
+    Network { message: String },
+    /// The prompt or the response was blocked by the provider's safety filter.
+    Safety { message: String },
+    /// The provider's response could not be understood.
+    Parse { message: String },
+    /// Any other rejected request, e.g. an unknown model name.
+    Request { status: u16, message: String },
+}
+
+impl LlmError {
+    /// Classifies a failed HTTP response by its status code.
+    pub fn from_status(status: u16, message: String, retry_after: Option<Duration>) -> Self {
+        match status {
+            401 | 403 => LlmError::Auth { message },
+            408 => LlmError::Timeout { message },
+            429 => LlmError::Quota { message, retry_after },
+            500..=599 => LlmError::Server { status, message },
+            _ => LlmError::Request { status, message },
+        }
+    }
+
+    /// Whether the same request might succeed if sent again later.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            LlmError::Quota { .. } | LlmError::Server { .. } | LlmError::Timeout { .. } | LlmError::Network { .. }
+        )
+    }
+
+    /// How long the provider asked us to wait before trying again.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            LlmError::Quota { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            LlmError::Auth { .. } => "Authentication failed",
+            LlmError::Quota { .. } => "Rate limit reached",
+            LlmError::Server { .. } => "Provider unavailable",
+            LlmError::Timeout { .. } => "Request timed out",
+            LlmError::Network { .. } => "Connection failed",
+            LlmError::Safety { .. } => "Blocked by the safety filter",
+            LlmError::Parse { .. } => "Unreadable response",
+            LlmError::Request { .. } => "Request rejected",
+        }
+    }
+}
+
+impl fmt::Display for LlmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LlmError::Server { status, message } | LlmError::Request { status, message } => {
+                write!(f, "{} [{}]: {}", self.title(), status, message)
+            }
+            LlmError::Auth { message }
+            | LlmError::Quota { message, .. }
+            | LlmError::Timeout { message }
+            | LlmError::Network { message }
+            | LlmError::Safety { message }
+            | LlmError::Parse { message } => write!(f, "{}: {}", self.title(), message),
+        }
+    }
+}
+
+impl std::error::Error for LlmError {}
+
+impl From<serde_json::Error> for LlmError {
+    fn from(e: serde_json::Error) -> Self {
+        LlmError::Parse { message: e.to_string() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_errors_are_classified_by_status() {
+        let error = |code: u16| LlmError::from_status(code, "msg".to_string(), None);
+        assert!(matches!(error(401), LlmError::Auth { .. }));
+        assert!(matches!(error(429), LlmError::Quota { .. }));
+        assert!(matches!(error(503), LlmError::Server { status: 503, .. }));
+        assert!(matches!(error(400), LlmError::Request { status: 400, .. }));
+        assert!(error(529).is_retryable());
+        assert!(!error(403).is_retryable());
+    }
+}
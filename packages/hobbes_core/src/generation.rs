@@ -0,0 +1,35 @@
+//! Generation parameters sent with chat requests.
+
+use serde::{Deserialize, Serialize};
+
+/// Sampling and output settings sent with chat requests. `None` (or no stop sequences)
+/// leaves the value to the provider, or to the global settings for a session override.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+#[serde(default)]
+pub struct GenerationParams {
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub top_k: Option<u32>,
+    pub max_output_tokens: Option<u32>,
+    pub stop_sequences: Vec<String>,
+    /// Tokens the model may spend reasoning before it answers. 0 turns thinking off.
+    pub thinking_budget: Option<u32>,
+}
+
+impl GenerationParams {
+    /// Returns these parameters with every value set in `overrides` taking precedence.
+    pub fn merged_with(&self, overrides: &GenerationParams) -> GenerationParams {
+        GenerationParams {
+            temperature: overrides.temperature.or(self.temperature),
+            top_p: overrides.top_p.or(self.top_p),
+            top_k: overrides.top_k.or(self.top_k),
+            max_output_tokens: overrides.max_output_tokens.or(self.max_output_tokens),
+            stop_sequences: if overrides.stop_sequences.is_empty() {
+                self.stop_sequences.clone()
+            } else {
+                overrides.stop_sequences.clone()
+            },
+            thinking_budget: overrides.thinking_budget.or(self.thinking_budget),
+        }
+    }
+}
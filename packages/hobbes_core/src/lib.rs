@@ -1,3 +1,15 @@
 //! Core data models and services for the Hobbes application.
+//!
+//! Nothing in here depends on a UI framework, so the sessions, messages and their storage
+//! can be shared by every frontend.
 
-pub mod models;
\ No newline at end of file
+pub mod error;
+pub mod generation;
+pub mod mcp;
+pub mod message;
+pub mod models;
+pub mod persist;
+pub mod schema;
+pub mod session;
+pub mod store;
+pub mod usage;
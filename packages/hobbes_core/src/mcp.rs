@@ -0,0 +1,56 @@
+//! The MCP servers and tools available to a conversation.
+
+use chrono::{DateTime, Utc};
+use rmcp::model::Tool;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct McpContext {
+    pub servers: Vec<McpServerContext>,
+    /// Every started server, including those whose tools aren't available right now. This is
+    /// live state, so it isn't saved with the sessions that keep a copy of the context.
+    #[serde(skip)]
+    pub status: Vec<McpServerStatus>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct McpServerContext {
+    pub name: String,
+    pub description: String,
+    pub tools: Vec<Tool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct McpServerStatus {
+    pub name: String,
+    pub state: McpServerState,
+    /// When the server last became ready, if it is ready now.
+    pub ready_since: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    /// How many times the server has been restarted after failing or crashing.
+    pub restarts: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum McpServerState {
+    Starting,
+    Ready,
+    /// The server couldn't be started or connected to.
+    Failed,
+    /// The server stopped after it was ready. `exit_code` is set for local servers that
+    /// exited; it is `None` for a dropped remote connection or a process killed by a signal.
+    Crashed { exit_code: Option<i32> },
+}
+
+impl McpServerState {
+    pub fn label(&self) -> String {
+        match self {
+            McpServerState::Starting => "Starting".to_string(),
+            McpServerState::Ready => "Ready".to_string(),
+            McpServerState::Failed => "Failed".to_string(),
+            McpServerState::Crashed { exit_code: Some(code) } => format!("Crashed (exit code {})", code),
+            McpServerState::Crashed { exit_code: None } => "Crashed".to_string(),
+        }
+    }
+}
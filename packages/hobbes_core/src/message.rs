@@ -0,0 +1,182 @@
+//! Chat messages and the content they carry.
+
+use serde::{Deserialize, Serialize};
+
+use chrono::{DateTime, Utc};
+
+use crate::error::LlmError;
+use crate::models::LlmMetadata;
+
+/// One entry in a session's conversation.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Message {
+    pub id: uuid::Uuid,
+    pub author: String,
+    pub content: MessageContent,
+    /// Set when the user stopped the response before it finished.
+    #[serde(default)]
+    pub interrupted: bool,
+    /// Set when the provider request failed, so the bubble can offer a retry.
+    #[serde(default)]
+    pub error: Option<LlmError>,
+    /// The message this one replies to. Messages with the same parent are alternative
+    /// branches of the conversation.
+    #[serde(default)]
+    pub parent_message_id: Option<uuid::Uuid>,
+    /// When the message was created. `None` for messages saved before this was recorded.
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
+    /// The model, token counts and finish reason of a generated response.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<LlmMetadata>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum MessageContent {
+    Text(String),
+    ToolCall(ToolCall),
+    PermissionRequest(ToolCall),
+    Attachment(Attachment),
+}
+
+/// The largest file that can be attached. Providers cap the size of inline data per request.
+pub const MAX_ATTACHMENT_BYTES: usize = 20 * 1024 * 1024;
+
+/// A file the user attached to the conversation. The bytes are kept in the session's
+/// attachments folder rather than in `sessions.json`.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Attachment {
+    pub id: uuid::Uuid,
+    pub file_name: String,
+    pub mime_type: String,
+    pub size: u64,
+    pub path: std::path::PathBuf,
+}
+
+impl Attachment {
+    pub fn is_image(&self) -> bool {
+        self.mime_type.starts_with("image/")
+    }
+
+    /// Text files are sent to the model as text rather than as inline data.
+    pub fn is_text(&self) -> bool {
+        is_text_mime_type(&self.mime_type)
+    }
+}
+
+pub fn is_text_mime_type(mime_type: &str) -> bool {
+    mime_type.starts_with("text/") || mime_type == "application/json"
+}
+
+/// The file extensions that can be attached, and the MIME type each is sent as.
+pub const ATTACHMENT_TYPES: &[(&str, &str)] = &[
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("pdf", "application/pdf"),
+    ("md", "text/markdown"),
+    ("csv", "text/csv"),
+    ("json", "application/json"),
+    ("txt", "text/plain"),
+    ("log", "text/plain"),
+    ("rs", "text/plain"),
+    ("py", "text/plain"),
+    ("js", "text/plain"),
+    ("ts", "text/plain"),
+    ("toml", "text/plain"),
+    ("yaml", "text/plain"),
+    ("yml", "text/plain"),
+    ("html", "text/plain"),
+    ("css", "text/plain"),
+    ("sh", "text/plain"),
+];
+
+/// Returns the MIME type for a file that can be attached, or `None` if the type isn't supported.
+pub fn attachment_mime_type(file_name: &str) -> Option<&'static str> {
+    let extension = std::path::Path::new(file_name).extension()?.to_str()?.to_ascii_lowercase();
+    ATTACHMENT_TYPES.iter().find(|(ext, _)| *ext == extension).map(|(_, mime_type)| *mime_type)
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Default)]
+pub struct ToolCall {
+    pub execution_id: String,
+    pub server_name: String,
+    pub tool_name: String,
+    pub arguments: String,
+    pub status: ToolCallStatus,
+    pub response: String,
+}
+
+/// Token counts reported by a provider for a single model response.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Default)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+}
+
+impl ToolCall {
+    pub fn new(server_name: String, tool_name: String, args: serde_json::Value) -> Self {
+        Self {
+            execution_id: uuid::Uuid::new_v4().to_string(),
+            server_name,
+            tool_name,
+            arguments: args.to_string(),
+            status: ToolCallStatus::Running,
+            response: String::new(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, Copy, Debug, Default)]
+pub enum ToolCallStatus {
+    #[default]
+    Running,
+    Completed,
+    Error,
+}
+
+impl std::fmt::Display for ToolCallStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ToolCallStatus::Running => write!(f, "Running"),
+            ToolCallStatus::Completed => write!(f, "Completed"),
+            ToolCallStatus::Error => write!(f, "Error"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ToolResult {
+    pub status: ToolCallStatus,
+    pub response: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ToolCallRecord {
+    pub call: ToolCall,
+    pub result: ToolResult,
+    /// Records requested in the same model turn share a batch id, so their calls and
+    /// results can be replayed to the model as a single turn each.
+    #[serde(default)]
+    pub batch_id: Option<uuid::Uuid>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages_saved_before_metadata_still_load() {
+        let json = r#"{
+            "id": "6f1c2a4e-8d1b-4c4a-9f2e-3b5d7a9c1e20",
+            "author": "Hobbes",
+            "content": { "Text": "hello" }
+        }"#;
+        let message: Message = serde_json::from_str(json).unwrap();
+        assert_eq!(message.created_at, None);
+        assert_eq!(message.metadata, None);
+    }
+}
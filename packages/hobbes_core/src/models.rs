@@ -1,7 +1,9 @@
 //! Data models for the Hobbes application.
 
+use serde::{Deserialize, Serialize};
+
 /// Represents a single, independent chat conversation.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatSession {
     /// A unique identifier for the chat session.
     pub id: String,
@@ -14,7 +16,7 @@ pub struct ChatSession {
 }
 
 /// Represents a single message within a `ChatSession`.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatMessage {
     /// A unique identifier for the message.
     pub id: String,
@@ -32,7 +34,7 @@ pub struct ChatMessage {
 }
 
 /// An enum that holds the source-specific data for a `ChatMessage`.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum MessageSource {
     /// A message from the human user.
     User,
@@ -45,13 +47,80 @@ pub enum MessageSource {
     },
 }
 
-/// A required struct containing metadata for messages from an LLM.
-#[derive(Debug, Clone)]
+/// Metadata for messages from an LLM.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct LlmMetadata {
     /// The name or identifier of the LLM that generated the response (e.g., "gemini-2.5-pro").
     pub model_name: String,
+    /// The number of prompt tokens the model read to produce the response.
+    pub prompt_token_count: u64,
     /// The number of tokens used to generate the response.
-    pub token_count: u32,
-    /// The reason the model stopped generating text (e.g., "stop_sequence", "max_tokens").
-    pub stop_reason: String,
-}
\ No newline at end of file
+    pub token_count: u64,
+    /// The reason the model stopped generating text. `None` until the provider reports it.
+    pub stop_reason: Option<FinishReason>,
+}
+
+/// Why a model stopped generating, normalized across providers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FinishReason {
+    /// The model finished its answer or hit a stop sequence.
+    Stop,
+    /// The response was cut off by the output token limit.
+    MaxTokens,
+    /// The provider's safety filters stopped the response.
+    Safety,
+    /// The model stopped to call a tool.
+    ToolUse,
+    /// A reason this app doesn't recognise, kept verbatim.
+    Other(String),
+}
+
+impl FinishReason {
+    /// Maps a provider's raw finish reason (e.g. Gemini's `MAX_TOKENS`, OpenAI's `length`,
+    /// Anthropic's `end_turn`) onto the normalized reasons.
+    pub fn from_provider(raw: &str) -> Self {
+        match raw.to_ascii_lowercase().as_str() {
+            "stop" | "end_turn" | "stop_sequence" => FinishReason::Stop,
+            "max_tokens" | "length" => FinishReason::MaxTokens,
+            "safety" | "content_filter" | "refusal" | "recitation" | "blocklist"
+            | "prohibited_content" | "spii" => FinishReason::Safety,
+            "tool_calls" | "tool_use" | "function_call" => FinishReason::ToolUse,
+            _ => FinishReason::Other(raw.to_string()),
+        }
+    }
+
+    /// Whether the response was cut short rather than finished.
+    pub fn is_truncated(&self) -> bool {
+        matches!(self, FinishReason::MaxTokens | FinishReason::Safety)
+    }
+
+    pub fn label(&self) -> &str {
+        match self {
+            FinishReason::Stop => "Completed",
+            FinishReason::MaxTokens => "Hit token limit",
+            FinishReason::Safety => "Stopped by safety filter",
+            FinishReason::ToolUse => "Tool call",
+            FinishReason::Other(raw) => raw,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_finish_reasons_are_normalized_across_providers() {
+        assert_eq!(FinishReason::from_provider("MAX_TOKENS"), FinishReason::MaxTokens);
+        assert_eq!(FinishReason::from_provider("length"), FinishReason::MaxTokens);
+        assert_eq!(FinishReason::from_provider("end_turn"), FinishReason::Stop);
+        assert_eq!(FinishReason::from_provider("content_filter"), FinishReason::Safety);
+        assert_eq!(FinishReason::from_provider("tool_use"), FinishReason::ToolUse);
+        assert_eq!(
+            FinishReason::from_provider("pause_turn"),
+            FinishReason::Other("pause_turn".to_string())
+        );
+    }
+}
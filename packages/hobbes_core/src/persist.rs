@@ -0,0 +1,57 @@
+//! Crash-safe file writes.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Writes `data` to `path` so that a crash leaves either the old file or the new one, never a
+/// partial write: the data goes to a temporary file next to `path`, is flushed to disk, and
+/// then renamed over it.
+pub fn write_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    fs::create_dir_all(dir)?;
+    let file_name = path.file_name().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let tmp_path = dir.join(format!(".{}.{}.tmp", file_name.to_string_lossy(), uuid::Uuid::new_v4()));
+
+    let result = (|| {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)?;
+        // Makes the rename itself durable.
+        #[cfg(unix)]
+        File::open(dir)?.sync_all()?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// Moves an unreadable file aside as `<name>.corrupt-<timestamp>` so it is kept for inspection
+/// and never overwritten. Returns where it was moved.
+pub fn quarantine(path: &Path) -> io::Result<std::path::PathBuf> {
+    let stamp = chrono::Utc::now().format("%Y%m%d-%H%M%S");
+    let file_name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let target = path.with_file_name(format!("{}.corrupt-{}", file_name, stamp));
+    fs::rename(path, &target)?;
+    tracing::warn!("Moved unreadable {} to {}", path.display(), target.display());
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_atomic_replaces_file_and_leaves_no_temp_files() {
+        let dir = std::env::temp_dir().join(format!("hobbes-persist-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("settings.json");
+        write_atomic(&path, b"old").unwrap();
+        write_atomic(&path, b"new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
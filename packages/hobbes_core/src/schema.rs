@@ -0,0 +1,85 @@
+//! Versioning for the JSON files Hobbes writes.
+//!
+//! Each file carries a `schema_version`. Files written before versioning count as version 0.
+//! Reading a file upgrades it one version at a time through a chain of migrations, so a file
+//! from any earlier version ends up in the current shape.
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+/// Upgrades a JSON document from version `n` to `n + 1`, where `n` is its index in the chain.
+pub type Migration = fn(&mut Value);
+
+pub const VERSION_FIELD: &str = "schema_version";
+
+/// The version of a document, 0 if it has none.
+pub fn version_of(value: &Value) -> u32 {
+    value.get(VERSION_FIELD).and_then(Value::as_u64).unwrap_or(0) as u32
+}
+
+/// Runs the migrations `value` hasn't had yet and stamps it with the current version, which is
+/// the length of the chain. A document from a newer version is left alone and reported as an
+/// error, so that it isn't overwritten with an older shape.
+pub fn upgrade(value: &mut Value, migrations: &[Migration], what: &str) -> Result<(), String> {
+    let current = migrations.len() as u32;
+    let version = version_of(value);
+    if version > current {
+        return Err(format!(
+            "{} has schema version {}, but this version of Hobbes only understands up to {}",
+            what, version, current
+        ));
+    }
+    for (from, migration) in migrations.iter().enumerate().skip(version as usize) {
+        tracing::info!("Migrating {} from schema version {} to {}", what, from, from + 1);
+        migration(value);
+    }
+    if let Value::Object(map) = value {
+        map.insert(VERSION_FIELD.to_string(), Value::from(current));
+    }
+    Ok(())
+}
+
+/// Deserializes `value`, logging a warning for every field the type doesn't know rather than
+/// failing. Returns the paths of those fields.
+pub fn from_value<T: DeserializeOwned>(value: Value, what: &str) -> Result<(T, Vec<String>), serde_json::Error> {
+    let mut unknown = Vec::new();
+    let parsed = serde_ignored::deserialize(value, |path| unknown.push(path.to_string()))?;
+    for path in &unknown {
+        tracing::warn!("Ignoring unknown field `{}` in {}", path, what);
+    }
+    Ok((parsed, unknown))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rename_name_to_title(value: &mut Value) {
+        if let Some(name) = value.as_object_mut().and_then(|map| map.remove("name")) {
+            value["title"] = name;
+        }
+    }
+
+    fn add_tags(value: &mut Value) {
+        value["tags"] = json!([]);
+    }
+
+    const MIGRATIONS: &[Migration] = &[rename_name_to_title, add_tags];
+
+    #[test]
+    fn test_upgrade_runs_only_missing_migrations() {
+        let mut v0 = json!({ "name": "a" });
+        upgrade(&mut v0, MIGRATIONS, "test").unwrap();
+        assert_eq!(v0, json!({ "title": "a", "tags": [], "schema_version": 2 }));
+
+        let mut v1 = json!({ "schema_version": 1, "title": "b", "name": "kept" });
+        upgrade(&mut v1, MIGRATIONS, "test").unwrap();
+        assert_eq!(v1["name"], "kept");
+        assert_eq!(version_of(&v1), 2);
+
+        let mut newer = json!({ "schema_version": 3 });
+        assert!(upgrade(&mut newer, MIGRATIONS, "test").is_err());
+        assert_eq!(version_of(&newer), 3);
+    }
+}
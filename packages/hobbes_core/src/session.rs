@@ -0,0 +1,569 @@
+//! Sessions and the state holding them, persisted through a `SessionStore`.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use serde_json::Value;
+use crate::generation::GenerationParams;
+use crate::mcp::McpContext;
+use crate::message::{Attachment, Message, MessageContent, TokenUsage, ToolCallRecord};
+use crate::schema::Migration;
+use crate::store::{Backup, SessionStore, SqliteStore, StoreHandle};
+use crate::usage::UsageTotals;
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Default)]
+pub struct ConversationSummaryEntities {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub user_name: String,
+    #[serde(flatten)]
+    pub other_entities: HashMap<String, Value>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Default)]
+pub struct ConversationSummary {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub summary: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub sentiment: String,
+    #[serde(default)]
+    pub entities: ConversationSummaryEntities,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Tool {
+    pub function_declarations: Vec<Value>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ToolWrapper {
+    pub tool: Tool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Default)]
+#[serde(default)]
+pub struct ActiveContext {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_persona: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_instruction: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub force_tool_use_instruction: Option<String>,
+    pub conversation_summary: ConversationSummary,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mcp_tools: Option<McpContext>, // Keep for now for other potential uses
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<ToolWrapper>>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Session {
+    pub id: String,
+    pub name: String,
+    /// Every message in the session, in the order they were added. Each one points at its
+    /// parent, so edits and regenerated responses form branches.
+    pub messages: Vec<Message>,
+    /// The last message of the branch being shown and continued.
+    #[serde(default)]
+    pub active_leaf_id: Option<uuid::Uuid>,
+    pub active_context: ActiveContext,
+    pub last_updated: DateTime<Utc>,
+    #[serde(default)]
+    pub usage: UsageTotals,
+    /// Overrides the global generation settings for this session only.
+    #[serde(default)]
+    pub generation: GenerationParams,
+}
+
+impl Session {
+    /// The messages on the active branch, from the first message down to the active leaf.
+    pub fn active_path(&self) -> Vec<&Message> {
+        let by_id: HashMap<uuid::Uuid, &Message> = self.messages.iter().map(|m| (m.id, m)).collect();
+        let mut path = Vec::new();
+        let mut next = self.active_leaf_id;
+        while let Some(message) = next.and_then(|id| by_id.get(&id)) {
+            path.push(*message);
+            next = message.parent_message_id;
+        }
+        path.reverse();
+        path
+    }
+
+    /// Adds `message` to the end of the active branch.
+    pub fn push_message(&mut self, mut message: Message) {
+        message.parent_message_id = self.active_leaf_id;
+        self.active_leaf_id = Some(message.id);
+        self.messages.push(message);
+    }
+
+    /// The ids of `message_id` and its alternatives (messages with the same parent), oldest first.
+    pub fn sibling_ids(&self, message_id: &uuid::Uuid) -> Vec<uuid::Uuid> {
+        let Some(message) = self.messages.iter().find(|m| m.id == *message_id) else { return Vec::new() };
+        self.messages
+            .iter()
+            .filter(|m| m.parent_message_id == message.parent_message_id)
+            .map(|m| m.id)
+            .collect()
+    }
+
+    /// Shows the branch through `message_id`, following its most recent replies to a leaf.
+    pub fn select_branch(&mut self, message_id: uuid::Uuid) {
+        if !self.messages.iter().any(|m| m.id == message_id) {
+            return;
+        }
+        let mut leaf = message_id;
+        while let Some(child) = self.messages.iter().rev().find(|m| m.parent_message_id == Some(leaf)) {
+            leaf = child.id;
+        }
+        self.active_leaf_id = Some(leaf);
+    }
+
+    /// Moves the active leaf back to `message_id` (or to the start of the conversation for
+    /// `None`), so that the next message starts a new branch. Later messages are kept.
+    pub fn fork_from(&mut self, message_id: Option<uuid::Uuid>) {
+        self.active_leaf_id = message_id;
+    }
+}
+
+/// The migrations a `sessions.json` goes through when it is imported. See `crate::schema`.
+pub const SESSIONS_MIGRATIONS: &[Migration] = &[link_legacy_messages];
+
+/// Version 1: messages form a tree. The messages of a session saved before that become a
+/// single branch. Files written after the change but before versioning are already linked.
+fn link_legacy_messages(state: &mut Value) {
+    let Some(sessions) = state.get_mut("sessions").and_then(Value::as_object_mut) else { return };
+    for session in sessions.values_mut() {
+        let Some(session) = session.as_object_mut() else { continue };
+        let Some(messages) = session.get_mut("messages").and_then(Value::as_array_mut) else { continue };
+        let linked = messages.iter().any(|m| !m["parent_message_id"].is_null());
+        if linked {
+            continue;
+        }
+        let mut parent = Value::Null;
+        for message in messages.iter_mut().filter_map(Value::as_object_mut) {
+            message.insert("parent_message_id".to_string(), parent);
+            parent = message.get("id").cloned().unwrap_or(Value::Null);
+        }
+        if session.get("active_leaf_id").is_none_or(Value::is_null) {
+            session.insert("active_leaf_id".to_string(), parent);
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct SessionState {
+    pub sessions: HashMap<String, Session>,
+    pub active_session_id: String,
+    pub window_width: f64,
+    pub window_height: f64,
+    #[serde(default)]
+    pub tool_call_history: Vec<ToolCallRecord>,
+    /// Set when the saved sessions couldn't be read at startup, until the user restores a
+    /// backup or dismisses it.
+    #[serde(skip)]
+    pub recovery: Option<Recovery>,
+    #[serde(skip)]
+    store: StoreHandle,
+}
+
+/// What happened to saved sessions that couldn't be read, and what can be restored.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recovery {
+    /// Where the unreadable file was moved. It is kept, never overwritten.
+    pub quarantined: PathBuf,
+    pub error: String,
+    /// The newest backup, if there is one.
+    pub backup: Option<Backup>,
+}
+
+fn get_data_dir() -> Option<PathBuf> {
+    dirs::config_dir().and_then(|mut path| {
+        path.push("cai-hobbes");
+        fs::create_dir_all(&path).ok()?;
+        Some(path)
+    })
+}
+
+/// The folder holding the files attached to the session `session_id`.
+pub fn attachments_dir(session_id: &str) -> Option<PathBuf> {
+    dirs::config_dir().map(|mut path| {
+        path.push("cai-hobbes");
+        path.push("attachments");
+        path.push(session_id);
+        path
+    })
+}
+
+/// Copies an attached file into the session's attachments folder.
+pub fn store_attachment(
+    session_id: &str,
+    file_name: &str,
+    mime_type: &str,
+    data: &[u8],
+) -> Result<Attachment, std::io::Error> {
+    let mut path = attachments_dir(session_id).ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Could not find attachments path"))?;
+    fs::create_dir_all(&path)?;
+    let id = uuid::Uuid::new_v4();
+    // Prefixing the id keeps two attachments with the same name apart.
+    path.push(format!("{}-{}", id, file_name.replace(['/', '\\'], "_")));
+    fs::write(&path, data)?;
+    Ok(Attachment {
+        id,
+        file_name: file_name.to_string(),
+        mime_type: mime_type.to_string(),
+        size: data.len() as u64,
+        path,
+    })
+}
+
+impl SessionState {
+    pub fn new() -> Self {
+        Self::load().unwrap_or_else(|e| {
+            tracing::error!("Failed to load session state, changes won't be saved: {}", e);
+            Self::default()
+        })
+    }
+
+    /// Opens `sessions.db`, importing `sessions.json` from earlier versions on first run.
+    ///
+    /// A file that can't be read is quarantined rather than overwritten, and the state starts
+    /// empty with `recovery` set so the user can restore the last backup. After a successful
+    /// load a backup is taken.
+    pub fn load() -> Result<Self, std::io::Error> {
+        let dir = get_data_dir().ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Could not find sessions path"))?;
+        let db_path = dir.join("sessions.db");
+        let legacy_path = dir.join("sessions.json");
+        let backups_dir = dir.join("backups");
+
+        let opened = SqliteStore::open(&db_path).and_then(|store| {
+            let state = store.load()?;
+            Ok((store, state))
+        });
+        let (store, state) = match opened {
+            Ok((store, Some(state))) => (store, state),
+            Ok((store, None)) if legacy_path.exists() => match crate::store::import_legacy_json(&store, &legacy_path) {
+                Ok(state) => (store, state),
+                Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+                    let recovery = Self::quarantine(&legacy_path, &backups_dir, e)?;
+                    (store, Self { recovery: Some(recovery), ..Self::default() })
+                }
+                Err(e) => return Err(e),
+            },
+            Ok((store, None)) => (store, Self::default()),
+            Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+                let recovery = Self::quarantine(&db_path, &backups_dir, e)?;
+                (SqliteStore::open(&db_path)?, Self { recovery: Some(recovery), ..Self::default() })
+            }
+            Err(e) => return Err(e),
+        };
+
+        if state.recovery.is_none() && !state.sessions.is_empty() {
+            if let Err(e) = crate::store::back_up(&store, &backups_dir) {
+                tracing::error!("Failed to back up sessions: {}", e);
+            }
+        }
+        tracing::info!("Loaded window size: {}x{}", state.window_width, state.window_height);
+        Ok(state.with_store(Arc::new(store)))
+    }
+
+    /// Moves an unreadable sessions file, and a database's journal files, out of the way.
+    fn quarantine(path: &std::path::Path, backups_dir: &std::path::Path, error: std::io::Error) -> Result<Recovery, std::io::Error> {
+        tracing::error!("Saved sessions in {} are unreadable: {}", path.display(), error);
+        let quarantined = crate::persist::quarantine(path)?;
+        for suffix in ["-wal", "-shm"] {
+            let journal = PathBuf::from(format!("{}{}", path.display(), suffix));
+            if journal.exists() {
+                crate::persist::quarantine(&journal)?;
+            }
+        }
+        Ok(Recovery {
+            quarantined,
+            error: error.to_string(),
+            backup: crate::store::list_backups(backups_dir).into_iter().next(),
+        })
+    }
+
+    /// Replaces the sessions with the backup offered by `recovery`.
+    pub fn restore_backup(&mut self) -> Result<(), std::io::Error> {
+        let backup = self.recovery.as_ref().and_then(|recovery| recovery.backup.clone())
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "No backup to restore"))?;
+        let store = self.store.0.clone()
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Sessions aren't being saved"))?;
+        store.restore_from(&backup.path)?;
+        let state = store.load()?.unwrap_or_default();
+        tracing::info!("Restored sessions from backup {}", backup.path.display());
+        *self = state.with_store(store);
+        Ok(())
+    }
+
+    /// Keeps the empty state and stops offering recovery. The quarantined file stays on disk.
+    pub fn dismiss_recovery(&mut self) {
+        self.recovery = None;
+    }
+
+    /// Writes changes through `store` from now on.
+    pub fn with_store(mut self, store: Arc<dyn SessionStore>) -> Self {
+        self.store = StoreHandle(Some(store));
+        self
+    }
+
+    /// Saves the window and active-session state, the active session's changed messages, and
+    /// the tool call history. Other sessions are saved by the methods that change them.
+    pub fn save(&self) -> Result<(), std::io::Error> {
+        let Some(store) = &self.store.0 else { return Ok(()) };
+        if let Some(session) = self.get_active_session() {
+            store.save_session(session)?;
+        }
+        store.save_tool_calls(&self.tool_call_history)?;
+        store.save_meta(self)
+    }
+
+    fn save_meta(&self) -> Result<(), std::io::Error> {
+        match &self.store.0 {
+            Some(store) => store.save_meta(self),
+            None => Ok(()),
+        }
+    }
+
+    /// `save` only writes the active session, so flush it before another one takes its place.
+    fn save_outgoing_session(&self) {
+        if let Err(e) = self.save_session(&self.active_session_id) {
+            tracing::error!("Failed to save session {}: {}", self.active_session_id, e);
+        }
+    }
+
+    /// Writes the session with `id`, for changes to a session other than the active one.
+    pub fn save_session(&self, id: &str) -> Result<(), std::io::Error> {
+        match (&self.store.0, self.sessions.get(id)) {
+            (Some(store), Some(session)) => store.save_session(session),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn create_session(&mut self) {
+        let new_id = uuid::Uuid::new_v4().to_string();
+        let now = chrono::Local::now();
+        let new_session = Session {
+            id: new_id.clone(),
+            name: now.format("%b %d - %I:%M %p").to_string(),
+            messages: vec![],
+            active_leaf_id: None,
+            active_context: ActiveContext::default(),
+            last_updated: Utc::now(),
+            usage: Default::default(),
+            generation: Default::default(),
+        };
+        self.sessions.insert(new_id.clone(), new_session);
+        self.save_outgoing_session();
+        self.active_session_id = new_id;
+        if let Err(e) = self.save() {
+            tracing::error!("Failed to save session state after creating session: {}", e);
+        }
+    }
+
+    pub fn delete_session(&mut self, id: &str) {
+        self.sessions.remove(id);
+        if let Some(dir) = attachments_dir(id).filter(|dir| dir.exists()) {
+            if let Err(e) = fs::remove_dir_all(&dir) {
+                tracing::error!("Failed to remove attachments for session {}: {}", id, e);
+            }
+        }
+
+        if self.active_session_id == id {
+            // The active session was deleted. Find a new one or clear the active id.
+            self.active_session_id = self.sessions.keys().next().cloned().unwrap_or_default();
+        } else if self.sessions.is_empty() {
+            self.active_session_id = String::new();
+        }
+
+        if let Some(store) = &self.store.0 {
+            if let Err(e) = store.delete_session(id) {
+                tracing::error!("Failed to delete session {} from the store: {}", id, e);
+            }
+        }
+        if let Err(e) = self.save_meta() {
+            tracing::error!("Failed to save session state after deleting session: {}", e);
+        }
+    }
+
+    pub fn get_active_session(&self) -> Option<&Session> {
+        self.sessions.get(&self.active_session_id)
+    }
+
+    pub fn get_active_session_mut(&mut self) -> Option<&mut Session> {
+        self.sessions.get_mut(&self.active_session_id)
+    }
+
+    pub fn touch_session(&mut self, id: &str) {
+        if let Some(session) = self.sessions.get_mut(id) {
+            session.last_updated = Utc::now();
+        }
+    }
+    pub fn set_active_session(&mut self, id: String) {
+        self.save_outgoing_session();
+        self.active_session_id = id;
+        if let Err(e) = self.save_meta() {
+            tracing::error!("Failed to save session state after setting active session: {}", e);
+        }
+    }
+
+    pub fn update_window_size(&mut self, width: f64, height: f64) {
+        tracing::debug!("Updating window size in state to: {}x{}", width, height);
+        self.window_width = width;
+        self.window_height = height;
+        if let Err(e) = self.save_meta() {
+            tracing::error!("Failed to save session state after updating window size: {}", e);
+        }
+    }
+
+    pub fn update_session_name(&mut self, id: &str, new_name: String) {
+        if let Some(session) = self.sessions.get_mut(id) {
+            session.name = new_name;
+            if let Err(e) = self.save_session(id) {
+                tracing::error!("Failed to save session state after updating session name: {}", e);
+            }
+        }
+    }
+    pub fn update_session_generation(&mut self, id: &str, generation: GenerationParams) {
+        if let Some(session) = self.sessions.get_mut(id) {
+            session.generation = generation;
+            if let Err(e) = self.save_session(id) {
+                tracing::error!("Failed to save session state after updating generation settings: {}", e);
+            }
+        }
+    }
+    /// Adds a response's token usage and cost to the session with `id`. A session other than the
+    /// active one is saved right away, since `save` only writes the active session.
+    pub fn add_session_usage(&mut self, id: &str, usage: &TokenUsage, cost: f64) {
+        let Some(session) = self.sessions.get_mut(id) else { return };
+        session.usage.add(usage, cost);
+        if id != self.active_session_id {
+            if let Err(e) = self.save_session(id) {
+                tracing::error!("Failed to save session {} after recording usage: {}", id, e);
+            }
+        }
+    }
+    /// The message with `message_id` in the session `session_id`, which needn't be the active
+    /// one. The store is told it changed, so edit messages through this rather than through
+    /// `Session::messages`.
+    pub fn get_message_mut(&mut self, session_id: &str, message_id: &uuid::Uuid) -> Option<&mut Message> {
+        self.find_message_mut(session_id, |m| m.id == *message_id)
+    }
+    pub fn get_message_mut_by_execution_id(&mut self, execution_id: &str) -> Option<&mut Message> {
+        let session_id = self.active_session_id.clone();
+        self.find_message_mut(&session_id, |m| {
+            match &m.content {
+                MessageContent::ToolCall(tc) => tc.execution_id == execution_id,
+                MessageContent::PermissionRequest(tc) => tc.execution_id == execution_id,
+                _ => false,
+            }
+        })
+    }
+
+    fn find_message_mut(&mut self, session_id: &str, predicate: impl Fn(&Message) -> bool) -> Option<&mut Message> {
+        let session = self.sessions.get_mut(session_id)?;
+        let message = session.messages.iter_mut().find(|m| predicate(m))?;
+        if let Some(store) = &self.store.0 {
+            store.message_changed(&session.id, message.id);
+        }
+        Some(message)
+    }
+}
+impl Default for SessionState {
+    fn default() -> Self {
+        Self {
+            sessions: HashMap::new(),
+            active_session_id: String::new(),
+            window_width: 675.0,
+            window_height: 750.0,
+            tool_call_history: Vec::new(),
+            recovery: None,
+            store: StoreHandle::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(author: &str, text: &str) -> Message {
+        Message {
+            id: uuid::Uuid::new_v4(),
+            author: author.to_string(),
+            content: MessageContent::Text(text.to_string()),
+            interrupted: false,
+            error: None,
+            parent_message_id: None,
+            created_at: None,
+            metadata: None,
+        }
+    }
+
+    fn path_texts(session: &Session) -> Vec<String> {
+        session
+            .active_path()
+            .into_iter()
+            .map(|m| match &m.content {
+                MessageContent::Text(t) => t.clone(),
+                _ => String::new(),
+            })
+            .collect()
+    }
+
+    fn empty_session() -> Session {
+        Session {
+            id: "s".to_string(),
+            name: "Test".to_string(),
+            messages: vec![],
+            active_leaf_id: None,
+            active_context: ActiveContext::default(),
+            last_updated: Utc::now(),
+            usage: Default::default(),
+            generation: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_branches_keep_history() {
+        let mut session = empty_session();
+        let question = text("User", "hi");
+        let question_id = question.id;
+        let first = text("Hobbes", "first");
+        let first_id = first.id;
+        session.push_message(question);
+        session.push_message(first);
+        session.push_message(text("User", "more"));
+        session.push_message(text("Hobbes", "reply"));
+
+        // Regenerating forks after the question, leaving the first answer in place.
+        session.fork_from(Some(question_id));
+        let second = text("Hobbes", "second");
+        let second_id = second.id;
+        session.push_message(second);
+        assert_eq!(path_texts(&session), ["hi", "second"]);
+        assert_eq!(session.sibling_ids(&second_id), [first_id, second_id]);
+
+        // Switching back follows the old branch to its end.
+        session.select_branch(first_id);
+        assert_eq!(path_texts(&session), ["hi", "first", "more", "reply"]);
+        assert_eq!(session.messages.len(), 5);
+    }
+
+    #[test]
+    fn test_legacy_sessions_become_one_branch() {
+        let mut value: Value = serde_json::from_str(include_str!("../fixtures/sessions_v0.json")).unwrap();
+        crate::schema::upgrade(&mut value, SESSIONS_MIGRATIONS, "sessions.json").unwrap();
+        let (state, unknown) = crate::schema::from_value::<SessionState>(value, "sessions.json").unwrap();
+
+        let session = state.sessions.values().next().unwrap();
+        assert_eq!(path_texts(session), ["What's the weather like?", "I can't check the weather, but I can help you find a forecast."]);
+        assert_eq!(session.active_context.conversation_summary.summary, "The user asked about the weather.");
+        assert!(unknown.contains(&format!("sessions.{}.pinned", session.id)));
+    }
+}
@@ -0,0 +1,290 @@
+//! Storage backends for `SessionState`.
+//!
+//! The state is kept in memory and written through a `SessionStore` as it changes. Backends
+//! write incrementally: saving a session only touches the messages that were added, moved or
+//! reported through `message_changed` since the store last saw it.
+
+mod sqlite;
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use chrono::{DateTime, NaiveDateTime, SubsecRound, Utc};
+
+pub use sqlite::SqliteStore;
+
+use crate::message::ToolCallRecord;
+use crate::session::{Session, SessionState};
+
+pub trait SessionStore: Send + Sync + fmt::Debug {
+    /// Reads every session, along with the window and active-session state. Returns `None`
+    /// when nothing has been saved yet.
+    fn load(&self) -> io::Result<Option<SessionState>>;
+
+    /// Writes the active session id and the window size.
+    fn save_meta(&self, state: &SessionState) -> io::Result<()>;
+
+    /// Writes one session and whichever of its messages changed since it was last saved.
+    fn save_session(&self, session: &Session) -> io::Result<()>;
+
+    /// Notes that a message was edited in place, so the next `save_session` writes it.
+    fn message_changed(&self, session_id: &str, message_id: uuid::Uuid);
+
+    fn delete_session(&self, id: &str) -> io::Result<()>;
+
+    fn save_tool_calls(&self, records: &[ToolCallRecord]) -> io::Result<()>;
+
+    /// Writes a consistent copy of everything stored to `path`, which must not exist yet.
+    fn backup_to(&self, path: &Path) -> io::Result<()>;
+
+    /// Replaces everything stored with the contents of a backup written by `backup_to`.
+    fn restore_from(&self, path: &Path) -> io::Result<()>;
+
+    /// Writes a whole state, e.g. one read from a legacy `sessions.json`.
+    fn import(&self, state: &SessionState) -> io::Result<()> {
+        for session in state.sessions.values() {
+            self.save_session(session)?;
+        }
+        self.save_tool_calls(&state.tool_call_history)?;
+        self.save_meta(state)
+    }
+}
+
+/// Reads a `sessions.json` written by earlier versions, imports it into `store` and renames
+/// the file so it is only imported once. The renamed file is kept as a backup.
+pub fn import_legacy_json(store: &dyn SessionStore, path: &Path) -> io::Result<SessionState> {
+    let data = fs::read_to_string(path)?;
+    let mut value: serde_json::Value = serde_json::from_str(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    crate::schema::upgrade(&mut value, crate::session::SESSIONS_MIGRATIONS, "sessions.json")
+        .map_err(|e| io::Error::new(io::ErrorKind::Unsupported, e))?;
+    // The file is never written again, so its version isn't kept.
+    if let Some(map) = value.as_object_mut() {
+        map.remove(crate::schema::VERSION_FIELD);
+    }
+    let (state, _) = crate::schema::from_value::<SessionState>(value, "sessions.json")
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    store.import(&state)?;
+    fs::rename(path, path.with_extension("json.imported"))?;
+    tracing::info!("Imported {} sessions from {}", state.sessions.len(), path.display());
+    Ok(state)
+}
+
+/// How many backups `back_up` keeps. Older ones are deleted.
+const BACKUPS_TO_KEEP: usize = 5;
+const BACKUP_NAME_FORMAT: &str = "sessions-%Y%m%d-%H%M%S.db";
+
+/// A snapshot of the session store taken by `back_up`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Backup {
+    pub path: PathBuf,
+    pub taken_at: DateTime<Utc>,
+}
+
+/// The backups in `dir`, newest first.
+pub fn list_backups(dir: &Path) -> Vec<Backup> {
+    let Ok(entries) = fs::read_dir(dir) else { return Vec::new() };
+    let mut backups: Vec<Backup> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let name = path.file_name()?.to_str()?;
+            let taken_at = NaiveDateTime::parse_from_str(name, BACKUP_NAME_FORMAT).ok()?.and_utc();
+            Some(Backup { path, taken_at })
+        })
+        .collect();
+    backups.sort_by_key(|backup| std::cmp::Reverse(backup.taken_at));
+    backups
+}
+
+/// Writes a timestamped backup of `store` into `dir` and deletes all but the newest few.
+pub fn back_up(store: &dyn SessionStore, dir: &Path) -> io::Result<Backup> {
+    fs::create_dir_all(dir)?;
+    // Backups are named to the second, so that's the precision `list_backups` reports.
+    let taken_at = Utc::now().trunc_subsecs(0);
+    let path = dir.join(taken_at.format(BACKUP_NAME_FORMAT).to_string());
+    // Written under a name `list_backups` ignores, so an interrupted backup is never offered.
+    let tmp_path = dir.join(format!(".{}.tmp", uuid::Uuid::new_v4()));
+    let result = store.backup_to(&tmp_path).and_then(|_| fs::rename(&tmp_path, &path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result?;
+    for old in list_backups(dir).into_iter().skip(BACKUPS_TO_KEEP) {
+        if let Err(e) = fs::remove_file(&old.path) {
+            tracing::error!("Failed to remove old backup {}: {}", old.path.display(), e);
+        }
+    }
+    Ok(Backup { path, taken_at })
+}
+
+/// The store a `SessionState` writes through. It isn't part of the state itself, so serde
+/// skips it and comparisons ignore it.
+#[derive(Clone, Default)]
+pub(crate) struct StoreHandle(pub(crate) Option<Arc<dyn SessionStore>>);
+
+impl PartialEq for StoreHandle {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl fmt::Debug for StoreHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            Some(store) => store.fmt(f),
+            None => f.write_str("no store"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::{Message, MessageContent};
+
+    fn text(author: &str, text: &str) -> Message {
+        Message {
+            id: uuid::Uuid::new_v4(),
+            author: author.to_string(),
+            content: MessageContent::Text(text.to_string()),
+            interrupted: false,
+            error: None,
+            parent_message_id: None,
+            created_at: None,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_sqlite_store_round_trips_incremental_saves() {
+        let store = Arc::new(SqliteStore::open_in_memory().unwrap());
+        let mut state = SessionState::default().with_store(store.clone());
+        state.create_session();
+        let session = state.get_active_session_mut().unwrap();
+        session.push_message(text("User", "hi"));
+        session.push_message(text("Hobbes", "hel"));
+        state.save().unwrap();
+
+        // A streamed response grows in place, then a new branch is started.
+        let reply = state.get_active_session_mut().unwrap().messages[1].id;
+        let session_id = state.active_session_id.clone();
+        if let Some(MessageContent::Text(t)) = state.get_message_mut(&session_id, &reply).map(|m| &mut m.content) {
+            t.push_str("lo");
+        }
+        state.get_active_session_mut().unwrap().push_message(text("User", "again"));
+        state.update_window_size(800.0, 600.0);
+        state.save().unwrap();
+
+        let loaded = store.load().unwrap().unwrap();
+        assert_eq!(loaded, state);
+        assert_eq!(loaded.window_width, 800.0);
+
+        let id = state.active_session_id.clone();
+        state.delete_session(&id);
+        assert!(store.load().unwrap().unwrap().sessions.is_empty());
+    }
+
+    #[test]
+    fn test_legacy_json_is_imported_once() {
+        let dir = std::env::temp_dir().join(format!("hobbes-import-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("sessions.json");
+        let json = r#"{
+            "sessions": {
+                "s": {
+                    "id": "s",
+                    "name": "Old",
+                    "messages": [
+                        { "id": "6f1c2a4e-8d1b-4c4a-9f2e-3b5d7a9c1e20", "author": "User", "content": { "Text": "hi" } },
+                        { "id": "0b8e4d2c-5a7f-4e19-8c3d-2f6a1b9e7d40", "author": "Hobbes", "content": { "Text": "hello" } }
+                    ],
+                    "active_context": {},
+                    "last_updated": "2025-01-01T00:00:00Z"
+                }
+            },
+            "active_session_id": "s",
+            "window_width": 700.0,
+            "window_height": 800.0
+        }"#;
+        fs::write(&path, json).unwrap();
+
+        let store = SqliteStore::open_in_memory().unwrap();
+        let imported = import_legacy_json(&store, &path).unwrap();
+        assert!(!path.exists());
+        assert!(path.with_extension("json.imported").exists());
+
+        let loaded = store.load().unwrap().unwrap();
+        assert_eq!(loaded, imported);
+        assert_eq!(loaded.sessions["s"].active_path().len(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_backups_rotate_and_restore() {
+        let dir = std::env::temp_dir().join(format!("hobbes-backups-{}", uuid::Uuid::new_v4()));
+        let store = Arc::new(SqliteStore::open_in_memory().unwrap());
+        let mut state = SessionState::default().with_store(store.clone());
+        state.create_session();
+        state.get_active_session_mut().unwrap().push_message(text("User", "keep me"));
+        state.save().unwrap();
+
+        // Older snapshots beyond the limit are pruned.
+        fs::create_dir_all(&dir).unwrap();
+        for day in 1..=BACKUPS_TO_KEEP {
+            fs::write(dir.join(format!("sessions-200001{:02}-000000.db", day)), "").unwrap();
+        }
+        let backup = back_up(store.as_ref(), &dir).unwrap();
+        let backups = list_backups(&dir);
+        assert_eq!(backups.len(), BACKUPS_TO_KEEP);
+        assert_eq!(backups[0], backup);
+        assert!(!dir.join("sessions-20000101-000000.db").exists());
+
+        let id = state.active_session_id.clone();
+        state.delete_session(&id);
+        store.restore_from(&backup.path).unwrap();
+        let restored = store.load().unwrap().unwrap();
+        assert_eq!(restored.sessions[&id].messages.len(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_unreadable_message_is_skipped_and_kept() {
+        let dir = std::env::temp_dir().join(format!("hobbes-bad-row-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("sessions.db");
+        let store = Arc::new(SqliteStore::open(&path).unwrap());
+        let mut state = SessionState::default().with_store(store.clone());
+        state.create_session();
+        let session = state.get_active_session_mut().unwrap();
+        session.push_message(text("User", "hi"));
+        session.push_message(text("Hobbes", "hello"));
+        let bad = session.messages[1].id.to_string();
+        state.save().unwrap();
+
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute("UPDATE messages SET data = '{\"not\": \"a message\"}' WHERE id = ?1", [&bad]).unwrap();
+        let mut loaded = store.load().unwrap().unwrap().with_store(store.clone());
+        assert_eq!(loaded.get_active_session().unwrap().messages.len(), 1);
+
+        // Saving what was loaded leaves the unreadable row alone.
+        loaded.get_active_session_mut().unwrap().push_message(text("User", "still here?"));
+        loaded.save().unwrap();
+        let rows: i64 = conn.query_row("SELECT COUNT(*) FROM messages", [], |row| row.get(0)).unwrap();
+        assert_eq!(rows, 3);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_damaged_database_reads_as_invalid_data() {
+        let dir = std::env::temp_dir().join(format!("hobbes-corrupt-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("sessions.db");
+        fs::write(&path, vec![0x42; 8192]).unwrap();
+
+        let error = SqliteStore::open(&path).and_then(|store| store.load()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,375 @@
+//! A `SessionStore` backed by a SQLite database, with sessions, messages and tool calls in
+//! their own tables.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+
+use rusqlite::{params, Connection};
+use uuid::Uuid;
+
+use super::SessionStore;
+use crate::message::{Message, ToolCallRecord};
+use crate::session::{Session, SessionState};
+
+/// Each entry upgrades the database by one version, tracked in SQLite's `user_version`.
+/// Databases created before versioning are at 0 and already have the version 1 tables, which
+/// is why those are created with `IF NOT EXISTS`.
+const MIGRATIONS: &[&str] = &[SCHEMA_V1];
+
+const SCHEMA_V1: &str = "
+    CREATE TABLE IF NOT EXISTS meta (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS sessions (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        active_leaf_id TEXT,
+        last_updated TEXT NOT NULL,
+        active_context TEXT NOT NULL,
+        usage TEXT NOT NULL,
+        generation TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS messages (
+        id TEXT PRIMARY KEY,
+        session_id TEXT NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS messages_by_session ON messages (session_id, position);
+    CREATE TABLE IF NOT EXISTS tool_calls (
+        position INTEGER PRIMARY KEY,
+        data TEXT NOT NULL
+    );
+";
+
+#[derive(Debug)]
+pub struct SqliteStore {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    conn: Connection,
+    /// The position of each message as last written, per session. A save writes the messages
+    /// that are new or have moved, and deletes the ones that are gone.
+    saved_messages: HashMap<String, HashMap<Uuid, usize>>,
+    /// Messages edited in place since their session was last saved, per session.
+    changed_messages: HashMap<String, HashSet<Uuid>>,
+    saved_tool_calls: Option<u64>,
+}
+
+/// Converts a SQLite error, reporting a damaged database file as `InvalidData` like any
+/// other unreadable data.
+fn to_io(e: rusqlite::Error) -> io::Error {
+    let corrupt = matches!(
+        e.sqlite_error_code(),
+        Some(rusqlite::ErrorCode::DatabaseCorrupt | rusqlite::ErrorCode::NotADatabase)
+    );
+    if corrupt {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    } else {
+        io::Error::other(e)
+    }
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> io::Result<String> {
+    serde_json::to_string(value).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn from_json<T: serde::de::DeserializeOwned>(data: &str) -> io::Result<T> {
+    serde_json::from_str(data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn fingerprint(data: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl SqliteStore {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Self::with_connection(Connection::open(path).map_err(to_io)?)
+    }
+
+    pub fn open_in_memory() -> io::Result<Self> {
+        Self::with_connection(Connection::open_in_memory().map_err(to_io)?)
+    }
+
+    fn with_connection(mut conn: Connection) -> io::Result<Self> {
+        conn.pragma_update(None, "journal_mode", "WAL").map_err(to_io)?;
+        conn.pragma_update(None, "synchronous", "NORMAL").map_err(to_io)?;
+        conn.pragma_update(None, "foreign_keys", "ON").map_err(to_io)?;
+        Self::migrate(&mut conn)?;
+        Ok(Self {
+            inner: Mutex::new(Inner {
+                conn,
+                saved_messages: HashMap::new(),
+                changed_messages: HashMap::new(),
+                saved_tool_calls: None,
+            }),
+        })
+    }
+
+    fn migrate(conn: &mut Connection) -> io::Result<()> {
+        let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0)).map_err(to_io)?;
+        if version > MIGRATIONS.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("sessions.db has schema version {}, but this version of Hobbes only understands up to {}", version, MIGRATIONS.len()),
+            ));
+        }
+        for (from, sql) in MIGRATIONS.iter().enumerate().skip(version) {
+            tracing::info!("Migrating sessions.db from schema version {} to {}", from, from + 1);
+            let tx = conn.transaction().map_err(to_io)?;
+            tx.execute_batch(sql).map_err(to_io)?;
+            tx.pragma_update(None, "user_version", from + 1).map_err(to_io)?;
+            tx.commit().map_err(to_io)?;
+        }
+        Ok(())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl SessionStore for SqliteStore {
+    fn load(&self) -> io::Result<Option<SessionState>> {
+        let mut inner = self.lock();
+        let Inner { conn, saved_messages, changed_messages, saved_tool_calls } = &mut *inner;
+        saved_messages.clear();
+        changed_messages.clear();
+
+        let mut meta: HashMap<String, String> = HashMap::new();
+        {
+            let mut stmt = conn.prepare("SELECT key, value FROM meta").map_err(to_io)?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?))).map_err(to_io)?;
+            for row in rows {
+                let (key, value) = row.map_err(to_io)?;
+                meta.insert(key, value);
+            }
+        }
+        if meta.is_empty() {
+            return Ok(None);
+        }
+
+        let mut state = SessionState::default();
+        if let Some(id) = meta.remove("active_session_id") {
+            state.active_session_id = id;
+        }
+        if let Some(width) = meta.get("window_width").and_then(|v| v.parse().ok()) {
+            state.window_width = width;
+        }
+        if let Some(height) = meta.get("window_height").and_then(|v| v.parse().ok()) {
+            state.window_height = height;
+        }
+
+        {
+            let mut stmt = conn
+                .prepare("SELECT id, name, active_leaf_id, last_updated, active_context, usage, generation FROM sessions")
+                .map_err(to_io)?;
+            let mut rows = stmt.query([]).map_err(to_io)?;
+            while let Some(row) = rows.next().map_err(to_io)? {
+                let id: String = row.get(0).map_err(to_io)?;
+                let active_leaf_id: Option<String> = row.get(2).map_err(to_io)?;
+                let last_updated: String = row.get(3).map_err(to_io)?;
+                let session = Session {
+                    id: id.clone(),
+                    name: row.get(1).map_err(to_io)?,
+                    messages: Vec::new(),
+                    active_leaf_id: active_leaf_id.and_then(|id| Uuid::parse_str(&id).ok()),
+                    active_context: from_json(&row.get::<_, String>(4).map_err(to_io)?)?,
+                    last_updated: last_updated.parse().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+                    usage: from_json(&row.get::<_, String>(5).map_err(to_io)?)?,
+                    generation: from_json(&row.get::<_, String>(6).map_err(to_io)?)?,
+                };
+                state.sessions.insert(id, session);
+            }
+        }
+
+        {
+            let mut stmt = conn
+                .prepare("SELECT id, session_id, data FROM messages ORDER BY session_id, position")
+                .map_err(to_io)?;
+            let mut rows = stmt.query([]).map_err(to_io)?;
+            while let Some(row) = rows.next().map_err(to_io)? {
+                let id: String = row.get(0).map_err(to_io)?;
+                let session_id: String = row.get(1).map_err(to_io)?;
+                let data: String = row.get(2).map_err(to_io)?;
+                let Some(session) = state.sessions.get_mut(&session_id) else { continue };
+                // One unreadable message shouldn't cost the rest of the history. Its row is
+                // left as it is, since saves only touch the messages that were loaded.
+                let message: Message = match from_json(&data) {
+                    Ok(message) => message,
+                    Err(e) => {
+                        tracing::error!("Skipping unreadable message {} in session {}: {}", id, session_id, e);
+                        continue;
+                    }
+                };
+                saved_messages.entry(session_id).or_default().insert(message.id, session.messages.len());
+                session.messages.push(message);
+            }
+        }
+
+        {
+            let mut stmt = conn.prepare("SELECT data FROM tool_calls ORDER BY position").map_err(to_io)?;
+            let rows = stmt.query_map([], |row| row.get::<_, String>(0)).map_err(to_io)?;
+            for row in rows {
+                state.tool_call_history.push(from_json(&row.map_err(to_io)?)?);
+            }
+            *saved_tool_calls = Some(fingerprint(&to_json(&state.tool_call_history)?));
+        }
+
+        Ok(Some(state))
+    }
+
+    fn save_meta(&self, state: &SessionState) -> io::Result<()> {
+        let mut inner = self.lock();
+        let tx = inner.conn.transaction().map_err(to_io)?;
+        for (key, value) in [
+            ("active_session_id", state.active_session_id.clone()),
+            ("window_width", state.window_width.to_string()),
+            ("window_height", state.window_height.to_string()),
+        ] {
+            tx.execute(
+                "INSERT INTO meta (key, value) VALUES (?1, ?2) ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+                params![key, value],
+            )
+            .map_err(to_io)?;
+        }
+        tx.commit().map_err(to_io)
+    }
+
+    fn save_session(&self, session: &Session) -> io::Result<()> {
+        let mut inner = self.lock();
+        let Inner { conn, saved_messages, changed_messages, .. } = &mut *inner;
+        let previous = saved_messages.get(&session.id);
+        let edited = changed_messages.get(&session.id);
+
+        let mut written = HashMap::with_capacity(session.messages.len());
+        let mut changed = Vec::new();
+        for (position, message) in session.messages.iter().enumerate() {
+            let moved = previous.and_then(|saved| saved.get(&message.id)) != Some(&position);
+            if moved || edited.is_some_and(|edited| edited.contains(&message.id)) {
+                changed.push((message.id, position, to_json(message)?));
+            }
+            written.insert(message.id, position);
+        }
+        let removed: Vec<Uuid> = previous
+            .map(|saved| saved.keys().filter(|id| !written.contains_key(id)).copied().collect())
+            .unwrap_or_default();
+
+        let tx = conn.transaction().map_err(to_io)?;
+        tx.execute(
+            "INSERT INTO sessions (id, name, active_leaf_id, last_updated, active_context, usage, generation)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT(id) DO UPDATE SET name = excluded.name, active_leaf_id = excluded.active_leaf_id,
+                last_updated = excluded.last_updated, active_context = excluded.active_context,
+                usage = excluded.usage, generation = excluded.generation",
+            params![
+                session.id,
+                session.name,
+                session.active_leaf_id.map(|id| id.to_string()),
+                session.last_updated.to_rfc3339(),
+                to_json(&session.active_context)?,
+                to_json(&session.usage)?,
+                to_json(&session.generation)?,
+            ],
+        )
+        .map_err(to_io)?;
+        {
+            let mut upsert = tx
+                .prepare(
+                    "INSERT INTO messages (id, session_id, position, data) VALUES (?1, ?2, ?3, ?4)
+                     ON CONFLICT(id) DO UPDATE SET position = excluded.position, data = excluded.data",
+                )
+                .map_err(to_io)?;
+            for (id, position, data) in &changed {
+                upsert.execute(params![id.to_string(), session.id, *position as i64, data]).map_err(to_io)?;
+            }
+            let mut delete = tx.prepare("DELETE FROM messages WHERE id = ?1").map_err(to_io)?;
+            for id in &removed {
+                delete.execute(params![id.to_string()]).map_err(to_io)?;
+            }
+        }
+        tx.commit().map_err(to_io)?;
+
+        if !changed.is_empty() || !removed.is_empty() {
+            tracing::debug!(
+                "Saved session {}: {} messages written, {} removed",
+                session.id, changed.len(), removed.len()
+            );
+        }
+        saved_messages.insert(session.id.clone(), written);
+        changed_messages.remove(&session.id);
+        Ok(())
+    }
+
+    fn message_changed(&self, session_id: &str, message_id: Uuid) {
+        self.lock().changed_messages.entry(session_id.to_string()).or_default().insert(message_id);
+    }
+
+    fn backup_to(&self, path: &Path) -> io::Result<()> {
+        let path = path.to_str().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "backup path is not valid UTF-8"))?;
+        self.lock().conn.execute("VACUUM INTO ?1", params![path]).map_err(to_io)?;
+        Ok(())
+    }
+
+    fn restore_from(&self, path: &Path) -> io::Result<()> {
+        let path = path.to_str().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "backup path is not valid UTF-8"))?;
+        let mut inner = self.lock();
+        // ATTACH can't run inside a transaction, so the copy is wrapped in one after it.
+        inner.conn.execute("ATTACH DATABASE ?1 AS backup", params![path]).map_err(to_io)?;
+        let copied = (|| {
+            let tx = inner.conn.transaction()?;
+            tx.execute_batch(
+                "DELETE FROM main.tool_calls; DELETE FROM main.messages; DELETE FROM main.sessions; DELETE FROM main.meta;
+                 INSERT INTO main.meta SELECT * FROM backup.meta;
+                 INSERT INTO main.sessions SELECT * FROM backup.sessions;
+                 INSERT INTO main.messages SELECT * FROM backup.messages;
+                 INSERT INTO main.tool_calls SELECT * FROM backup.tool_calls;",
+            )?;
+            tx.commit()
+        })();
+        let detached = inner.conn.execute("DETACH DATABASE backup", []);
+        copied.and(detached.map(|_| ())).map_err(to_io)?;
+        // Everything changed, so the next `load` works out what was saved afresh.
+        inner.saved_messages.clear();
+        inner.changed_messages.clear();
+        inner.saved_tool_calls = None;
+        Ok(())
+    }
+
+    fn delete_session(&self, id: &str) -> io::Result<()> {
+        let mut inner = self.lock();
+        // Messages go with it through the foreign key's ON DELETE CASCADE.
+        inner.conn.execute("DELETE FROM sessions WHERE id = ?1", params![id]).map_err(to_io)?;
+        inner.saved_messages.remove(id);
+        inner.changed_messages.remove(id);
+        Ok(())
+    }
+
+    fn save_tool_calls(&self, records: &[ToolCallRecord]) -> io::Result<()> {
+        let mut inner = self.lock();
+        let print = fingerprint(&to_json(&records)?);
+        if inner.saved_tool_calls == Some(print) {
+            return Ok(());
+        }
+        let tx = inner.conn.transaction().map_err(to_io)?;
+        tx.execute("DELETE FROM tool_calls", []).map_err(to_io)?;
+        {
+            let mut insert = tx.prepare("INSERT INTO tool_calls (position, data) VALUES (?1, ?2)").map_err(to_io)?;
+            for (position, record) in records.iter().enumerate() {
+                insert.execute(params![position as i64, to_json(record)?]).map_err(to_io)?;
+            }
+        }
+        tx.commit().map_err(to_io)?;
+        inner.saved_tool_calls = Some(print);
+        Ok(())
+    }
+}
@@ -0,0 +1,26 @@
+//! Token usage and cost totals.
+
+use serde::{Deserialize, Serialize};
+
+use crate::message::TokenUsage;
+
+/// Accumulated token counts and their cost in USD.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(default)]
+pub struct UsageTotals {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cost: f64,
+}
+
+impl UsageTotals {
+    pub fn add(&mut self, usage: &TokenUsage, cost: f64) {
+        self.prompt_tokens += usage.prompt_tokens;
+        self.completion_tokens += usage.completion_tokens;
+        self.cost += cost;
+    }
+
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
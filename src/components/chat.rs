@@ -4,6 +4,7 @@ use uuid::Uuid;
 use dioxus_free_icons::{Icon, icons::fi_icons};
 use std::rc::Rc;
 use dioxus::html::geometry::euclid::Rect;
+use dioxus::html::HasFileData;
 use std::time::Duration;
 use tokio::time::sleep;
 use pulldown_cmark::{html, Options, Parser, Event, Tag, TagEnd};
@@ -14,12 +15,11 @@ use syntect::highlighting::{ThemeSet, Theme};
 use syntect::parsing::SyntaxSet;
 use syntect::html::{styled_line_to_highlighted_html, IncludeBackground};
 use feature_clipboard::copy_to_clipboard;
-use crate::context::prompt_builder::PromptBuilder;
+use crate::context::prompt_builder::{AttachmentData, PromptBuilder};
 use crate::processing::conversation_processor::ConversationProcessor;
-// Define a simple `Message` struct
-use serde::{Deserialize, Serialize};
 use crate::settings::Settings;
-use super::shared::{MessageContent};
+use hobbes_core::message::{Attachment, Message, MessageContent};
+use base64::prelude::*;
 use crate::components::tool_call_display::{PermissionPrompt, ToolCallDisplay};
 use super::link_with_controls::LinkWithControls;
 lazy_static! {
@@ -28,22 +28,62 @@ lazy_static! {
     static ref THEME: &'static Theme = &THEME_SET.themes["base16-ocean.dark"];
 }
 
-#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
-pub struct Message {
-    pub id: uuid::Uuid,
-    pub author: String,
-    pub content: MessageContent,
+/// A file added to the composer that hasn't been sent yet.
+#[derive(Clone, PartialEq)]
+struct PendingAttachment {
+    name: String,
+    mime_type: &'static str,
+    data: Vec<u8>,
+    /// A `data:` URL for image thumbnails.
+    preview: Option<String>,
+}
+
+/// Validates a dropped, pasted or picked file and adds it to the composer.
+fn stage_attachment(
+    mut pending_attachments: Signal<Vec<PendingAttachment>>,
+    mut attachment_error: Signal<Option<String>>,
+    name: String,
+    data: Vec<u8>,
+) {
+    let Some(mime_type) = hobbes_core::message::attachment_mime_type(&name) else {
+        attachment_error.set(Some(format!("`{}` can't be attached. Images, PDFs and text files are supported.", name)));
+        return;
+    };
+    if data.len() > hobbes_core::message::MAX_ATTACHMENT_BYTES {
+        attachment_error.set(Some(format!(
+            "`{}` is too large to attach (limit {}).",
+            name,
+            format_file_size(hobbes_core::message::MAX_ATTACHMENT_BYTES as u64)
+        )));
+        return;
+    }
+    let preview = mime_type
+        .starts_with("image/")
+        .then(|| format!("data:{};base64,{}", mime_type, BASE64_STANDARD.encode(&data)));
+    attachment_error.set(None);
+    pending_attachments.write().push(PendingAttachment { name, mime_type, data, preview });
+}
+
+fn format_file_size(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1024 * 1024 => format!("{:.1} MB", b as f64 / (1024.0 * 1024.0)),
+        b if b >= 1024 => format!("{} KB", b / 1024),
+        b => format!("{} B", b),
+    }
 }
 
 // The main ChatWindow component
 #[component]
 pub fn ChatWindow(on_content_resize: EventHandler<Rect<f64, f64>>, on_interaction: EventHandler<()>, on_toggle_sessions: EventHandler<()>, on_toggle_settings: EventHandler<()>) -> Element {
-    let mut session_state = consume_context::<Signal<crate::session::SessionState>>();
+    let mut session_state = consume_context::<Signal<hobbes_core::session::SessionState>>();
     let settings = use_context::<Signal<Settings>>();
     let mcp_manager = use_context::<Signal<crate::mcp::manager::McpManager>>();
-    let mcp_context = use_context::<Signal<crate::mcp::manager::McpContext>>();
+    let mcp_context = use_context::<Signal<hobbes_core::mcp::McpContext>>();
+    let permission_manager = use_context::<Signal<crate::context::permissions::PermissionManager>>();
     let mut draft = use_signal(|| "".to_string());
     use_context_provider(|| draft);
+    let mut pending_attachments = use_signal(Vec::<PendingAttachment>::new);
+    let attachment_error = use_signal(|| None::<String>);
     let mut container_element = use_signal(|| None as Option<Rc<MountedData>>);
     let mut has_interacted = use_signal(|| false);
     let is_sending = use_signal(|| false);
@@ -119,6 +159,31 @@ pub fn ChatWindow(on_content_resize: EventHandler<Rect<f64, f64>>, on_interactio
         }
     });
 
+    // Forward files pasted anywhere in the window (e.g. screenshots) to the composer.
+    use_hook(move || {
+        spawn(async move {
+            let mut eval = document::eval(r#"
+                document.addEventListener('paste', (event) => {
+                    for (const item of event.clipboardData.items) {
+                        if (item.kind !== 'file') continue;
+                        const file = item.getAsFile();
+                        const reader = new FileReader();
+                        reader.onload = () => dioxus.send({ name: file.name, data: reader.result.split(',')[1] });
+                        reader.readAsDataURL(file);
+                        event.preventDefault();
+                    }
+                });
+            "#);
+            while let Ok(file) = eval.recv::<serde_json::Value>().await {
+                let name = file.get("name").and_then(|n| n.as_str()).unwrap_or("pasted").to_string();
+                match file.get("data").and_then(|d| d.as_str()).map(|d| BASE64_STANDARD.decode(d)) {
+                    Some(Ok(data)) => stage_attachment(pending_attachments, attachment_error, name, data),
+                    _ => tracing::error!("Failed to read pasted file '{}'.", name),
+                }
+            }
+        });
+    });
+
     // Reusable closure for sending a message
     let send_prompt_to_llm = {
         // Capture signals which are all `Copy`
@@ -126,7 +191,7 @@ pub fn ChatWindow(on_content_resize: EventHandler<Rect<f64, f64>>, on_interactio
         let stream_manager = stream_manager;
         let settings = settings;
 
-        move |prompt_data: crate::context::prompt_builder::LlmPrompt, mcp_context: Option<crate::mcp::manager::McpContext>, hobbes_message_id: Uuid| {
+        move |prompt_data: crate::context::prompt_builder::LlmPrompt, mcp_context: Option<hobbes_core::mcp::McpContext>, hobbes_message_id: Uuid| {
             spawn(async move {
                 // Now clone/read them inside the async block
                 let mut is_sending = is_sending;
@@ -174,10 +239,11 @@ pub fn ChatWindow(on_content_resize: EventHandler<Rect<f64, f64>>, on_interactio
                 return;
             }
             let user_message = draft.read().clone();
-            if user_message.is_empty() {
+            if user_message.is_empty() && pending_attachments.read().is_empty() {
                 return;
             }
             draft.set("".to_string());
+            let attachments = std::mem::take(&mut *pending_attachments.write());
             let _ = document::eval(r#"
                 const el = document.getElementById('chat-textarea');
                 if (el) { el.style.height = 'auto'; }
@@ -191,23 +257,71 @@ pub fn ChatWindow(on_content_resize: EventHandler<Rect<f64, f64>>, on_interactio
                 let send_prompt_to_llm = send_prompt_to_llm;
 
                 let hobbes_message_id = Uuid::new_v4();
-                {
+                let session_id = {
                     let mut state = session_state.write();
                     if state.active_session_id.is_empty() {
                         state.create_session();
                     }
+                    state.active_session_id.clone()
+                };
+                // Written off the UI thread and without holding the session lock, since large
+                // files take a while.
+                let stored = tokio::task::spawn_blocking(move || {
+                    attachments
+                        .into_iter()
+                        .filter_map(|pending| match hobbes_core::session::store_attachment(&session_id, &pending.name, pending.mime_type, &pending.data) {
+                            Ok(attachment) => Some(attachment),
+                            Err(e) => {
+                                tracing::error!("Failed to store attachment '{}': {}", pending.name, e);
+                                None
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .await
+                .unwrap_or_else(|e| {
+                    tracing::error!("Failed to store attachments: {}", e);
+                    Vec::new()
+                });
+                {
+                    let mut state = session_state.write();
                     if let Some(session) = state.get_active_session_mut() {
+                        // Attachments go first, so the message text can refer to them.
+                        for attachment in stored {
+                            session.push_message(Message {
+                                id: Uuid::new_v4(),
+                                author: "User".to_string(),
+                                content: MessageContent::Attachment(attachment),
+                                interrupted: false,
+                                error: None,
+                                parent_message_id: None,
+                                created_at: Some(chrono::Utc::now()),
+                                metadata: None,
+                            });
+                        }
                         // Push the user's message
-                        session.messages.push(Message {
-                            id: Uuid::new_v4(),
-                            author: "User".to_string(),
-                            content: MessageContent::Text(user_message.clone()),
-                        });
+                        if !user_message.is_empty() {
+                            session.push_message(Message {
+                                id: Uuid::new_v4(),
+                                author: "User".to_string(),
+                                content: MessageContent::Text(user_message.clone()),
+                                interrupted: false,
+                                error: None,
+                                parent_message_id: None,
+                                created_at: Some(chrono::Utc::now()),
+                                metadata: None,
+                            });
+                        }
                         // Immediately push the empty "Hobbes" message to show the thinking indicator
-                        session.messages.push(Message {
+                        session.push_message(Message {
                             id: hobbes_message_id,
                             author: "Hobbes".to_string(),
                             content: MessageContent::Text("".to_string()),
+                            interrupted: false,
+                            error: None,
+                            parent_message_id: None,
+                            created_at: Some(chrono::Utc::now()),
+                            metadata: None,
                         });
                     }
                 }
@@ -217,7 +331,10 @@ pub fn ChatWindow(on_content_resize: EventHandler<Rect<f64, f64>>, on_interactio
                     let (user_prompt, conversation_summary) = {
                         let mut session_for_processing = session_state.read().get_active_session().cloned().unwrap();
                         let processor = ConversationProcessor::new();
-                        let prompt = processor.process_and_respond(&mut session_for_processing, &settings).await;
+                        let (prompt, summary_usage) = processor.process_and_respond(&mut session_for_processing, &settings).await;
+                        for usage in summary_usage {
+                            stream_manager.record_usage(&session_for_processing.id, &settings, &settings.summary_model, usage);
+                        }
                         (prompt, session_for_processing.active_context.conversation_summary)
                     };
 
@@ -231,14 +348,16 @@ pub fn ChatWindow(on_content_resize: EventHandler<Rect<f64, f64>>, on_interactio
                         }
                     }
 
+                    let read_attachments = AttachmentData::read(session_state.read().get_active_session().unwrap(), &settings);
+                    let attachments = read_attachments.await;
                     let state = session_state.read();
                     let session = state.get_active_session().unwrap();
-                    let last_agent_message = session.messages.iter().filter(|m| m.author == "Hobbes").last().and_then(|m| match &m.content {
+                    let last_agent_message = session.active_path().into_iter().filter(|m| m.author == "Hobbes").last().and_then(|m| match &m.content {
                         MessageContent::Text(text) => Some(text.clone()),
                         _ => None,
                     });
 
-                    let builder = PromptBuilder::new(session, &settings, &state);
+                    let builder = PromptBuilder::new(session, &settings, &state, &attachments);
                     builder.build_prompt(user_prompt, last_agent_message)
                 };
 
@@ -252,6 +371,148 @@ pub fn ChatWindow(on_content_resize: EventHandler<Rect<f64, f64>>, on_interactio
         }
     };
 
+    // Sends the active session's conversation as it stands, streaming the reply into the
+    // (empty) message `hobbes_message_id`.
+    let resend_conversation = move |hobbes_message_id: Uuid| {
+        if let Err(e) = session_state.read().save() {
+            tracing::error!("Failed to save session state: {}", e);
+        }
+        spawn(async move {
+            let Some(read_attachments) = session_state.read().get_active_session().map(|session| AttachmentData::read(session, &settings.read())) else { return };
+            let attachments = read_attachments.await;
+            let (prompt_data, mcp_context) = {
+                let state = session_state.read();
+                let Some(session) = state.get_active_session() else { return };
+                let settings = settings.read();
+                let builder = PromptBuilder::new(session, &settings, &state, &attachments);
+                (builder.build_prompt("".to_string(), None), session.active_context.mcp_tools.clone())
+            };
+            send_prompt_to_llm(prompt_data, mcp_context, hobbes_message_id);
+        });
+    };
+
+    // Replaces a failed Hobbes response with a fresh one and re-sends the conversation.
+    let retry_message = move |failed_message_id: Uuid| {
+        if *is_sending.read() {
+            tracing::warn!("'retry_message' blocked: already sending.");
+            return;
+        }
+        let mut session_state = session_state;
+        let hobbes_message_id = Uuid::new_v4();
+        {
+            let mut state = session_state.write();
+            let Some(session) = state.get_active_session_mut() else { return };
+            let Some(message) = session.messages.iter_mut().find(|m| m.id == failed_message_id) else { return };
+            // A new id gives the retry its own bubble, and keeps any partial text out of the prompt.
+            *message = Message {
+                id: hobbes_message_id,
+                author: "Hobbes".to_string(),
+                content: MessageContent::Text("".to_string()),
+                interrupted: false,
+                error: None,
+                parent_message_id: message.parent_message_id,
+                created_at: Some(chrono::Utc::now()),
+                metadata: None,
+            };
+            if session.active_leaf_id == Some(failed_message_id) {
+                session.active_leaf_id = Some(hobbes_message_id);
+            }
+        }
+        resend_conversation(hobbes_message_id);
+    };
+
+    // Asks for a new response to the last user message. The current response stays on its
+    // own branch.
+    let regenerate_response = move |_: ()| {
+        if *is_sending.read() {
+            tracing::warn!("'regenerate_response' blocked: already sending.");
+            return;
+        }
+        let mut session_state = session_state;
+        let hobbes_message_id = Uuid::new_v4();
+        {
+            let mut state = session_state.write();
+            let Some(session) = state.get_active_session_mut() else { return };
+            let Some(last_user_id) = session.active_path().into_iter().rev().find(|m| m.author == "User").map(|m| m.id) else { return };
+            session.fork_from(Some(last_user_id));
+            session.push_message(Message {
+                id: hobbes_message_id,
+                author: "Hobbes".to_string(),
+                content: MessageContent::Text("".to_string()),
+                interrupted: false,
+                error: None,
+                parent_message_id: None,
+                created_at: Some(chrono::Utc::now()),
+                metadata: None,
+            });
+        }
+        resend_conversation(hobbes_message_id);
+    };
+
+    // Sends an edited copy of a user message as a new branch next to the original.
+    let edit_message = move |(message_id, text): (Uuid, String)| {
+        if *is_sending.read() {
+            tracing::warn!("'edit_message' blocked: already sending.");
+            return;
+        }
+        let mut session_state = session_state;
+        let hobbes_message_id = Uuid::new_v4();
+        {
+            let mut state = session_state.write();
+            let Some(session) = state.get_active_session_mut() else { return };
+            let Some(parent_id) = session.messages.iter().find(|m| m.id == message_id).map(|m| m.parent_message_id) else { return };
+            session.fork_from(parent_id);
+            session.push_message(Message {
+                id: Uuid::new_v4(),
+                author: "User".to_string(),
+                content: MessageContent::Text(text),
+                interrupted: false,
+                error: None,
+                parent_message_id: None,
+                created_at: Some(chrono::Utc::now()),
+                metadata: None,
+            });
+            session.push_message(Message {
+                id: hobbes_message_id,
+                author: "Hobbes".to_string(),
+                content: MessageContent::Text("".to_string()),
+                interrupted: false,
+                error: None,
+                parent_message_id: None,
+                created_at: Some(chrono::Utc::now()),
+                metadata: None,
+            });
+        }
+        resend_conversation(hobbes_message_id);
+    };
+
+    // Shows the branch through `message_id`.
+    let select_branch = move |message_id: Uuid| {
+        if *is_sending.read() {
+            return;
+        }
+        let mut session_state = session_state;
+        let mut state = session_state.write();
+        let Some(session) = state.get_active_session_mut() else { return };
+        session.select_branch(message_id);
+        if let Err(e) = state.save() {
+            tracing::error!("Failed to save session state after switching branches: {}", e);
+        }
+    };
+
+    // Continues the conversation from `message_id`; the next message starts a new branch.
+    let fork_from = move |message_id: Uuid| {
+        if *is_sending.read() {
+            return;
+        }
+        let mut session_state = session_state;
+        let mut state = session_state.write();
+        let Some(session) = state.get_active_session_mut() else { return };
+        session.fork_from(Some(message_id));
+        if let Err(e) = state.save() {
+            tracing::error!("Failed to save session state after forking: {}", e);
+        }
+    };
 
     let root_classes = "relative flex flex-col bg-gray-900 text-gray-100 rounded-lg shadow-2xl h-full w-full flex-1 min-h-0";
 
@@ -259,6 +520,32 @@ pub fn ChatWindow(on_content_resize: EventHandler<Rect<f64, f64>>, on_interactio
         div {
             class: "{root_classes}",
             onmounted: move |cx| container_element.set(Some(cx.data())),
+            // Files dropped anywhere on the window are attached to the next message.
+            ondragover: move |event| event.prevent_default(),
+            ondrop: move |event| {
+                event.prevent_default();
+                if let Some(file_engine) = event.files() {
+                    spawn(async move {
+                        for path in file_engine.files() {
+                            let name = std::path::Path::new(&path)
+                                .file_name()
+                                .map(|name| name.to_string_lossy().to_string())
+                                .unwrap_or_else(|| path.clone());
+                            match file_engine.read_file(&path).await {
+                                Some(data) => stage_attachment(pending_attachments, attachment_error, name, data),
+                                None => tracing::error!("Failed to read dropped file '{}'.", path),
+                            }
+                        }
+                    });
+                }
+            },
+            // Escape stops the in-flight response from anywhere in the chat window.
+            onkeydown: move |event| {
+                if event.key() == Key::Escape && is_sending() {
+                    event.prevent_default();
+                    stream_manager.cancel();
+                }
+            },
             // This div is the new message list container
             div { // This new div creates a relative container for the message list and the scroll button
                 class: "relative flex-1 min-h-0",
@@ -295,7 +582,7 @@ pub fn ChatWindow(on_content_resize: EventHandler<Rect<f64, f64>>, on_interactio
 
                         // If user scrolls to the top, load more messages while preserving scroll position
                         if is_at_top {
-                            let total_messages = session_state.read().get_active_session().map_or(0, |s| s.messages.len());
+                            let total_messages = session_state.read().get_active_session().map_or(0, |s| s.active_path().len());
                             if *visible_message_count.read() < total_messages {
                                 // 1. Get current scroll state BEFORE adding new messages
                                 let _ = document::eval(r#"
@@ -330,8 +617,17 @@ pub fn ChatWindow(on_content_resize: EventHandler<Rect<f64, f64>>, on_interactio
                 {
                     let state = session_state.read();
                     if let Some(session) = state.sessions.get(&state.active_session_id) {
-                        let total_messages = session.messages.len();
-                        let messages_to_render = session.messages.iter().skip(total_messages.saturating_sub(*visible_message_count.read())).collect::<Vec<_>>();
+                        let path = session.active_path();
+                        let total_messages = path.len();
+                        let last_message_id = path.last().map(|m| m.id);
+                        // Each message with the ids of its alternatives, for the branch switcher.
+                        let messages_to_render = path
+                            .into_iter()
+                            .skip(total_messages.saturating_sub(*visible_message_count.read()))
+                            .map(|message| (message, session.sibling_ids(&message.id)))
+                            .collect::<Vec<_>>();
+                        // The active leaf has replies when the user has gone back to fork from it.
+                        let is_forking = session.active_leaf_id.is_some_and(|leaf| session.messages.iter().any(|m| m.parent_message_id == Some(leaf)));
 
                         if session.messages.is_empty() {
                             rsx! { WelcomeMessage {} }
@@ -350,12 +646,23 @@ pub fn ChatWindow(on_content_resize: EventHandler<Rect<f64, f64>>, on_interactio
                                         }
                                     }
                                 }
-                                for message in messages_to_render {
+                                for (message, siblings) in messages_to_render {
                                     match &message.content {
                                         MessageContent::Text(_) => rsx! {
                                             MessageBubble {
                                                 key: "{message.id}",
-                                                message: message.clone()
+                                                message: message.clone(),
+                                                // Only the latest response can be retried without rewriting history.
+                                                can_retry: Some(message.id) == last_message_id,
+                                                on_retry: retry_message,
+                                                can_regenerate: Some(message.id) == last_message_id && !is_sending(),
+                                                on_regenerate: regenerate_response,
+                                                can_edit: !is_sending(),
+                                                on_edit: edit_message,
+                                                siblings: siblings.clone(),
+                                                on_select_branch: select_branch,
+                                                can_fork: Some(message.id) != last_message_id && !is_sending(),
+                                                on_fork: fork_from
                                             }
                                         },
                                         MessageContent::ToolCall(tool_call) => {
@@ -379,11 +686,22 @@ pub fn ChatWindow(on_content_resize: EventHandler<Rect<f64, f64>>, on_interactio
                                                         div {
                                                             class: "{author_classes}",
                                                             "{message.author}"
+                                                            BranchNav {
+                                                                message_id: message.id,
+                                                                siblings: siblings.clone(),
+                                                                on_select: select_branch
+                                                            }
                                                         }
                                                     }
                                                 }
                                             }
                                         }
+                                        MessageContent::Attachment(attachment) => rsx! {
+                                            AttachmentBubble {
+                                                key: "{message.id}",
+                                                attachment: attachment.clone()
+                                            }
+                                        },
                                         MessageContent::PermissionRequest(tool_call) => {
                                             let container_classes = "flex justify-start";
                                             let author_classes = "text-xs text-gray-500 mt-1 px-2 text-left";
@@ -404,6 +722,22 @@ pub fn ChatWindow(on_content_resize: EventHandler<Rect<f64, f64>>, on_interactio
                                         }
                                     }
                                 }
+                                if is_forking {
+                                    div {
+                                        class: "flex items-center justify-center space-x-3 text-xs text-gray-400",
+                                        Icon { width: 14, height: 14, icon: fi_icons::FiGitBranch }
+                                        span { "Your next message starts a new branch from here." }
+                                        button {
+                                            class: "text-purple-400 hover:text-purple-300 focus:outline-none",
+                                            onclick: move |_| {
+                                                if let Some(leaf) = last_message_id {
+                                                    select_branch(leaf);
+                                                }
+                                            },
+                                            "Back to latest"
+                                        }
+                                    }
+                                }
                             }
                         }
                     } else {
@@ -431,6 +765,33 @@ pub fn ChatWindow(on_content_resize: EventHandler<Rect<f64, f64>>, on_interactio
             div {
                 class: "bg-gray-900 p-4 border-t border-gray-700", // Removed absolute positioning
                 onmousedown: |e| e.stop_propagation(),
+                if !pending_attachments.read().is_empty() {
+                    div {
+                        class: "mb-3 flex flex-wrap gap-2",
+                        for (index, pending) in pending_attachments.read().iter().enumerate() {
+                            div {
+                                key: "{index}-{pending.name}",
+                                class: "relative group",
+                                if let Some(preview) = &pending.preview {
+                                    img { class: "h-16 w-16 object-cover rounded-lg border border-gray-700", src: "{preview}", alt: "{pending.name}" }
+                                } else {
+                                    FileChip { name: pending.name.clone(), mime_type: pending.mime_type.to_string(), size: pending.data.len() as u64 }
+                                }
+                                button {
+                                    class: "absolute -top-2 -right-2 p-0.5 rounded-full bg-gray-900 text-gray-400 hover:text-white border border-gray-700",
+                                    title: "Remove",
+                                    onclick: move |_| {
+                                        pending_attachments.write().remove(index);
+                                    },
+                                    Icon { width: 12, height: 12, icon: fi_icons::FiX }
+                                }
+                            }
+                        }
+                    }
+                }
+                if let Some(error) = attachment_error.read().as_ref() {
+                    div { class: "mb-2 text-xs text-red-400", "{error}" }
+                }
                 div {
                     class: "flex items-center space-x-3",
                     button {
@@ -451,6 +812,31 @@ pub fn ChatWindow(on_content_resize: EventHandler<Rect<f64, f64>>, on_interactio
                             icon: fi_icons::FiSettings
                         }
                     }
+                    button {
+                        class: "p-2 rounded-full text-gray-400 hover:bg-gray-700 hover:text-white focus:outline-none focus:ring-2 focus:ring-gray-600",
+                        title: "Attach files",
+                        onclick: move |_| {
+                            spawn(async move {
+                                let extensions: Vec<&str> = hobbes_core::message::ATTACHMENT_TYPES.iter().map(|(ext, _)| *ext).collect();
+                                let Some(files) = rfd::AsyncFileDialog::new()
+                                    .add_filter("Images, PDFs and text files", &extensions)
+                                    .pick_files()
+                                    .await
+                                else {
+                                    return;
+                                };
+                                for file in files {
+                                    let data = file.read().await;
+                                    stage_attachment(pending_attachments, attachment_error, file.file_name(), data);
+                                }
+                            });
+                        },
+                        Icon {
+                            width: 20,
+                            height: 20,
+                            icon: fi_icons::FiPaperclip
+                        }
+                    }
                     textarea {
                         id: "chat-textarea",
                         class: "flex-1 py-2 px-4 rounded-xl bg-gray-800 border border-gray-700 text-gray-100 placeholder-gray-500 focus:outline-none focus:ring-2 focus:ring-purple-500 resize-none overflow-y-hidden",
@@ -504,6 +890,12 @@ pub fn ChatWindow(on_content_resize: EventHandler<Rect<f64, f64>>, on_interactio
                                                     mcp_manager_reader.get_mcp_context().await
                                                 };
 
+                                                let read_attachments = session_state.read().get_active_session().map(|session| AttachmentData::read(session, &settings.read()));
+                                                let attachments = match read_attachments {
+                                                    Some(read_attachments) => read_attachments.await,
+                                                    None => AttachmentData::default(),
+                                                };
+
                                                 // Now, read the state and build the context string for debugging.
                                                 let context_string = {
                                                     let state = session_state.read();
@@ -517,7 +909,7 @@ pub fn ChatWindow(on_content_resize: EventHandler<Rect<f64, f64>>, on_interactio
 
                                                         // Build the prompt from the modified clone to show an accurate preview.
                                                         let settings_reader = settings.read();
-                                                        let builder = PromptBuilder::new(&session_for_debug, &settings_reader, &state);
+                                                        let builder = PromptBuilder::new(&session_for_debug, &settings_reader, &state, &attachments);
                                                         // Note: This debug view might not be perfect after the refactor,
                                                         // but it's better to show the raw prompt struct than to crash.
                                                         let prompt_data = builder.build_prompt("[DEBUG USER MESSAGE]".to_string(), None);
@@ -550,17 +942,45 @@ pub fn ChatWindow(on_content_resize: EventHandler<Rect<f64, f64>>, on_interactio
                             icon: fi_icons::FiPlus
                         }
                     }
-                    button {
-                        class: "px-5 py-2 bg-purple-600 rounded-full text-white font-semibold hover:bg-purple-700 focus:outline-none focus:ring-2 focus:ring-purple-500 focus:ring-opacity-50 transition-colors disabled:bg-gray-500",
-                        disabled: mcp_context.read().servers.is_empty(),
-                        onclick: move |_| {
-                            if !*has_interacted.read() {
-                                on_interaction.call(());
-                                has_interacted.set(true);
+                    if is_sending() {
+                        button {
+                            class: "px-5 py-2 bg-red-600 rounded-full text-white font-semibold hover:bg-red-700 focus:outline-none focus:ring-2 focus:ring-red-500 focus:ring-opacity-50 transition-colors",
+                            title: "Stop (Esc)",
+                            onclick: move |_| stream_manager.cancel(),
+                            "Stop"
+                        }
+                    } else {
+                        button {
+                            class: "px-5 py-2 bg-purple-600 rounded-full text-white font-semibold hover:bg-purple-700 focus:outline-none focus:ring-2 focus:ring-purple-500 focus:ring-opacity-50 transition-colors disabled:bg-gray-500",
+                            disabled: mcp_context.read().servers.is_empty(),
+                            onclick: move |_| {
+                                if !*has_interacted.read() {
+                                    on_interaction.call(());
+                                    has_interacted.set(true);
+                                }
+                                send_message()
+                            },
+                            "Send"
+                        }
+                    }
+                }
+                {
+                    let session_usage = session_state.read().get_active_session().map(|s| s.usage).unwrap_or_default();
+                    let today_usage = permission_manager.read().today_usage();
+                    let session_cost = format!("{:.4}", session_usage.cost);
+                    let today_cost = format!("{:.2}", today_usage.cost);
+                    rsx! {
+                        div {
+                            class: "mt-2 flex justify-end space-x-3 text-xs text-gray-500",
+                            span {
+                                title: "{session_usage.prompt_tokens} prompt + {session_usage.completion_tokens} completion tokens",
+                                "{session_usage.total_tokens()} tokens · ${session_cost} this session"
                             }
-                            send_message()
-                        },
-                        "Send"
+                            span {
+                                title: "{today_usage.total_tokens()} tokens today",
+                                "${today_cost} today"
+                            }
+                        }
                     }
                 }
             }
@@ -642,7 +1062,19 @@ pub fn CodeBlock(code: String, lang: String) -> Element {
 
 // Sub-component for styling individual messages
 #[component]
-fn MessageBubble(message: Message) -> Element {
+fn MessageBubble(
+    message: Message,
+    can_retry: bool,
+    on_retry: EventHandler<Uuid>,
+    can_regenerate: bool,
+    on_regenerate: EventHandler<()>,
+    can_edit: bool,
+    on_edit: EventHandler<(Uuid, String)>,
+    siblings: Vec<Uuid>,
+    on_select_branch: EventHandler<Uuid>,
+    can_fork: bool,
+    on_fork: EventHandler<Uuid>,
+) -> Element {
     let is_user = message.author == "User";
 
     // This component now specifically handles Text content.
@@ -652,6 +1084,8 @@ fn MessageBubble(message: Message) -> Element {
         let stream_manager = consume_context::<StreamManagerContext>();
         let mut content = use_signal(|| text_content.clone());
         let mut copied = use_signal(|| false);
+        let mut editing = use_signal(|| false);
+        let mut edit_text = use_signal(String::new);
 
         // This effect runs once when the component is created.
         // If it's a streaming Hobbes message, it takes the stream and updates its local state.
@@ -669,7 +1103,7 @@ fn MessageBubble(message: Message) -> Element {
             }
         });
 
-        let is_thinking = !is_user && content.read().is_empty();
+        let is_thinking = !is_user && content.read().is_empty() && !message.interrupted && message.error.is_none();
 
         let bubble_classes = if is_user {
             "bg-purple-600 text-white self-end ml-auto"
@@ -797,6 +1231,14 @@ fn MessageBubble(message: Message) -> Element {
             elements
         });
 
+        let details = message_details(&message);
+        let sent_at = message.created_at
+            .map(|created_at| created_at.with_timezone(&chrono::Local).format("%H:%M").to_string());
+        let truncation = message.metadata.as_ref()
+            .and_then(|metadata| metadata.stop_reason.as_ref())
+            .filter(|reason| reason.is_truncated())
+            .map(|reason| reason.label().to_string());
+
         let button_position_classes = if is_user {
             "absolute bottom-[-10px] left-[-10px]"
         } else {
@@ -807,10 +1249,43 @@ fn MessageBubble(message: Message) -> Element {
             div {
                 class: "{container_classes} w-full",
                 div {
-                    class: "flex flex-col max-w-2/3 min-w-0",
+                    class: "group flex flex-col max-w-2/3 min-w-0",
                     div {
                         class: "relative group px-4 py-2 rounded-2xl {bubble_classes}",
-                        if is_thinking {
+                        if editing() {
+                            div {
+                                class: "flex flex-col space-y-2 min-w-64",
+                                textarea {
+                                    class: "w-full p-2 rounded-md bg-purple-800 text-white text-sm focus:outline-none focus:ring-2 focus:ring-purple-300",
+                                    rows: "3",
+                                    value: "{edit_text}",
+                                    oninput: move |event| edit_text.set(event.value()),
+                                    onkeydown: move |event| {
+                                        if event.key() == Key::Escape {
+                                            event.stop_propagation();
+                                            editing.set(false);
+                                        }
+                                    }
+                                }
+                                div {
+                                    class: "flex justify-end space-x-2",
+                                    button {
+                                        class: "px-3 py-1 rounded-full bg-gray-800 text-gray-200 text-xs font-semibold hover:bg-gray-600",
+                                        onclick: move |_| editing.set(false),
+                                        "Cancel"
+                                    }
+                                    button {
+                                        class: "px-3 py-1 rounded-full bg-white text-purple-700 text-xs font-semibold hover:bg-gray-200 disabled:opacity-50",
+                                        disabled: !can_edit || edit_text.read().trim().is_empty(),
+                                        onclick: move |_| {
+                                            editing.set(false);
+                                            on_edit.call((message.id, edit_text.read().clone()));
+                                        },
+                                        "Save & Send"
+                                    }
+                                }
+                            }
+                        } else if is_thinking {
                             ThinkingIndicator {}
                         } else {
                             div {
@@ -820,6 +1295,21 @@ fn MessageBubble(message: Message) -> Element {
                                 }
                             }
                         }
+                        if let Some(error) = &message.error {
+                            div {
+                                class: "flex items-start space-x-2 text-sm text-red-300",
+                                class: if !content.read().is_empty() { "mt-2 pt-2 border-t border-gray-600" },
+                                Icon { width: 16, height: 16, class: "flex-shrink-0 mt-0.5", icon: fi_icons::FiAlertTriangle }
+                                span { class: "flex-1 min-w-0 break-words", "{error}" }
+                                if can_retry {
+                                    button {
+                                        class: "px-3 py-1 rounded-full bg-gray-800 text-gray-200 text-xs font-semibold hover:bg-gray-600 focus:outline-none focus:ring-2 focus:ring-purple-500",
+                                        onclick: move |_| on_retry.call(message.id),
+                                        "Retry"
+                                    }
+                                }
+                            }
+                        }
                         if !content.read().is_empty() {
                             button {
                                 class: "{button_position_classes} p-1 rounded-full text-gray-400 bg-gray-900 bg-opacity-75 hover:bg-gray-700 hover:text-white transition-all opacity-0 group-hover:opacity-100",
@@ -843,7 +1333,49 @@ fn MessageBubble(message: Message) -> Element {
                     }
                     div {
                         class: "{author_classes}",
+                        title: "{details}",
                         "{message.author}"
+                        if let Some(sent_at) = sent_at {
+                            span { class: "ml-2 opacity-0 group-hover:opacity-100 transition-opacity", "{sent_at}" }
+                        }
+                        if let Some(truncation) = truncation {
+                            span { class: "ml-2 italic text-yellow-500", "{truncation}" }
+                        }
+                        if message.interrupted {
+                            span { class: "ml-2 italic text-yellow-500", "Stopped" }
+                        }
+                        BranchNav {
+                            message_id: message.id,
+                            siblings: siblings.clone(),
+                            on_select: on_select_branch
+                        }
+                        if is_user && can_edit && !editing() {
+                            button {
+                                class: "ml-2 text-gray-500 hover:text-white align-middle",
+                                title: "Edit and resend",
+                                onclick: move |_| {
+                                    edit_text.set(content.read().clone());
+                                    editing.set(true);
+                                },
+                                Icon { width: 12, height: 12, icon: fi_icons::FiEdit2 }
+                            }
+                        }
+                        if can_fork {
+                            button {
+                                class: "ml-2 text-gray-500 hover:text-white align-middle",
+                                title: "Branch from here",
+                                onclick: move |_| on_fork.call(message.id),
+                                Icon { width: 12, height: 12, icon: fi_icons::FiGitBranch }
+                            }
+                        }
+                        if !is_user && can_regenerate && !is_thinking {
+                            button {
+                                class: "ml-2 text-gray-500 hover:text-white align-middle",
+                                title: "Regenerate response",
+                                onclick: move |_| on_regenerate.call(()),
+                                Icon { width: 12, height: 12, icon: fi_icons::FiRefreshCw }
+                            }
+                        }
                     }
                 }
             }
@@ -855,6 +1387,115 @@ fn MessageBubble(message: Message) -> Element {
 }
 
 
+/// The hover tooltip for a message's author line: when it was sent and, for model
+/// responses, the model, token counts and why generation stopped.
+fn message_details(message: &Message) -> String {
+    let mut lines = Vec::new();
+    match message.created_at {
+        Some(created_at) => lines.push(created_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string()),
+        None => lines.push("Sent before timestamps were recorded".to_string()),
+    }
+    if let Some(metadata) = &message.metadata {
+        lines.push(format!("Model: {}", metadata.model_name));
+        if metadata.prompt_token_count > 0 || metadata.token_count > 0 {
+            lines.push(format!("Tokens: {} prompt, {} completion", metadata.prompt_token_count, metadata.token_count));
+        }
+        if let Some(reason) = &metadata.stop_reason {
+            lines.push(format!("Finished: {}", reason.label()));
+        }
+    }
+    lines.join("\n")
+}
+
+/// "< 2/3 >" navigation between a message and its alternatives. Renders nothing when the
+/// message has none.
+#[component]
+fn BranchNav(message_id: Uuid, siblings: Vec<Uuid>, on_select: EventHandler<Uuid>) -> Element {
+    if siblings.len() < 2 {
+        return rsx! {};
+    }
+    let position = siblings.iter().position(|id| *id == message_id).unwrap_or(0);
+    let previous = position.checked_sub(1).map(|i| siblings[i]);
+    let next = siblings.get(position + 1).copied();
+    rsx! {
+        span {
+            class: "inline-flex items-center ml-2 space-x-1 text-gray-400 align-middle",
+            button {
+                class: "hover:text-white disabled:opacity-30",
+                title: "Previous branch",
+                disabled: previous.is_none(),
+                onclick: move |_| {
+                    if let Some(id) = previous {
+                        on_select.call(id);
+                    }
+                },
+                Icon { width: 12, height: 12, icon: fi_icons::FiChevronLeft }
+            }
+            span { "{position + 1}/{siblings.len()}" }
+            button {
+                class: "hover:text-white disabled:opacity-30",
+                title: "Next branch",
+                disabled: next.is_none(),
+                onclick: move |_| {
+                    if let Some(id) = next {
+                        on_select.call(id);
+                    }
+                },
+                Icon { width: 12, height: 12, icon: fi_icons::FiChevronRight }
+            }
+        }
+    }
+}
+
+/// An attached file in the message list: images as thumbnails, other files as chips.
+#[component]
+fn AttachmentBubble(attachment: Attachment) -> Element {
+    let preview = use_hook(|| {
+        if !attachment.is_image() {
+            return None;
+        }
+        match std::fs::read(&attachment.path) {
+            Ok(data) => Some(format!("data:{};base64,{}", attachment.mime_type, BASE64_STANDARD.encode(data))),
+            Err(e) => {
+                tracing::error!("Failed to read attachment {:?}: {}", attachment.path, e);
+                None
+            }
+        }
+    });
+
+    rsx! {
+        div {
+            class: "flex justify-end w-full",
+            div {
+                class: "flex flex-col items-end max-w-2/3 min-w-0",
+                if let Some(src) = preview {
+                    img { class: "max-h-48 rounded-2xl border border-gray-700", src: "{src}", alt: "{attachment.file_name}", title: "{attachment.file_name}" }
+                } else {
+                    FileChip { name: attachment.file_name.clone(), mime_type: attachment.mime_type.clone(), size: attachment.size }
+                }
+                div { class: "text-xs text-gray-500 mt-1 px-2 text-right", "User" }
+            }
+        }
+    }
+}
+
+#[component]
+fn FileChip(name: String, mime_type: String, size: u64) -> Element {
+    rsx! {
+        div {
+            class: "flex items-center space-x-2 px-3 py-2 rounded-lg bg-gray-800 border border-gray-700 text-gray-200",
+            title: "{mime_type}",
+            if hobbes_core::message::is_text_mime_type(&mime_type) {
+                Icon { width: 16, height: 16, icon: fi_icons::FiFileText }
+            } else {
+                Icon { width: 16, height: 16, icon: fi_icons::FiFile }
+            }
+            span { class: "text-sm truncate max-w-[12rem]", "{name}" }
+            span { class: "text-xs text-gray-500", "{format_file_size(size)}" }
+        }
+    }
+}
+
 #[component]
 fn ThinkingIndicator() -> Element {
     rsx! {
@@ -8,7 +8,7 @@ use feature_clipboard::copy_to_clipboard;
 pub fn LinkWithControls(href: String, text_html: String) -> Element {
     let mut copied = use_signal(|| false);
     let mut draft = consume_context::<Signal<String>>();
-    let mcp_context = use_context::<Signal<crate::mcp::manager::McpContext>>();
+    let mcp_context = use_context::<Signal<hobbes_core::mcp::McpContext>>();
 
     let fetch_tool_available = use_memo(move || {
         mcp_context.read().servers.iter().any(|s| s.name == "fetch")
@@ -1,255 +1,377 @@
+//! Provider-neutral interface to the chat and summary LLMs.
+//!
+//! The rest of the application builds an `LlmPrompt` and consumes `StreamMessage`s;
+//! the concrete wire formats live in the provider submodules.
+use futures::future::BoxFuture;
 use serde::{Deserialize, Serialize};
-use reqwest::Client;
-use futures_util::StreamExt;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc;
 
-use crate::components::shared::ToolCall;
 use crate::components::shared::StreamMessage;
-const BASE_API_URL: &str = "https://generativelanguage.googleapis.com/v1beta/models";
+use crate::context::prompt_builder::LlmPrompt;
+use crate::settings::Settings;
+pub use hobbes_core::error::LlmError;
+use hobbes_core::mcp::McpContext;
+use hobbes_core::message::{TokenUsage, ToolCall};
+use hobbes_core::session::{ConversationSummary, ConversationSummaryEntities};
+
+pub mod anthropic;
+pub mod gemini;
+pub mod ollama;
+pub mod openai;
+
+/// The author of a message in a provider-neutral conversation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LlmRole {
+    User,
+    Assistant,
+}
 
-use crate::session::Tool;
+/// A single piece of content within an `LlmMessage`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LlmPart {
+    Text(String),
+    /// A function call previously requested by the model.
+    ToolCall {
+        id: String,
+        name: String,
+        args: serde_json::Value,
+    },
+    /// The outcome of executing the `ToolCall` with the same `id`.
+    ToolResult {
+        id: String,
+        name: String,
+        response: String,
+        is_error: bool,
+    },
+    /// An attached image or document, base64-encoded.
+    InlineData {
+        name: String,
+        mime_type: String,
+        data: String,
+    },
+}
 
-#[derive(Serialize, Deserialize)]
-pub(crate) struct GeminiRequest {
-    contents: Vec<Content>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    tools: Option<Vec<Tool>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    system_instruction: Option<SystemInstruction>,
+impl LlmPart {
+    pub fn is_empty(&self) -> bool {
+        match self {
+            LlmPart::Text(text) => text.is_empty(),
+            LlmPart::ToolCall { .. } | LlmPart::ToolResult { .. } | LlmPart::InlineData { .. } => false,
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize)]
-#[derive(Debug)]
-pub struct Content {
-    pub role: String,
-    pub parts: Vec<Part>,
+/// A single turn in the conversation history sent to a provider.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LlmMessage {
+    pub role: LlmRole,
+    pub parts: Vec<LlmPart>,
 }
 
-#[derive(Serialize, Deserialize)]
-#[derive(Debug)]
-pub struct Part {
-    pub text: String,
+impl LlmMessage {
+    pub fn text(role: LlmRole, text: String) -> Self {
+        Self { role, parts: vec![LlmPart::Text(text)] }
+    }
 }
 
-#[derive(Deserialize, Debug)]
-struct GeminiErrorResponse {
-    error: GeminiError,
+/// The LLM backends Hobbes knows how to talk to.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderKind {
+    #[default]
+    Gemini,
+    /// Any server implementing the OpenAI chat completions API.
+    #[serde(rename = "openai")]
+    OpenAi,
+    Anthropic,
+    /// A model served by a local Ollama instance.
+    Ollama,
 }
 
-#[derive(Deserialize, Debug)]
-struct GeminiError {
-    message: String,
+impl ProviderKind {
+    pub const ALL: [ProviderKind; 4] = [ProviderKind::Gemini, ProviderKind::OpenAi, ProviderKind::Anthropic, ProviderKind::Ollama];
+
+    /// A stable identifier, matching the serialized form.
+    pub fn id(&self) -> &'static str {
+        match self {
+            ProviderKind::Gemini => "gemini",
+            ProviderKind::OpenAi => "openai",
+            ProviderKind::Anthropic => "anthropic",
+            ProviderKind::Ollama => "ollama",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ProviderKind::Gemini => "Google Gemini",
+            ProviderKind::OpenAi => "OpenAI-compatible",
+            ProviderKind::Anthropic => "Anthropic",
+            ProviderKind::Ollama => "Ollama (local)",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.id() == id)
+    }
 }
 
-#[derive(Serialize, Deserialize)]
-#[derive(Debug)]
-pub struct SystemInstruction {
-    pub parts: Vec<Part>,
+/// Builds the error for a failed HTTP response. `error_message` extracts the provider's
+/// description of the problem from the response body.
+async fn error_from_response(response: reqwest::Response, error_message: fn(&str) -> String) -> LlmError {
+    let status = response.status();
+    let retry_after = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_retry_after);
+    let body_text = response.text().await.unwrap_or_else(|_| "Failed to read error body".to_string());
+    LlmError::from_status(status.as_u16(), error_message(&body_text), retry_after)
 }
 
-#[derive(Deserialize, Debug)]
-struct GeminiResponse {
-    candidates: Vec<Candidate>,
+/// Classifies a failed request. A `From` impl isn't possible, as neither type is defined here.
+/// The URL is left out of the message, since it can carry credentials and the message is
+/// shown in the chat and saved with the session.
+pub(crate) fn request_error(e: reqwest::Error) -> LlmError {
+    let e = e.without_url();
+    if e.is_timeout() {
+        LlmError::Timeout { message: e.to_string() }
+    } else if e.is_decode() {
+        LlmError::Parse { message: e.to_string() }
+    } else if let Some(status) = e.status() {
+        LlmError::from_status(status.as_u16(), e.to_string(), None)
+    } else {
+        LlmError::Network { message: e.to_string() }
+    }
 }
 
-#[derive(Deserialize, Debug)]
-#[serde(rename_all = "camelCase")]
-struct Candidate {
-    content: ContentResponse,
-    finish_reason: Option<String>,
+/// Parses a `Retry-After` header, which is either a number of seconds or an HTTP date.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    Some((date.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().unwrap_or(Duration::ZERO))
 }
 
-#[derive(Deserialize, Debug)]
-struct ContentResponse {
-    #[serde(default)]
-    parts: Vec<PartResponse>,
+const MAX_ATTEMPTS: u32 = 4;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+/// Longer waits are reported to the user rather than leaving the chat silently stalled.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// How long to wait after `attempt` failed attempts before trying again, or `None` to give up.
+fn backoff_delay(attempt: u32, retry_after: Option<Duration>) -> Option<Duration> {
+    if attempt >= MAX_ATTEMPTS {
+        return None;
+    }
+    let delay = retry_after.unwrap_or(INITIAL_BACKOFF * 2u32.pow(attempt.saturating_sub(1)));
+    (delay <= MAX_BACKOFF).then_some(delay)
 }
 
-#[derive(Deserialize, Debug, Clone)]
-#[serde(rename_all = "camelCase")]
-pub struct FunctionCall {
-    pub name: String,
-    pub args: serde_json::Value,
+/// Sends `request`, retrying rate limits, server errors and dropped connections with
+/// exponential backoff (or as long as the provider's `Retry-After` asks). Returns the
+/// response once it has a success status.
+pub(crate) async fn send_with_retry(
+    request: reqwest::RequestBuilder,
+    error_message: fn(&str) -> String,
+) -> Result<reqwest::Response, LlmError> {
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let result = request
+            .try_clone()
+            .expect("JSON request bodies can always be cloned")
+            .send()
+            .await;
+        let error = match result {
+            Ok(response) if response.status().is_success() => return Ok(response),
+            Ok(response) => error_from_response(response, error_message).await,
+            Err(e) => request_error(e),
+        };
+        let delay = if error.is_retryable() { backoff_delay(attempt, error.retry_after()) } else { None };
+        let Some(delay) = delay else { return Err(error) };
+        tracing::warn!("{} Retrying in {:?} (attempt {} of {}).", error, delay, attempt + 1, MAX_ATTEMPTS);
+        tokio::time::sleep(delay).await;
+    }
 }
 
-#[derive(Deserialize, Debug)]
-#[serde(rename_all = "camelCase")]
-struct PartResponse {
-    #[serde(default)]
-    text: String,
-    function_call: Option<FunctionCall>,
+/// A backend capable of streaming chat responses (including tool calls) and
+/// producing one-shot completions.
+pub trait LlmProvider: Send + Sync {
+    /// Streams the response to `prompt` into `tx`. Function calls requested by the model
+    /// are resolved against `mcp_context` and sent as `StreamMessage::ToolCall`.
+    fn stream_chat(
+        &self,
+        model: String,
+        prompt: LlmPrompt,
+        tx: mpsc::UnboundedSender<StreamMessage>,
+        mcp_context: Option<McpContext>,
+    ) -> BoxFuture<'static, ()>;
+
+    /// Sends `prompt` and returns the full text of the response.
+    fn complete(&self, model: String, prompt: LlmPrompt) -> BoxFuture<'static, Result<Completion, LlmError>>;
 }
 
-use crate::context::prompt_builder::LlmPrompt;
+/// The response to a one-shot completion.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    pub text: String,
+    /// The tokens the request used, if the provider reported them.
+    pub usage: Option<TokenUsage>,
+}
 
-pub async fn generate_content_stream(
-    api_key: String,
-    model: String,
-    prompt_data: LlmPrompt,
-    tx: mpsc::UnboundedSender<StreamMessage>,
-    mcp_context: Option<crate::mcp::manager::McpContext>,
-) {
-    const MAX_RETRIES: u32 = 2;
-    let client = Client::builder()
+/// Returns the provider configured for `kind`, using the credentials from `settings`.
+pub fn provider_for(settings: &Settings, kind: ProviderKind) -> Arc<dyn LlmProvider> {
+    match kind {
+        ProviderKind::Gemini => {
+            let api_key = settings.api_key.clone().or_else(|| std::env::var("GEMINI_API_KEY").ok());
+            Arc::new(gemini::GeminiProvider::new(api_key))
+        }
+        ProviderKind::OpenAi => {
+            let api_key = settings.openai.api_key.clone().or_else(|| std::env::var("OPENAI_API_KEY").ok());
+            Arc::new(openai::OpenAiProvider::new(settings.openai.base_url.clone(), api_key))
+        }
+        ProviderKind::Anthropic => {
+            let api_key = settings.anthropic.api_key.clone().or_else(|| std::env::var("ANTHROPIC_API_KEY").ok());
+            Arc::new(anthropic::AnthropicProvider::new(api_key))
+        }
+        ProviderKind::Ollama => Arc::new(ollama::OllamaProvider::new(settings.ollama.base_url.clone())),
+    }
+}
+
+/// Accumulates raw bytes from a streaming response and yields complete, trimmed lines.
+/// Used for both SSE (`data: ...`) and newline-delimited JSON streams.
+#[derive(Default)]
+pub(crate) struct LineBuffer {
+    buffer: Vec<u8>,
+}
+
+impl LineBuffer {
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(bytes);
+        let mut lines = Vec::new();
+        while let Some(i) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line_bytes = self.buffer.drain(..=i).collect::<Vec<u8>>();
+            lines.push(String::from_utf8_lossy(&line_bytes).trim().to_string());
+        }
+        lines
+    }
+}
+
+/// Maps a function call requested by the model to the MCP server that provides it.
+pub(crate) fn resolve_tool_call(
+    mcp_context: Option<&McpContext>,
+    name: &str,
+    args: serde_json::Value,
+) -> Option<ToolCall> {
+    let context = mcp_context?;
+    let server = context.servers.iter().find(|server| server.tools.iter().any(|t| t.name == name))?;
+    Some(ToolCall::new(server.name.clone(), name.to_string(), args))
+}
+
+pub(crate) fn build_http_client() -> reqwest::Client {
+    reqwest::Client::builder()
         .timeout(std::time::Duration::from_secs(120))
         .build()
-        .expect("Failed to build reqwest client");
-
-    let request_body = GeminiRequest {
-        contents: prompt_data.contents,
-        tools: prompt_data.tools,
-        system_instruction: prompt_data.system_instruction,
-    };
-    tracing::info!("Using chat model: {}", model);
-    let url = format!("{}/{}:streamGenerateContent?key={}&alt=sse", BASE_API_URL, model, api_key);
+        .expect("Failed to build reqwest client")
+}
 
-    for attempt in 0..MAX_RETRIES {
-        let response = match client.post(&url).json(&request_body).send().await {
-            Ok(r) => r,
-            Err(e) => {
-                tracing::error!("Error sending request on attempt {}: {}", attempt + 1, e);
-                if attempt + 1 == MAX_RETRIES { return; }
-                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-                continue;
-            }
-        };
+/// The shape the summary model is asked to return. Entities are name/value pairs rather than
+/// an object, because structured-output schemas can't describe an object with arbitrary keys.
+#[derive(Deserialize, Debug)]
+#[cfg_attr(test, derive(Serialize))]
+struct SummaryResponse {
+    summary: String,
+    sentiment: String,
+    #[serde(default)]
+    user_name: String,
+    #[serde(default)]
+    entities: Vec<SummaryEntity>,
+}
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let body_text = response.text().await.unwrap_or_else(|_| "Failed to read error body".to_string());
-            if let Ok(error_response) = serde_json::from_str::<GeminiErrorResponse>(&body_text) {
-                tracing::error!("Gemini API Error [{}]: {}", status, error_response.error.message);
-            } else {
-                tracing::error!("Gemini API Error [{}]: {}", status, body_text);
-            }
-            return;
-        }
+#[derive(Deserialize, Debug)]
+#[cfg_attr(test, derive(Serialize))]
+struct SummaryEntity {
+    name: String,
+    value: String,
+}
 
-        let mut stream = response.bytes_stream();
-        let mut has_sent_data = false;
-        let mut finish_reason: Option<String> = None;
-        let mut buffer = Vec::<u8>::new();
-        let mut malformed_call_detected = false;
-
-        while let Some(item) = stream.next().await {
-            match item {
-                Ok(bytes) => {
-                    buffer.extend_from_slice(&bytes);
-                    while let Some(i) = buffer.iter().position(|&b| b == b'\n') {
-                        let line_bytes = buffer.drain(..=i).collect::<Vec<u8>>();
-                        let line = String::from_utf8_lossy(&line_bytes).trim().to_string();
-
-                        if line.starts_with("data: ") {
-                            let json_str = &line["data: ".len()..];
-                            if json_str.is_empty() { continue; }
-                            match serde_json::from_str::<GeminiResponse>(json_str) {
-                                Ok(parsed) => {
-                                    if let Some(candidate) = parsed.candidates.get(0) {
-                                        if let Some(reason) = &candidate.finish_reason {
-                                            finish_reason = Some(reason.clone());
-                                            if reason == "MALFORMED_FUNCTION_CALL" {
-                                                tracing::warn!("Malformed function call detected on attempt {}. Retrying...", attempt + 1);
-                                                malformed_call_detected = true;
-                                                break; // Break from inner while to retry
-                                            }
-                                            if reason != "STOP" {
-                                                tracing::warn!("Gemini stream finished with reason: {}", reason);
-                                            }
-                                        }
-                                        if let Some(part) = candidate.content.parts.get(0) {
-                                            if let Some(function_call) = &part.function_call {
-                                                let mut found_tool = false;
-                                                if let Some(context) = &mcp_context {
-                                                    for server in &context.servers {
-                                                        if server.tools.iter().any(|t| t.name == function_call.name) {
-                                                            let tool_call = ToolCall::new(
-                                                                server.name.clone(),
-                                                                function_call.name.clone(),
-                                                                function_call.args.clone(),
-                                                            );
-                                                            if tx.send(StreamMessage::ToolCall(tool_call)).is_err() {
-                                                                return;
-                                                            }
-                                                            has_sent_data = true;
-                                                            found_tool = true;
-                                                            break;
-                                                        }
-                                                    }
-                                                }
-                                                if !found_tool {
-                                                    tracing::error!("LLM requested tool '{}' which was not found in the provided context.", function_call.name);
-                                                }
-                                            } else if !part.text.is_empty() {
-                                                if tx.send(StreamMessage::Text(part.text.clone())).is_err() {
-                                                    return;
-                                                }
-                                                has_sent_data = true;
-                                            }
-                                        }
-                                    }
-                                }
-                                Err(e) => {
-                                    tracing::error!("Failed to parse JSON chunk from stream: {}. Chunk: '{}'", e, json_str);
-                                    // Check if this error is due to a malformed call finish reason
-                                    if json_str.contains("MALFORMED_FUNCTION_CALL") {
-                                        tracing::warn!("Malformed function call detected via string search on attempt {}. Retrying...", attempt + 1);
-                                        malformed_call_detected = true;
-                                        break; // Break from inner while to retry
-                                    }
-                                    let error_message = "[Hobbes encountered a stream error. Please check the logs for details.]";
-                                    if tx.send(StreamMessage::Text(error_message.to_string())).is_err() {
-                                        tracing::error!("Failed to send stream error message to UI.");
-                                    }
-                                    return;
-                                }
-                            }
-                        }
-                    }
-                    if malformed_call_detected { break; }
-                }
-                Err(e) => {
-                    tracing::error!("Error in stream: {}", e);
-                    break;
+fn summary_response_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "object",
+        "properties": {
+            "summary": { "type": "string", "description": "A concise, updated summary of the entire conversation so far." },
+            "sentiment": { "type": "string", "description": "The user's current sentiment or mood." },
+            "user_name": { "type": "string", "description": "The name the user wants to be called, or an empty string if unknown." },
+            "entities": {
+                "type": "array",
+                "description": "Key facts, preferences and other extracted information.",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "name": { "type": "string" },
+                        "value": { "type": "string" }
+                    },
+                    "required": ["name", "value"]
                 }
             }
-        }
+        },
+        "required": ["summary", "sentiment", "user_name", "entities"]
+    })
+}
 
-        if malformed_call_detected {
-            if attempt + 1 < MAX_RETRIES {
-                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-                continue; // Go to the next iteration of the for loop
-            } else {
-                tracing::error!("Malformed function call persisted after {} retries. Aborting.", MAX_RETRIES);
-                let _ = tx.send(StreamMessage::Text("[Hobbes failed to process a tool call after multiple retries.]".to_string()));
-                return;
-            }
-        }
+impl TryFrom<SummaryResponse> for ConversationSummary {
+    type Error = String;
 
-        if !has_sent_data {
-            let default_message = match finish_reason.as_deref() {
-                Some("SAFETY") => "[Hobbes did not provide a response due to the safety filter.]".to_string(),
-                Some(reason) => format!("[Hobbes did not provide a response. Finish Reason: {}]", reason),
-                None => "[Hobbes did not provide a response due to an internal error.]".to_string(),
-            };
-            if tx.send(StreamMessage::Text(default_message)).is_err() {
-                tracing::error!("Failed to send default message to UI.");
+    fn try_from(response: SummaryResponse) -> Result<Self, Self::Error> {
+        if response.summary.trim().is_empty() {
+            return Err("`summary` is empty".to_string());
+        }
+        let mut entities = ConversationSummaryEntities {
+            user_name: response.user_name.trim().to_string(),
+            ..Default::default()
+        };
+        for entity in response.entities {
+            if entity.name == "user_name" {
+                if entities.user_name.is_empty() {
+                    entities.user_name = entity.value;
+                }
+                continue;
             }
+            entities.other_entities.insert(entity.name, serde_json::Value::String(entity.value));
         }
-        // If we've successfully processed the stream without a malformed call, break the retry loop.
-        break;
+        Ok(ConversationSummary {
+            summary: response.summary,
+            sentiment: response.sentiment,
+            entities,
+        })
     }
 }
 
+/// Parses and validates the summary model's output. JSON wrapped in prose or a markdown
+/// code block is accepted, but anything that isn't a complete summary is rejected.
+fn parse_summary(text: &str) -> Result<ConversationSummary, String> {
+    let json = match (text.find('{'), text.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &text[start..=end],
+        _ => return Err("the response contains no JSON object".to_string()),
+    };
+    let response: SummaryResponse = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    response.try_into()
+}
+
+/// Asks the summary model to fold `recent_messages` into `previous_summary`. Output that
+/// fails validation is sent back to the model to be corrected; if it still fails, an error
+/// is returned so the caller can keep the previous summary. The usage of every response,
+/// including rejected ones, is passed to `on_usage`.
 pub async fn summarize_conversation(
-    api_key: String,
+    provider: &dyn LlmProvider,
     model: String,
     previous_summary: String,
     recent_messages: String,
-) -> Result<serde_json::Value, reqwest::Error> {
-    let client = Client::builder()
-        .timeout(std::time::Duration::from_secs(120))
-        .build()
-        .expect("Failed to build reqwest client");
+    mut on_usage: impl FnMut(TokenUsage),
+) -> Result<ConversationSummary, String> {
+    const SUMMARY_ATTEMPTS: usize = 2;
 
     let full_prompt = format!(
         r#"
@@ -260,10 +382,11 @@ Preserve existing information while incorporating new facts, entities, or user p
 
 A crucial part of your task is to analyze the **sentiment and mood** of the user in the "Recent Messages".
 
-Format your response as a single, clean JSON object with three keys: "summary", "entities", and "sentiment".
+Format your response as a single JSON object with four keys: "summary", "sentiment", "user_name", and "entities".
 - "summary": A concise, updated summary of the entire conversation so far.
-- "entities": An object containing all key-value pairs of extracted information. If the user mentions their name, be sure to extract it and include it as `{{\"user_name\": \"...\"}}` in this object.
 - "sentiment": A brief string describing the user's current sentiment or mood (e.g., "curious and collaborative", "frustrated but focused", "pleased with the progress", "neutral"). This should reflect the feeling of the recent messages.
+- "user_name": The user's name if they have mentioned it (or it is in the previous summary), otherwise an empty string.
+- "entities": A list of all other key information, each as an object with a "name" and a "value".
 
 Previous Summary:
 ---
@@ -278,72 +401,104 @@ Recent Messages:
         recent_messages
     );
 
-    let request_body = GeminiRequest {
-        contents: vec![Content {
-            role: "user".to_string(),
-            parts: vec![Part { text: full_prompt }],
-        }],
-        tools: None,
-        system_instruction: None,
-    };
-
     tracing::info!("Using summary model: {}", model);
-    let url = format!("{}/{}:generateContent?key={}", BASE_API_URL, model, api_key);
-
-    let response = client
-        .post(&url)
-        .json(&request_body)
-        .send()
-        .await?;
-
-    if !response.status().is_success() {
-        let status = response.status();
-        let body_text = response.text().await.unwrap_or_else(|_| "Failed to read error body".to_string());
-        if let Ok(error_response) = serde_json::from_str::<GeminiErrorResponse>(&body_text) {
-            tracing::error!("Gemini API Error [{}]: {}", status, error_response.error.message);
-        } else {
-            tracing::error!("Gemini API Error [{}]: {}", status, body_text);
+    let mut messages = vec![LlmMessage::text(LlmRole::User, full_prompt)];
+    let mut last_error = String::new();
+    for attempt in 1..=SUMMARY_ATTEMPTS {
+        let prompt = LlmPrompt {
+            system_instruction: None,
+            messages: messages.clone(),
+            tools: None,
+            response_schema: Some(summary_response_schema()),
+            generation: Default::default(),
+        };
+        let Completion { text, usage } = provider.complete(model.clone(), prompt).await.map_err(|e| e.to_string())?;
+        if let Some(usage) = usage {
+            on_usage(usage);
         }
-        // Return a structured error instead of panicking or returning a generic reqwest::Error
-        return Ok(serde_json::json!({
-            "error": "API request failed",
-            "status": status.as_u16(),
-            "body": body_text
-        }));
-    }
-
-    let response_json: GeminiResponse = response.json().await?;
+        tracing::debug!("Raw LLM summary response: {}", text);
 
-    if let Some(candidate) = response_json.candidates.get(0) {
-        if let Some(part) = candidate.content.parts.get(0) {
-            // The model's response is expected to be a JSON string.
-            tracing::debug!("Raw LLM summary response: {}", part.text);
-
-            // Attempt to parse the text directly as JSON.
-            if let Ok(json_value) = serde_json::from_str(&part.text) {
-                return Ok(json_value);
+        match parse_summary(&text) {
+            Ok(summary) => return Ok(summary),
+            Err(e) => {
+                tracing::warn!("Summary attempt {} failed validation: {}", attempt, e);
+                // Show the model what it returned and what was wrong with it.
+                messages.push(LlmMessage::text(LlmRole::Assistant, text));
+                messages.push(LlmMessage::text(
+                    LlmRole::User,
+                    format!("That response was not valid: {}. Reply with only the corrected JSON object.", e),
+                ));
+                last_error = e;
             }
+        }
+    }
+    Err(format!("The summary was still invalid after {} attempts: {}", SUMMARY_ATTEMPTS, last_error))
+}
 
-            // If direct parsing fails, try to extract it from a markdown code block.
-            if let Some(start) = part.text.find('{') {
-                if let Some(end) = part.text.rfind('}') {
-                    let potential_json = &part.text[start..=end];
-                    if let Ok(json_value) = serde_json::from_str(potential_json) {
-                        tracing::warn!("Successfully parsed JSON from markdown code block.");
-                        return Ok(json_value);
-                    }
-                }
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_summary_rejects_incomplete_output() {
+        let summary = parse_summary(
+            "```json\n{\"summary\": \"Planning a trip.\", \"sentiment\": \"excited\", \"user_name\": \"Sam\", \"entities\": [{\"name\": \"destination\", \"value\": \"Lisbon\"}]}\n```",
+        )
+        .unwrap();
+        assert_eq!(summary.entities.user_name, "Sam");
+        assert_eq!(summary.entities.other_entities["destination"], "Lisbon");
+
+        // An API error body must never be mistaken for an (empty) summary.
+        assert!(parse_summary(r#"{"error": {"code": 429, "message": "Resource exhausted"}}"#).is_err());
+        assert!(parse_summary(r#"{"summary": " ", "sentiment": "neutral"}"#).is_err());
+        assert!(parse_summary("The user is planning a trip.").is_err());
+    }
 
-            // If all parsing fails, return the raw text as the summary.
-            tracing::warn!("Failed to parse LLM response as JSON. Returning raw text as summary.");
-            let fallback_json = serde_json::json!({
-                "summary": part.text,
-                "entities": {}
-            });
-            return Ok(fallback_json);
+    /// A value that conforms to `schema`, with each string naming the property it fills.
+    fn example_for(schema: &serde_json::Value, name: &str) -> serde_json::Value {
+        match schema["type"].as_str() {
+            Some("object") => schema["properties"]
+                .as_object()
+                .unwrap()
+                .iter()
+                .map(|(key, property)| (key.clone(), example_for(property, key)))
+                .collect::<serde_json::Map<_, _>>()
+                .into(),
+            Some("array") => serde_json::Value::Array(vec![example_for(&schema["items"], name)]),
+            _ => serde_json::Value::String(format!("{} example", name)),
         }
     }
 
-    Ok(serde_json::Value::Null)
-}
\ No newline at end of file
+    #[test]
+    fn test_summary_schema_matches_summary_response() {
+        let example = example_for(&summary_response_schema(), "response");
+        // Every property the schema asks for is a field of `SummaryResponse`, and vice versa.
+        let response: SummaryResponse = serde_json::from_value(example.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&response).unwrap(), example);
+        let summary = parse_summary(&example.to_string()).unwrap();
+        assert_eq!(summary.summary, "summary example");
+        assert_eq!(summary.sentiment, "sentiment example");
+        assert_eq!(summary.entities.user_name, "user_name example");
+        assert_eq!(summary.entities.other_entities["name example"], "value example");
+    }
+
+    #[test]
+    fn test_backoff_honors_retry_after() {
+        assert_eq!(backoff_delay(1, None), Some(Duration::from_secs(1)));
+        assert_eq!(backoff_delay(3, None), Some(Duration::from_secs(4)));
+        assert_eq!(backoff_delay(MAX_ATTEMPTS, None), None);
+        assert_eq!(backoff_delay(1, parse_retry_after("7")), Some(Duration::from_secs(7)));
+        // A quota that resets an hour from now is reported instead of waited out.
+        assert_eq!(backoff_delay(1, parse_retry_after("3600")), None);
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(Duration::ZERO));
+    }
+
+    #[tokio::test]
+    async fn test_request_errors_leave_out_the_url() {
+        // Nothing listens on port 1, so the connection is refused.
+        let e = reqwest::Client::new().get("http://127.0.0.1:1/models?key=secret-key").send().await.unwrap_err();
+        let error = request_error(e);
+        assert!(matches!(error, LlmError::Network { .. }));
+        assert!(!error.to_string().contains("secret-key"));
+    }
+}
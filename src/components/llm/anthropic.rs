@@ -0,0 +1,534 @@
+//! Anthropic Messages API backend.
+use futures::future::BoxFuture;
+use futures_util::StreamExt;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use tokio::sync::mpsc;
+
+use super::{Completion, LineBuffer, LlmError, LlmMessage, LlmPart, LlmProvider, LlmRole};
+use crate::components::shared::StreamMessage;
+use hobbes_core::message::TokenUsage;
+use hobbes_core::models::FinishReason;
+use crate::context::prompt_builder::LlmPrompt;
+use hobbes_core::mcp::McpContext;
+
+const MESSAGES_API_URL: &str = "https://api.anthropic.com/v1/messages";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+/// The Messages API requires an explicit output limit.
+const DEFAULT_MAX_TOKENS: u32 = 8192;
+/// The smallest thinking budget the Messages API accepts.
+const MIN_THINKING_BUDGET: u32 = 1024;
+
+/// Server-sent events emitted by a streaming Messages API request.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum StreamEvent {
+    MessageStart { message: MessageStart },
+    ContentBlockStart { index: usize, content_block: ContentBlock },
+    ContentBlockDelta { index: usize, delta: BlockDelta },
+    ContentBlockStop { index: usize },
+    MessageDelta {
+        delta: MessageDelta,
+        usage: Option<Usage>,
+    },
+    Error { error: AnthropicError },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentBlock {
+    Text {
+        #[serde(default)]
+        text: String,
+    },
+    ToolUse { name: String },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum BlockDelta {
+    TextDelta { text: String },
+    InputJsonDelta { partial_json: String },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize, Debug)]
+struct MessageStart {
+    usage: Option<Usage>,
+}
+
+/// Token counts. `message_start` reports the input, and `message_delta` the running output.
+#[derive(Deserialize, Debug, Clone, Copy)]
+struct Usage {
+    #[serde(default)]
+    input_tokens: u64,
+    #[serde(default)]
+    output_tokens: u64,
+    #[serde(default)]
+    cache_creation_input_tokens: u64,
+    #[serde(default)]
+    cache_read_input_tokens: u64,
+}
+
+impl From<Usage> for TokenUsage {
+    fn from(usage: Usage) -> Self {
+        let prompt_tokens = usage.input_tokens + usage.cache_creation_input_tokens + usage.cache_read_input_tokens;
+        TokenUsage {
+            prompt_tokens,
+            completion_tokens: usage.output_tokens,
+            total_tokens: prompt_tokens + usage.output_tokens,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct MessageDelta {
+    stop_reason: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct MessagesResponse {
+    #[serde(default)]
+    content: Vec<ContentBlock>,
+    stop_reason: Option<String>,
+    usage: Option<Usage>,
+}
+
+#[derive(Deserialize, Debug)]
+struct AnthropicErrorResponse {
+    error: AnthropicError,
+}
+
+#[derive(Deserialize, Debug)]
+struct AnthropicError {
+    #[serde(rename = "type", default)]
+    kind: String,
+    message: String,
+}
+
+impl From<AnthropicError> for LlmError {
+    /// Classifies an error reported inside the event stream, where there is no HTTP status.
+    fn from(error: AnthropicError) -> Self {
+        let message = error.message;
+        match error.kind.as_str() {
+            "authentication_error" | "permission_error" => LlmError::Auth { message },
+            "rate_limit_error" => LlmError::Quota { message, retry_after: None },
+            "overloaded_error" => LlmError::Server { status: 529, message },
+            "api_error" => LlmError::Server { status: 500, message },
+            "timeout_error" => LlmError::Timeout { message },
+            _ => LlmError::Request { status: 400, message },
+        }
+    }
+}
+
+/// A `tool_use` block whose JSON input is still being streamed.
+#[derive(Debug)]
+struct PendingToolUse {
+    name: String,
+    input_json: String,
+}
+
+fn part_to_block(part: &LlmPart) -> Option<Value> {
+    match part {
+        LlmPart::Text(text) if text.is_empty() => None,
+        LlmPart::Text(text) => Some(json!({ "type": "text", "text": text })),
+        LlmPart::ToolCall { id, name, args } => Some(json!({
+            "type": "tool_use",
+            "id": id,
+            "name": name,
+            "input": args,
+        })),
+        LlmPart::ToolResult { id, response, is_error, .. } => Some(json!({
+            "type": "tool_result",
+            "tool_use_id": id,
+            "content": response,
+            "is_error": is_error,
+        })),
+        LlmPart::InlineData { name, mime_type, data } => {
+            let block_type = match mime_type.as_str() {
+                "application/pdf" => "document",
+                m if m.starts_with("image/") => "image",
+                _ => return Some(json!({ "type": "text", "text": format!("[`{}` was attached, but this model can't read {} files.]", name, mime_type) })),
+            };
+            Some(json!({
+                "type": block_type,
+                "source": { "type": "base64", "media_type": mime_type, "data": data },
+            }))
+        }
+    }
+}
+
+/// Converts the neutral history into Messages API turns. Consecutive messages from the
+/// same role are merged, since the API requires strictly alternating roles.
+fn build_messages(messages: &[LlmMessage]) -> Vec<Value> {
+    let mut turns: Vec<(LlmRole, Vec<Value>)> = Vec::new();
+    for message in messages {
+        let blocks: Vec<Value> = message.parts.iter().filter_map(part_to_block).collect();
+        if blocks.is_empty() {
+            continue;
+        }
+        match turns.last_mut() {
+            Some((role, existing)) if *role == message.role => existing.extend(blocks),
+            _ => turns.push((message.role, blocks)),
+        }
+    }
+    turns
+        .into_iter()
+        .map(|(role, content)| {
+            let role = match role {
+                LlmRole::User => "user",
+                LlmRole::Assistant => "assistant",
+            };
+            json!({ "role": role, "content": content })
+        })
+        .collect()
+}
+
+/// Builds the `tools` array straight from the MCP schemas, which the Messages API accepts as-is.
+fn build_tools(mcp_context: Option<&McpContext>) -> Vec<Value> {
+    mcp_context
+        .map(|context| {
+            context
+                .servers
+                .iter()
+                .flat_map(|server| server.tools.iter())
+                .map(|tool| {
+                    json!({
+                        "name": tool.name,
+                        "description": tool.description.clone().unwrap_or_default(),
+                        "input_schema": tool.input_schema.as_ref(),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn build_request_body(model: &str, prompt: &LlmPrompt, mcp_context: Option<&McpContext>, stream: bool) -> Value {
+    let generation = &prompt.generation;
+    let max_tokens = generation.max_output_tokens.unwrap_or(DEFAULT_MAX_TOKENS);
+    let mut body = json!({
+        "model": model,
+        "max_tokens": max_tokens,
+        "messages": build_messages(&prompt.messages),
+        "stream": stream,
+    });
+    if !generation.stop_sequences.is_empty() {
+        body["stop_sequences"] = json!(generation.stop_sequences);
+    }
+    // Tool-use turns must echo back the model's signed thinking blocks, which the history
+    // doesn't keep, so thinking is only enabled for prompts without tool calls.
+    let has_tool_calls = prompt.messages.iter().flat_map(|m| &m.parts).any(|part| matches!(part, LlmPart::ToolCall { .. }));
+    let thinking_budget = generation
+        .thinking_budget
+        .filter(|budget| *budget >= MIN_THINKING_BUDGET && *budget < max_tokens && !has_tool_calls);
+    if let Some(budget_tokens) = thinking_budget {
+        // Extended thinking doesn't allow changing the sampling parameters.
+        body["thinking"] = json!({ "type": "enabled", "budget_tokens": budget_tokens });
+    } else {
+        if let Some(temperature) = generation.temperature {
+            body["temperature"] = json!(temperature);
+        }
+        if let Some(top_p) = generation.top_p {
+            body["top_p"] = json!(top_p);
+        }
+        if let Some(top_k) = generation.top_k {
+            body["top_k"] = json!(top_k);
+        }
+    }
+    // The Messages API has no structured-output mode, so the schema is given as an instruction.
+    let schema_instruction = prompt.response_schema.as_ref().map(|schema| {
+        format!("Respond with only a JSON object that matches this JSON schema, and no other text:\n{}", schema)
+    });
+    let system = [prompt.system_instruction.clone(), schema_instruction].into_iter().flatten().collect::<Vec<_>>();
+    if !system.is_empty() {
+        body["system"] = json!(system.join("\n\n"));
+    }
+    let tools = if prompt.tools.is_some() { build_tools(mcp_context) } else { Vec::new() };
+    if !tools.is_empty() {
+        body["tools"] = Value::Array(tools);
+    }
+    body
+}
+
+pub struct AnthropicProvider {
+    api_key: Option<String>,
+    client: Client,
+}
+
+impl AnthropicProvider {
+    pub fn new(api_key: Option<String>) -> Self {
+        Self {
+            api_key: api_key.filter(|key| !key.is_empty()),
+            client: super::build_http_client(),
+        }
+    }
+
+    /// Fails with `LlmError::Auth` when no API key is configured, so the chat shows the
+    /// problem instead of sending a request that is bound to be rejected.
+    fn request(&self, body: &Value) -> Result<reqwest::RequestBuilder, LlmError> {
+        let api_key = self.api_key.as_ref().ok_or_else(|| LlmError::Auth {
+            message: "No Anthropic API key is set. Add one in Settings or set ANTHROPIC_API_KEY.".to_string(),
+        })?;
+        Ok(self.client
+            .post(MESSAGES_API_URL)
+            .header("x-api-key", api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .json(body))
+    }
+}
+
+impl LlmProvider for AnthropicProvider {
+    fn stream_chat(
+        &self,
+        model: String,
+        prompt: LlmPrompt,
+        tx: mpsc::UnboundedSender<StreamMessage>,
+        mcp_context: Option<McpContext>,
+    ) -> BoxFuture<'static, ()> {
+        tracing::info!("Using chat model: {}", model);
+        let request = self.request(&build_request_body(&model, &prompt, mcp_context.as_ref(), true));
+        Box::pin(async move {
+            match request {
+                Ok(request) => stream_messages(request, tx, mcp_context).await,
+                Err(e) => {
+                    tracing::error!("Anthropic API Error: {}", e);
+                    let _ = tx.send(StreamMessage::Error(e));
+                }
+            }
+        })
+    }
+
+    fn complete(&self, model: String, prompt: LlmPrompt) -> BoxFuture<'static, Result<Completion, LlmError>> {
+        let request = self.request(&build_request_body(&model, &prompt, None, false));
+        Box::pin(async move {
+            let response = super::send_with_retry(request?, error_message)
+                .await
+                .inspect_err(|e| tracing::error!("Anthropic API Error: {}", e))?;
+            let response: MessagesResponse = response.json().await.map_err(super::request_error)?;
+            if response.stop_reason.as_deref() == Some("refusal") {
+                return Err(LlmError::Safety { message: "The model declined to respond (refusal).".to_string() });
+            }
+            let usage = response.usage.map(TokenUsage::from);
+            let text = response
+                .content
+                .into_iter()
+                .filter_map(|block| match block {
+                    ContentBlock::Text { text } => Some(text),
+                    _ => None,
+                })
+                .collect::<String>();
+            if text.is_empty() {
+                Err(LlmError::Parse { message: "Anthropic returned no text content".to_string() })
+            } else {
+                Ok(Completion { text, usage })
+            }
+        })
+    }
+}
+
+fn error_message(body_text: &str) -> String {
+    match serde_json::from_str::<AnthropicErrorResponse>(body_text) {
+        Ok(error_response) => error_response.error.message,
+        Err(_) => body_text.to_string(),
+    }
+}
+
+async fn stream_messages(
+    request: reqwest::RequestBuilder,
+    tx: mpsc::UnboundedSender<StreamMessage>,
+    mcp_context: Option<McpContext>,
+) {
+    let response = match super::send_with_retry(request, error_message).await {
+        Ok(r) => r,
+        Err(e) => {
+            tracing::error!("Anthropic API Error: {}", e);
+            let _ = tx.send(StreamMessage::Error(e));
+            return;
+        }
+    };
+
+    let mut stream = response.bytes_stream();
+    let mut lines = LineBuffer::default();
+    let mut pending_tool_uses: BTreeMap<usize, PendingToolUse> = BTreeMap::new();
+    let mut has_sent_data = false;
+    let mut stop_reason: Option<String> = None;
+    let mut usage = TokenUsage::default();
+    let mut stream_error: Option<LlmError> = None;
+
+    'stream: while let Some(item) = stream.next().await {
+        let bytes = match item {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                tracing::error!("Error in stream: {}", e);
+                stream_error = Some(super::request_error(e));
+                break;
+            }
+        };
+        for line in lines.push(&bytes) {
+            // The `event:` lines duplicate the `type` field of the data payload.
+            let Some(json_str) = line.strip_prefix("data:").map(str::trim) else { continue };
+            if json_str.is_empty() {
+                continue;
+            }
+            let event = match serde_json::from_str::<StreamEvent>(json_str) {
+                Ok(event) => event,
+                Err(e) => {
+                    tracing::error!("Failed to parse JSON chunk from stream: {}. Chunk: '{}'", e, json_str);
+                    stream_error = Some(e.into());
+                    break 'stream;
+                }
+            };
+            match event {
+                StreamEvent::MessageStart { message } => {
+                    if let Some(start_usage) = message.usage {
+                        usage.prompt_tokens = start_usage.input_tokens
+                            + start_usage.cache_creation_input_tokens
+                            + start_usage.cache_read_input_tokens;
+                        usage.completion_tokens = start_usage.output_tokens;
+                    }
+                }
+                StreamEvent::ContentBlockStart { index, content_block: ContentBlock::ToolUse { name } } => {
+                    pending_tool_uses.insert(index, PendingToolUse { name, input_json: String::new() });
+                }
+                StreamEvent::ContentBlockStart { content_block: ContentBlock::Text { text }, .. } if !text.is_empty() => {
+                    if tx.send(StreamMessage::Text(text)).is_err() {
+                        return;
+                    }
+                    has_sent_data = true;
+                }
+                StreamEvent::ContentBlockDelta { delta: BlockDelta::TextDelta { text }, .. } => {
+                    if tx.send(StreamMessage::Text(text)).is_err() {
+                        return;
+                    }
+                    has_sent_data = true;
+                }
+                StreamEvent::ContentBlockDelta { index, delta: BlockDelta::InputJsonDelta { partial_json } } => {
+                    if let Some(pending) = pending_tool_uses.get_mut(&index) {
+                        pending.input_json.push_str(&partial_json);
+                    }
+                }
+                StreamEvent::ContentBlockStop { index } => {
+                    let Some(tool_use) = pending_tool_uses.remove(&index) else { continue };
+                    let input = if tool_use.input_json.trim().is_empty() {
+                        json!({})
+                    } else {
+                        match serde_json::from_str(&tool_use.input_json) {
+                            Ok(input) => input,
+                            Err(e) => {
+                                tracing::error!("Failed to parse input for tool '{}': {}. Input: '{}'", tool_use.name, e, tool_use.input_json);
+                                stream_error = Some(LlmError::Parse {
+                                    message: format!("The input for tool '{}' is not valid JSON: {}", tool_use.name, e),
+                                });
+                                break 'stream;
+                            }
+                        }
+                    };
+                    match super::resolve_tool_call(mcp_context.as_ref(), &tool_use.name, input) {
+                        Some(tool_call) => {
+                            if tx.send(StreamMessage::ToolCall(tool_call)).is_err() {
+                                return;
+                            }
+                            has_sent_data = true;
+                        }
+                        None => tracing::error!("LLM requested tool '{}' which was not found in the provided context.", tool_use.name),
+                    }
+                }
+                StreamEvent::MessageDelta { delta, usage: delta_usage } => {
+                    if let Some(delta_usage) = delta_usage {
+                        usage.completion_tokens = delta_usage.output_tokens;
+                    }
+                    if let Some(reason) = delta.stop_reason {
+                        if reason != "end_turn" && reason != "tool_use" {
+                            tracing::warn!("Anthropic stream finished with reason: {}", reason);
+                        }
+                        stop_reason = Some(reason);
+                    }
+                }
+                StreamEvent::Error { error } => {
+                    tracing::error!("Anthropic stream error: {}", error.message);
+                    stream_error = Some(error.into());
+                    break 'stream;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if usage.prompt_tokens > 0 || usage.completion_tokens > 0 {
+        usage.total_tokens = usage.prompt_tokens + usage.completion_tokens;
+        let _ = tx.send(StreamMessage::Usage(usage));
+    }
+    if let Some(reason) = stop_reason.as_deref() {
+        let _ = tx.send(StreamMessage::Finish(FinishReason::from_provider(reason)));
+    }
+
+    if stop_reason.as_deref() == Some("refusal") {
+        stream_error = Some(LlmError::Safety { message: "The model declined to respond (refusal).".to_string() });
+    }
+    if let Some(error) = stream_error {
+        let _ = tx.send(StreamMessage::Error(error));
+        return;
+    }
+
+    if !has_sent_data {
+        let default_message = match stop_reason.as_deref() {
+            Some(reason) => format!("[Hobbes did not provide a response. Finish Reason: {}]", reason),
+            None => "[Hobbes did not provide a response due to an internal error.]".to_string(),
+        };
+        if tx.send(StreamMessage::Text(default_message)).is_err() {
+            tracing::error!("Failed to send default message to UI.");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hobbes_core::generation::GenerationParams;
+
+    #[test]
+    fn test_tool_history_merges_turns_and_disables_thinking() {
+        let prompt = LlmPrompt {
+            system_instruction: None,
+            messages: vec![
+                LlmMessage { role: LlmRole::User, parts: vec![LlmPart::Text("Fetch it".to_string())] },
+                LlmMessage {
+                    role: LlmRole::Assistant,
+                    parts: vec![LlmPart::ToolCall { id: "1".to_string(), name: "fetch".to_string(), args: json!({ "url": "https://example.com" }) }],
+                },
+                LlmMessage {
+                    role: LlmRole::User,
+                    parts: vec![LlmPart::ToolResult { id: "1".to_string(), name: "fetch".to_string(), response: "page body".to_string(), is_error: false }],
+                },
+                LlmMessage { role: LlmRole::User, parts: vec![LlmPart::Text("Summarize it".to_string())] },
+            ],
+            tools: None,
+            response_schema: None,
+            generation: GenerationParams { temperature: Some(0.5), thinking_budget: Some(2048), ..Default::default() },
+        };
+
+        let body = build_request_body("claude-sonnet-4-0", &prompt, None, true);
+
+        assert_eq!(
+            body["messages"],
+            json!([
+                { "role": "user", "content": [{ "type": "text", "text": "Fetch it" }] },
+                { "role": "assistant", "content": [{ "type": "tool_use", "id": "1", "name": "fetch", "input": { "url": "https://example.com" } }] },
+                { "role": "user", "content": [
+                    { "type": "tool_result", "tool_use_id": "1", "content": "page body", "is_error": false },
+                    { "type": "text", "text": "Summarize it" },
+                ] },
+            ])
+        );
+        assert!(body.get("thinking").is_none());
+        assert_eq!(body["temperature"], json!(0.5));
+    }
+}
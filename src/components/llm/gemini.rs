@@ -0,0 +1,489 @@
+//! Google Gemini (`generativelanguage.googleapis.com`) backend.
+use futures::future::BoxFuture;
+use futures_util::StreamExt;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use super::{Completion, LineBuffer, LlmError, LlmMessage, LlmPart, LlmProvider, LlmRole};
+use crate::components::shared::StreamMessage;
+use hobbes_core::message::TokenUsage;
+use hobbes_core::models::FinishReason;
+use crate::context::prompt_builder::LlmPrompt;
+use hobbes_core::mcp::McpContext;
+use hobbes_core::session::Tool;
+
+const BASE_API_URL: &str = "https://generativelanguage.googleapis.com/v1beta/models";
+const API_KEY_HEADER: &str = "x-goog-api-key";
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct GeminiRequest {
+    contents: Vec<Content>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<Tool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system_instruction: Option<SystemInstruction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    generation_config: Option<GenerationConfig>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+struct GenerationConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_k: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_output_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop_sequences: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thinking_config: Option<ThinkingConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_mime_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_schema: Option<serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ThinkingConfig {
+    thinking_budget: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[derive(Debug)]
+pub struct Content {
+    pub role: String,
+    pub parts: Vec<Part>,
+}
+
+/// A single part of a `Content` turn. Serializes to Gemini's `{"<kind>": ...}` shape.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub enum Part {
+    Text(String),
+    FunctionCall(FunctionCall),
+    FunctionResponse(FunctionResponse),
+    InlineData(Blob),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FunctionResponse {
+    pub name: String,
+    /// Must be a JSON object.
+    pub response: serde_json::Value,
+}
+
+/// Raw bytes sent inline with the request, base64-encoded.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Blob {
+    pub mime_type: String,
+    pub data: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct GeminiErrorResponse {
+    error: GeminiError,
+}
+
+#[derive(Deserialize, Debug)]
+struct GeminiError {
+    message: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[derive(Debug)]
+pub struct SystemInstruction {
+    pub parts: Vec<Part>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct GeminiResponse {
+    #[serde(default)]
+    candidates: Vec<Candidate>,
+    usage_metadata: Option<UsageMetadata>,
+    prompt_feedback: Option<PromptFeedback>,
+}
+
+/// Set instead of `candidates` when the prompt itself was blocked.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct PromptFeedback {
+    block_reason: Option<String>,
+}
+
+/// Token counts for the request so far. In a stream, each chunk carries the running totals.
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+struct UsageMetadata {
+    #[serde(default)]
+    prompt_token_count: u64,
+    #[serde(default)]
+    candidates_token_count: u64,
+    /// Tokens spent on thinking, which are billed as output.
+    #[serde(default)]
+    thoughts_token_count: u64,
+    #[serde(default)]
+    total_token_count: u64,
+}
+
+impl From<UsageMetadata> for TokenUsage {
+    fn from(usage: UsageMetadata) -> Self {
+        TokenUsage {
+            prompt_tokens: usage.prompt_token_count,
+            completion_tokens: usage.candidates_token_count + usage.thoughts_token_count,
+            total_tokens: usage.total_token_count,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Candidate {
+    content: ContentResponse,
+    finish_reason: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ContentResponse {
+    #[serde(default)]
+    parts: Vec<PartResponse>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FunctionCall {
+    pub name: String,
+    pub args: serde_json::Value,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct PartResponse {
+    #[serde(default)]
+    text: String,
+    function_call: Option<FunctionCall>,
+}
+
+/// Finish reasons that mean the response was withheld by a content filter.
+const BLOCKED_FINISH_REASONS: &[&str] = &["SAFETY", "PROHIBITED_CONTENT", "BLOCKLIST", "SPII", "IMAGE_SAFETY"];
+
+fn error_message(body_text: &str) -> String {
+    match serde_json::from_str::<GeminiErrorResponse>(body_text) {
+        Ok(error_response) => error_response.error.message,
+        Err(_) => body_text.to_string(),
+    }
+}
+
+/// Wraps a tool's textual output in the JSON object Gemini expects as a `functionResponse`.
+/// Output that is already a JSON object is passed through unchanged.
+fn function_response_body(response: &str, is_error: bool) -> serde_json::Value {
+    let value = serde_json::from_str::<serde_json::Value>(response)
+        .unwrap_or_else(|_| serde_json::Value::String(response.to_string()));
+    if is_error {
+        serde_json::json!({ "error": value })
+    } else if value.is_object() {
+        value
+    } else {
+        serde_json::json!({ "result": value })
+    }
+}
+
+impl From<LlmMessage> for Content {
+    fn from(message: LlmMessage) -> Self {
+        let role = match message.role {
+            LlmRole::User => "user",
+            LlmRole::Assistant => "model",
+        }
+        .to_string();
+        let parts = message
+            .parts
+            .into_iter()
+            .map(|part| match part {
+                LlmPart::Text(text) => Part::Text(text),
+                LlmPart::ToolCall { name, args, .. } => Part::FunctionCall(FunctionCall { name, args }),
+                LlmPart::ToolResult { name, response, is_error, .. } => Part::FunctionResponse(FunctionResponse {
+                    name,
+                    response: function_response_body(&response, is_error),
+                }),
+                LlmPart::InlineData { mime_type, data, .. } => Part::InlineData(Blob { mime_type, data }),
+            })
+            .collect();
+        Content { role, parts }
+    }
+}
+
+impl From<LlmPrompt> for GeminiRequest {
+    fn from(prompt: LlmPrompt) -> Self {
+        GeminiRequest {
+            contents: prompt.messages.into_iter().map(Content::from).collect(),
+            tools: prompt.tools,
+            system_instruction: prompt.system_instruction.map(|text| SystemInstruction {
+                parts: vec![Part::Text(text)],
+            }),
+            generation_config: Some(GenerationConfig {
+                temperature: prompt.generation.temperature,
+                top_p: prompt.generation.top_p,
+                top_k: prompt.generation.top_k,
+                max_output_tokens: prompt.generation.max_output_tokens,
+                stop_sequences: prompt.generation.stop_sequences,
+                thinking_config: prompt.generation.thinking_budget.map(|thinking_budget| ThinkingConfig { thinking_budget }),
+                response_mime_type: prompt.response_schema.is_some().then(|| "application/json".to_string()),
+                response_schema: prompt.response_schema,
+            }),
+        }
+    }
+}
+
+pub struct GeminiProvider {
+    api_key: Option<String>,
+    client: Client,
+}
+
+impl GeminiProvider {
+    pub fn new(api_key: Option<String>) -> Self {
+        Self {
+            api_key: api_key.filter(|key| !key.is_empty()),
+            client: super::build_http_client(),
+        }
+    }
+
+    /// Fails with `LlmError::Auth` when no API key is configured, so the chat shows the
+    /// problem instead of sending a request that is bound to be rejected.
+    fn api_key(&self) -> Result<String, LlmError> {
+        self.api_key.clone().ok_or_else(|| LlmError::Auth {
+            message: "No Gemini API key is set. Add one in Settings or set GEMINI_API_KEY.".to_string(),
+        })
+    }
+}
+
+impl LlmProvider for GeminiProvider {
+    fn stream_chat(
+        &self,
+        model: String,
+        prompt: LlmPrompt,
+        tx: mpsc::UnboundedSender<StreamMessage>,
+        mcp_context: Option<McpContext>,
+    ) -> BoxFuture<'static, ()> {
+        let client = self.client.clone();
+        let api_key = self.api_key();
+        Box::pin(async move {
+            match api_key {
+                Ok(api_key) => generate_content_stream(client, api_key, model, prompt, tx, mcp_context).await,
+                Err(e) => {
+                    tracing::error!("Gemini API Error: {}", e);
+                    let _ = tx.send(StreamMessage::Error(e));
+                }
+            }
+        })
+    }
+
+    fn complete(&self, model: String, prompt: LlmPrompt) -> BoxFuture<'static, Result<Completion, LlmError>> {
+        let client = self.client.clone();
+        let api_key = self.api_key();
+        Box::pin(async move { generate_content(client, api_key?, model, prompt).await })
+    }
+}
+
+async fn generate_content_stream(
+    client: Client,
+    api_key: String,
+    model: String,
+    prompt_data: LlmPrompt,
+    tx: mpsc::UnboundedSender<StreamMessage>,
+    mcp_context: Option<McpContext>,
+) {
+    const MAX_RETRIES: u32 = 2;
+
+    let request_body = GeminiRequest::from(prompt_data);
+    tracing::info!("Using chat model: {}", model);
+    // The key goes in a header rather than the URL, which ends up in error messages.
+    let url = format!("{}/{}:streamGenerateContent?alt=sse", BASE_API_URL, model);
+
+    for attempt in 0..MAX_RETRIES {
+        let response = match super::send_with_retry(client.post(&url).header(API_KEY_HEADER, &api_key).json(&request_body), error_message).await {
+            Ok(r) => r,
+            Err(e) => {
+                tracing::error!("Gemini API Error: {}", e);
+                let _ = tx.send(StreamMessage::Error(e));
+                return;
+            }
+        };
+
+        let mut stream = response.bytes_stream();
+        let mut has_sent_data = false;
+        let mut finish_reason: Option<String> = None;
+        let mut lines = LineBuffer::default();
+        let mut malformed_call_detected = false;
+        let mut usage: Option<UsageMetadata> = None;
+        let mut error: Option<LlmError> = None;
+
+        'stream: while let Some(item) = stream.next().await {
+            match item {
+                Ok(bytes) => {
+                    for line in lines.push(&bytes) {
+                        if let Some(json_str) = line.strip_prefix("data: ") {
+                            if json_str.is_empty() { continue; }
+                            match serde_json::from_str::<GeminiResponse>(json_str) {
+                                Ok(parsed) => {
+                                    if parsed.usage_metadata.is_some() {
+                                        usage = parsed.usage_metadata;
+                                    }
+                                    if let Some(block_reason) = parsed.prompt_feedback.and_then(|feedback| feedback.block_reason) {
+                                        tracing::warn!("Gemini blocked the prompt: {}", block_reason);
+                                        error = Some(LlmError::Safety { message: format!("The prompt was blocked ({}).", block_reason) });
+                                        break 'stream;
+                                    }
+                                    if let Some(candidate) = parsed.candidates.first() {
+                                        if let Some(reason) = &candidate.finish_reason {
+                                            finish_reason = Some(reason.clone());
+                                            if reason == "MALFORMED_FUNCTION_CALL" {
+                                                tracing::warn!("Malformed function call detected on attempt {}. Retrying...", attempt + 1);
+                                                malformed_call_detected = true;
+                                                break; // Break from the line loop to retry
+                                            }
+                                            if reason != "STOP" {
+                                                tracing::warn!("Gemini stream finished with reason: {}", reason);
+                                            }
+                                        }
+                                        for part in &candidate.content.parts {
+                                            if let Some(function_call) = &part.function_call {
+                                                match super::resolve_tool_call(mcp_context.as_ref(), &function_call.name, function_call.args.clone()) {
+                                                    Some(tool_call) => {
+                                                        if tx.send(StreamMessage::ToolCall(tool_call)).is_err() {
+                                                            return;
+                                                        }
+                                                        has_sent_data = true;
+                                                    }
+                                                    None => {
+                                                        tracing::error!("LLM requested tool '{}' which was not found in the provided context.", function_call.name);
+                                                    }
+                                                }
+                                            } else if !part.text.is_empty() {
+                                                if tx.send(StreamMessage::Text(part.text.clone())).is_err() {
+                                                    return;
+                                                }
+                                                has_sent_data = true;
+                                            }
+                                        }
+                                    }
+                                }
+                                Err(e) => {
+                                    tracing::error!("Failed to parse JSON chunk from stream: {}. Chunk: '{}'", e, json_str);
+                                    // Check if this error is due to a malformed call finish reason
+                                    if json_str.contains("MALFORMED_FUNCTION_CALL") {
+                                        tracing::warn!("Malformed function call detected via string search on attempt {}. Retrying...", attempt + 1);
+                                        malformed_call_detected = true;
+                                        break; // Break from the line loop to retry
+                                    }
+                                    error = Some(e.into());
+                                    break 'stream;
+                                }
+                            }
+                        }
+                    }
+                    if malformed_call_detected { break; }
+                }
+                Err(e) => {
+                    tracing::error!("Error in stream: {}", e);
+                    error = Some(super::request_error(e));
+                    break;
+                }
+            }
+        }
+
+        // Every attempt is billed, including ones retried below.
+        if let Some(usage) = usage {
+            let _ = tx.send(StreamMessage::Usage(usage.into()));
+        }
+        if let Some(reason) = finish_reason.as_deref() {
+            let _ = tx.send(StreamMessage::Finish(FinishReason::from_provider(reason)));
+        }
+
+        if let Some(reason) = finish_reason.as_deref().filter(|reason| BLOCKED_FINISH_REASONS.contains(reason)) {
+            error = Some(LlmError::Safety { message: format!("The response was withheld ({}).", reason) });
+        }
+        if let Some(error) = error {
+            let _ = tx.send(StreamMessage::Error(error));
+            return;
+        }
+
+        if malformed_call_detected {
+            if attempt + 1 < MAX_RETRIES {
+                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                continue; // Go to the next iteration of the for loop
+            } else {
+                tracing::error!("Malformed function call persisted after {} retries. Aborting.", MAX_RETRIES);
+                let _ = tx.send(StreamMessage::Text("[Hobbes failed to process a tool call after multiple retries.]".to_string()));
+                return;
+            }
+        }
+
+        if !has_sent_data {
+            let default_message = match finish_reason.as_deref() {
+                Some(reason) => format!("[Hobbes did not provide a response. Finish Reason: {}]", reason),
+                None => "[Hobbes did not provide a response due to an internal error.]".to_string(),
+            };
+            if tx.send(StreamMessage::Text(default_message)).is_err() {
+                tracing::error!("Failed to send default message to UI.");
+            }
+        }
+        // If we've successfully processed the stream without a malformed call, break the retry loop.
+        break;
+    }
+}
+
+async fn generate_content(client: Client, api_key: String, model: String, prompt_data: LlmPrompt) -> Result<Completion, LlmError> {
+    let request_body = GeminiRequest::from(prompt_data);
+    let url = format!("{}/{}:generateContent", BASE_API_URL, model);
+
+    let response = super::send_with_retry(client.post(&url).header(API_KEY_HEADER, &api_key).json(&request_body), error_message)
+        .await
+        .inspect_err(|e| tracing::error!("Gemini API Error: {}", e))?;
+
+    let response_json: GeminiResponse = response.json().await.map_err(super::request_error)?;
+    if let Some(block_reason) = response_json.prompt_feedback.and_then(|feedback| feedback.block_reason) {
+        return Err(LlmError::Safety { message: format!("The prompt was blocked ({}).", block_reason) });
+    }
+    let candidate = response_json
+        .candidates
+        .first()
+        .ok_or_else(|| LlmError::Parse { message: "Gemini returned no candidates".to_string() })?;
+    Ok(Completion {
+        text: candidate.content.parts.iter().map(|part| part.text.as_str()).collect(),
+        usage: response_json.usage_metadata.map(TokenUsage::from),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_tool_history_serializes_as_function_parts() {
+        let call = Content::from(LlmMessage {
+            role: LlmRole::Assistant,
+            parts: vec![LlmPart::ToolCall { id: "1".to_string(), name: "fetch".to_string(), args: json!({ "url": "https://example.com" }) }],
+        });
+        let result = Content::from(LlmMessage {
+            role: LlmRole::User,
+            parts: vec![LlmPart::ToolResult { id: "1".to_string(), name: "fetch".to_string(), response: "page body".to_string(), is_error: false }],
+        });
+
+        assert_eq!(
+            serde_json::to_value(&call).unwrap(),
+            json!({ "role": "model", "parts": [{ "functionCall": { "name": "fetch", "args": { "url": "https://example.com" } } }] })
+        );
+        assert_eq!(
+            serde_json::to_value(&result).unwrap(),
+            json!({ "role": "user", "parts": [{ "functionResponse": { "name": "fetch", "response": { "result": "page body" } } }] })
+        );
+    }
+}
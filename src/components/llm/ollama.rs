@@ -0,0 +1,495 @@
+//! Native Ollama (`/api/chat`, `/api/tags`, `/api/pull`) backend for models running on the local machine.
+use futures::future::BoxFuture;
+use futures_util::StreamExt;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+
+use super::{Completion, LineBuffer, LlmError, LlmMessage, LlmPart, LlmProvider, LlmRole};
+use crate::components::shared::StreamMessage;
+use hobbes_core::message::TokenUsage;
+use hobbes_core::models::FinishReason;
+use crate::context::prompt_builder::LlmPrompt;
+use hobbes_core::mcp::McpContext;
+
+/// One line of the newline-delimited JSON stream returned by `/api/chat`.
+#[derive(Deserialize, Debug)]
+struct ChatResponse {
+    message: Option<ResponseMessage>,
+    #[serde(default)]
+    done: bool,
+    done_reason: Option<String>,
+    error: Option<String>,
+    /// Token counts, reported on the final (`done`) line.
+    prompt_eval_count: Option<u64>,
+    eval_count: Option<u64>,
+}
+
+impl ChatResponse {
+    fn usage(&self) -> Option<TokenUsage> {
+        if self.prompt_eval_count.is_none() && self.eval_count.is_none() {
+            return None;
+        }
+        let prompt_tokens = self.prompt_eval_count.unwrap_or(0);
+        let completion_tokens = self.eval_count.unwrap_or(0);
+        Some(TokenUsage { prompt_tokens, completion_tokens, total_tokens: prompt_tokens + completion_tokens })
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ResponseMessage {
+    #[serde(default)]
+    content: String,
+    #[serde(default)]
+    tool_calls: Vec<OllamaToolCall>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OllamaToolCall {
+    function: OllamaFunction,
+}
+
+#[derive(Deserialize, Debug)]
+struct OllamaFunction {
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+#[derive(Deserialize, Debug)]
+struct TagsResponse {
+    #[serde(default)]
+    models: Vec<ModelTag>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ModelTag {
+    name: String,
+}
+
+/// The part of the `/api/show` response that says what a model can do.
+#[derive(Deserialize, Debug)]
+struct ShowResponse {
+    #[serde(default)]
+    capabilities: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OllamaErrorResponse {
+    error: String,
+}
+
+/// Converts a neutral message into one or more Ollama chat messages. Tool results are
+/// sent as separate `tool` role messages.
+fn message_to_json(message: &LlmMessage) -> Vec<Value> {
+    let role = match message.role {
+        LlmRole::User => "user",
+        LlmRole::Assistant => "assistant",
+    };
+    let mut text = Vec::new();
+    let mut notes = Vec::new();
+    let mut images = Vec::new();
+    let mut tool_calls = Vec::new();
+    let mut tool_results = Vec::new();
+    for part in &message.parts {
+        match part {
+            LlmPart::Text(t) => text.push(t.as_str()),
+            // Ollama only accepts images, as raw base64 alongside the message text.
+            LlmPart::InlineData { mime_type, data, .. } if mime_type.starts_with("image/") => images.push(data.clone()),
+            LlmPart::InlineData { name, mime_type, .. } => {
+                notes.push(format!("[`{}` was attached, but this model can't read {} files.]", name, mime_type));
+            }
+            // Unlike OpenAI, Ollama expects the arguments as an object rather than a string.
+            LlmPart::ToolCall { name, args, .. } => tool_calls.push(json!({
+                "function": { "name": name, "arguments": args },
+            })),
+            LlmPart::ToolResult { name, response, .. } => tool_results.push(json!({
+                "role": "tool",
+                "tool_name": name,
+                "content": response,
+            })),
+        }
+    }
+
+    text.extend(notes.iter().map(String::as_str));
+
+    let mut messages = tool_results;
+    if !text.is_empty() || !tool_calls.is_empty() || !images.is_empty() {
+        let mut message = json!({ "role": role, "content": text.join("\n") });
+        if !tool_calls.is_empty() {
+            message["tool_calls"] = Value::Array(tool_calls);
+        }
+        if !images.is_empty() {
+            message["images"] = json!(images);
+        }
+        messages.push(message);
+    }
+    messages
+}
+
+/// `supports_thinking` says whether the model has the `thinking` capability. Ollama rejects
+/// `think` for models without it, so the thinking budget is ignored for them.
+fn build_request_body(model: &str, prompt: &LlmPrompt, supports_thinking: bool, stream: bool) -> Value {
+    let mut messages = Vec::new();
+    if let Some(system_instruction) = &prompt.system_instruction {
+        messages.push(json!({ "role": "system", "content": system_instruction }));
+    }
+    messages.extend(prompt.messages.iter().flat_map(message_to_json));
+
+    let mut body = json!({
+        "model": model,
+        "messages": messages,
+        "stream": stream,
+    });
+    let generation = &prompt.generation;
+    let mut options = serde_json::Map::new();
+    if let Some(temperature) = generation.temperature {
+        options.insert("temperature".to_string(), json!(temperature));
+    }
+    if let Some(top_p) = generation.top_p {
+        options.insert("top_p".to_string(), json!(top_p));
+    }
+    if let Some(top_k) = generation.top_k {
+        options.insert("top_k".to_string(), json!(top_k));
+    }
+    if let Some(max_output_tokens) = generation.max_output_tokens {
+        options.insert("num_predict".to_string(), json!(max_output_tokens));
+    }
+    if !generation.stop_sequences.is_empty() {
+        options.insert("stop".to_string(), json!(generation.stop_sequences));
+    }
+    if !options.is_empty() {
+        body["options"] = Value::Object(options);
+    }
+    // Ollama can only switch thinking on or off, for models that support it.
+    if let Some(thinking_budget) = generation.thinking_budget.filter(|_| supports_thinking) {
+        body["think"] = json!(thinking_budget > 0);
+    }
+    // Ollama constrains the output to a JSON schema given as `format`.
+    if let Some(schema) = &prompt.response_schema {
+        body["format"] = schema.clone();
+    }
+
+    let tools: Vec<Value> = prompt
+        .tools
+        .iter()
+        .flatten()
+        .flat_map(|tool| tool.function_declarations.iter())
+        .map(|declaration| {
+            json!({
+                "type": "function",
+                "function": {
+                    "name": declaration.get("name").cloned().unwrap_or(Value::Null),
+                    "description": declaration.get("description").cloned().unwrap_or_else(|| json!("")),
+                    "parameters": declaration.get("parameters").cloned().unwrap_or_else(|| json!({ "type": "object" })),
+                }
+            })
+        })
+        .collect();
+    if !tools.is_empty() {
+        body["tools"] = Value::Array(tools);
+    }
+    body
+}
+
+/// Returns the names of the models installed on the Ollama server at `base_url`.
+pub async fn list_models(base_url: &str) -> Result<Vec<String>, String> {
+    let url = format!("{}/api/tags", base_url.trim_end_matches('/'));
+    let response = super::build_http_client().get(&url).send().await.map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let message = error_message(&response.text().await.unwrap_or_default());
+        return Err(format!("Ollama Error [{}]: {}", status, message));
+    }
+    let tags: TagsResponse = response.json().await.map_err(|e| e.to_string())?;
+    let mut names: Vec<String> = tags.models.into_iter().map(|model| model.name).collect();
+    names.sort();
+    Ok(names)
+}
+
+/// One line of the progress stream returned by `/api/pull`.
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct PullProgress {
+    #[serde(default)]
+    pub status: String,
+    /// The size of the layer being downloaded, and how much of it has arrived.
+    pub total: Option<u64>,
+    pub completed: Option<u64>,
+    error: Option<String>,
+}
+
+impl PullProgress {
+    /// How far the current download is, from 0 to 1, if Ollama reported sizes.
+    pub fn fraction(&self) -> Option<f64> {
+        match (self.completed, self.total) {
+            (Some(completed), Some(total)) if total > 0 => Some(completed as f64 / total as f64),
+            _ => None,
+        }
+    }
+}
+
+/// Downloads `model` to the Ollama server at `base_url`, passing each progress update Ollama
+/// streams back to `on_progress`.
+pub async fn pull_model(base_url: &str, model: &str, mut on_progress: impl FnMut(PullProgress)) -> Result<(), String> {
+    let url = format!("{}/api/pull", base_url.trim_end_matches('/'));
+    // A download can take far longer than the timeout of the client used for chats.
+    let response = Client::new()
+        .post(&url)
+        .json(&json!({ "model": model, "stream": true }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let message = error_message(&response.text().await.unwrap_or_default());
+        return Err(format!("Ollama Error [{}]: {}", status, message));
+    }
+
+    let mut stream = response.bytes_stream();
+    let mut lines = LineBuffer::default();
+    while let Some(item) = stream.next().await {
+        let bytes = item.map_err(|e| e.to_string())?;
+        for line in lines.push(&bytes) {
+            if line.is_empty() {
+                continue;
+            }
+            let progress: PullProgress = serde_json::from_str(&line).map_err(|e| format!("Unexpected response from Ollama: {}", e))?;
+            if let Some(error) = progress.error {
+                return Err(error);
+            }
+            let done = progress.status == "success";
+            on_progress(progress);
+            if done {
+                return Ok(());
+            }
+        }
+    }
+    Err("Ollama closed the connection before the download finished".to_string())
+}
+
+pub struct OllamaProvider {
+    base_url: String,
+    client: Client,
+}
+
+impl OllamaProvider {
+    pub fn new(base_url: String) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client: super::build_http_client(),
+        }
+    }
+
+    /// Builds the `/api/chat` request for `prompt`, asking the server whether `model` can
+    /// think only when the prompt sets a thinking budget.
+    fn request(&self, model: String, prompt: LlmPrompt, stream: bool) -> impl std::future::Future<Output = reqwest::RequestBuilder> + Send + 'static {
+        let client = self.client.clone();
+        let base_url = self.base_url.clone();
+        async move {
+            let supports_thinking = prompt.generation.thinking_budget.is_some() && supports_thinking(&client, &base_url, &model).await;
+            client.post(format!("{}/api/chat", base_url)).json(&build_request_body(&model, &prompt, supports_thinking, stream))
+        }
+    }
+}
+
+/// Whether `model` reports the `thinking` capability. A model that can't be looked up is
+/// treated as not thinking.
+async fn supports_thinking(client: &Client, base_url: &str, model: &str) -> bool {
+    let response = client.post(format!("{}/api/show", base_url)).json(&json!({ "model": model })).send().await;
+    match response {
+        Ok(response) if response.status().is_success() => response
+            .json::<ShowResponse>()
+            .await
+            .map(|show| show.capabilities.iter().any(|capability| capability == "thinking"))
+            .unwrap_or(false),
+        Ok(response) => {
+            tracing::warn!("Couldn't look up the capabilities of Ollama model '{}': {}", model, response.status());
+            false
+        }
+        Err(e) => {
+            tracing::warn!("Couldn't look up the capabilities of Ollama model '{}': {}", model, e);
+            false
+        }
+    }
+}
+
+impl LlmProvider for OllamaProvider {
+    fn stream_chat(
+        &self,
+        model: String,
+        prompt: LlmPrompt,
+        tx: mpsc::UnboundedSender<StreamMessage>,
+        mcp_context: Option<McpContext>,
+    ) -> BoxFuture<'static, ()> {
+        tracing::info!("Using chat model: {} ({})", model, self.base_url);
+        let request = self.request(model, prompt, true);
+        Box::pin(async move {
+            stream_chat(request.await, tx, mcp_context).await;
+        })
+    }
+
+    fn complete(&self, model: String, prompt: LlmPrompt) -> BoxFuture<'static, Result<Completion, LlmError>> {
+        let request = self.request(model, prompt, false);
+        Box::pin(async move {
+            let response = super::send_with_retry(request.await, error_message)
+                .await
+                .inspect_err(|e| tracing::error!("Ollama Error: {}", e))?;
+            let response: ChatResponse = response.json().await.map_err(super::request_error)?;
+            if let Some(error) = response.error {
+                return Err(LlmError::Server { status: 500, message: error });
+            }
+            let usage = response.usage();
+            let text = response
+                .message
+                .map(|message| message.content)
+                .filter(|content| !content.is_empty())
+                .ok_or_else(|| LlmError::Parse { message: "Ollama returned no content".to_string() })?;
+            Ok(Completion { text, usage })
+        })
+    }
+}
+
+fn error_message(body_text: &str) -> String {
+    match serde_json::from_str::<OllamaErrorResponse>(body_text) {
+        Ok(error_response) => error_response.error,
+        Err(_) => body_text.to_string(),
+    }
+}
+
+async fn stream_chat(
+    request: reqwest::RequestBuilder,
+    tx: mpsc::UnboundedSender<StreamMessage>,
+    mcp_context: Option<McpContext>,
+) {
+    let response = match super::send_with_retry(request, error_message).await {
+        Ok(r) => r,
+        Err(e) => {
+            tracing::error!("Ollama Error: {}", e);
+            let _ = tx.send(StreamMessage::Error(e));
+            return;
+        }
+    };
+
+    let mut stream = response.bytes_stream();
+    let mut lines = LineBuffer::default();
+    let mut has_sent_data = false;
+    let mut done_reason: Option<String> = None;
+
+    'stream: while let Some(item) = stream.next().await {
+        let bytes = match item {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                tracing::error!("Error in stream: {}", e);
+                let _ = tx.send(StreamMessage::Error(super::request_error(e)));
+                return;
+            }
+        };
+        for line in lines.push(&bytes) {
+            if line.is_empty() {
+                continue;
+            }
+            let mut chunk = match serde_json::from_str::<ChatResponse>(&line) {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    tracing::error!("Failed to parse JSON chunk from stream: {}. Chunk: '{}'", e, line);
+                    let _ = tx.send(StreamMessage::Error(e.into()));
+                    return;
+                }
+            };
+            if let Some(error) = chunk.error {
+                tracing::error!("Ollama stream error: {}", error);
+                let _ = tx.send(StreamMessage::Error(LlmError::Server { status: 500, message: error }));
+                return;
+            }
+            if let Some(message) = chunk.message.take() {
+                if !message.content.is_empty() {
+                    if tx.send(StreamMessage::Text(message.content)).is_err() {
+                        return;
+                    }
+                    has_sent_data = true;
+                }
+                // Ollama delivers each tool call fully formed, rather than as deltas.
+                for call in message.tool_calls {
+                    let args = if call.function.arguments.is_null() { json!({}) } else { call.function.arguments };
+                    match super::resolve_tool_call(mcp_context.as_ref(), &call.function.name, args) {
+                        Some(tool_call) => {
+                            if tx.send(StreamMessage::ToolCall(tool_call)).is_err() {
+                                return;
+                            }
+                            has_sent_data = true;
+                        }
+                        None => tracing::error!("LLM requested tool '{}' which was not found in the provided context.", call.function.name),
+                    }
+                }
+            }
+            if chunk.done {
+                if let Some(usage) = chunk.usage() {
+                    let _ = tx.send(StreamMessage::Usage(usage));
+                }
+                if let Some(reason) = chunk.done_reason.as_deref() {
+                    let _ = tx.send(StreamMessage::Finish(FinishReason::from_provider(reason)));
+                }
+                done_reason = chunk.done_reason;
+                break 'stream;
+            }
+        }
+    }
+
+    if !has_sent_data {
+        let default_message = match done_reason.as_deref() {
+            Some(reason) => format!("[Hobbes did not provide a response. Finish Reason: {}]", reason),
+            None => "[Hobbes did not provide a response due to an internal error.]".to_string(),
+        };
+        if tx.send(StreamMessage::Text(default_message)).is_err() {
+            tracing::error!("Failed to send default message to UI.");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hobbes_core::generation::GenerationParams;
+
+    #[test]
+    fn test_generation_params_and_schema_serialize_as_options_and_format() {
+        let schema = json!({ "type": "object", "properties": { "summary": { "type": "string" } } });
+        let prompt = LlmPrompt {
+            system_instruction: Some("Be brief.".to_string()),
+            messages: vec![LlmMessage { role: LlmRole::User, parts: vec![LlmPart::Text("Hi".to_string())] }],
+            tools: None,
+            response_schema: Some(schema.clone()),
+            generation: GenerationParams {
+                temperature: Some(0.5),
+                top_k: Some(40),
+                max_output_tokens: Some(256),
+                stop_sequences: vec!["END".to_string()],
+                thinking_budget: Some(0),
+                ..Default::default()
+            },
+        };
+
+        let body = build_request_body("llama3.2", &prompt, true, false);
+
+        assert_eq!(body["options"], json!({ "temperature": 0.5, "top_k": 40, "num_predict": 256, "stop": ["END"] }));
+        assert_eq!(body["think"], json!(false));
+        assert_eq!(body["format"], schema);
+        assert_eq!(body["messages"][0], json!({ "role": "system", "content": "Be brief." }));
+
+        // Models without the thinking capability never get `think`.
+        assert!(build_request_body("llama3.2", &prompt, false, false).get("think").is_none());
+    }
+
+    #[test]
+    fn test_pull_progress_parses_from_split_lines() {
+        let mut lines = LineBuffer::default();
+        assert!(lines.push(br#"{"status":"pulling abc","total":200,"#).is_empty());
+        let line = lines.push(b"\"completed\":50}\n").remove(0);
+
+        let progress: PullProgress = serde_json::from_str(&line).unwrap();
+        assert_eq!(progress.status, "pulling abc");
+        assert_eq!(progress.fraction(), Some(0.25));
+        assert_eq!(serde_json::from_str::<PullProgress>(r#"{"status":"success"}"#).unwrap().fraction(), None);
+    }
+}
@@ -0,0 +1,467 @@
+//! OpenAI-compatible `/v1/chat/completions` backend.
+//!
+//! Works against api.openai.com as well as local servers that implement the same API
+//! (llama.cpp `server`, vLLM, LM Studio, ...).
+use futures::future::BoxFuture;
+use futures_util::StreamExt;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use tokio::sync::mpsc;
+
+use super::{Completion, LineBuffer, LlmError, LlmMessage, LlmPart, LlmProvider, LlmRole};
+use crate::components::shared::StreamMessage;
+use hobbes_core::message::TokenUsage;
+use hobbes_core::models::FinishReason;
+use crate::context::prompt_builder::LlmPrompt;
+use hobbes_core::mcp::McpContext;
+
+#[derive(Deserialize, Debug)]
+struct ChatCompletionChunk {
+    #[serde(default)]
+    choices: Vec<ChunkChoice>,
+    /// Only present on the final chunk, when `stream_options.include_usage` is set.
+    usage: Option<Usage>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+struct Usage {
+    #[serde(default)]
+    prompt_tokens: u64,
+    #[serde(default)]
+    completion_tokens: u64,
+    #[serde(default)]
+    total_tokens: u64,
+}
+
+impl From<Usage> for TokenUsage {
+    fn from(usage: Usage) -> Self {
+        TokenUsage {
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            total_tokens: usage.total_tokens,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ChunkChoice {
+    #[serde(default)]
+    delta: Delta,
+    finish_reason: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct Delta {
+    content: Option<String>,
+    #[serde(default)]
+    tool_calls: Vec<ToolCallDelta>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ToolCallDelta {
+    #[serde(default)]
+    index: usize,
+    function: Option<FunctionDelta>,
+}
+
+#[derive(Deserialize, Debug)]
+struct FunctionDelta {
+    name: Option<String>,
+    arguments: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ChatCompletion {
+    #[serde(default)]
+    choices: Vec<CompletionChoice>,
+    usage: Option<Usage>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CompletionChoice {
+    message: CompletionMessage,
+    finish_reason: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CompletionMessage {
+    content: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenAiErrorResponse {
+    error: OpenAiError,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenAiError {
+    message: String,
+}
+
+/// A function call assembled from streamed `tool_calls` deltas.
+#[derive(Default, Debug)]
+struct PendingToolCall {
+    name: String,
+    arguments: String,
+}
+
+/// Converts a neutral message into one or more chat completion messages. Tool results
+/// become separate `tool` role messages, as the API requires.
+fn message_to_json(message: &LlmMessage) -> Vec<Value> {
+    let role = match message.role {
+        LlmRole::User => "user",
+        LlmRole::Assistant => "assistant",
+    };
+    let mut text = Vec::new();
+    let mut files = Vec::new();
+    let mut tool_calls = Vec::new();
+    let mut tool_results = Vec::new();
+    for part in &message.parts {
+        match part {
+            LlmPart::Text(t) => text.push(t.as_str()),
+            LlmPart::ToolCall { id, name, args } => tool_calls.push(json!({
+                "id": id,
+                "type": "function",
+                "function": { "name": name, "arguments": args.to_string() },
+            })),
+            LlmPart::ToolResult { id, response, .. } => tool_results.push(json!({
+                "role": "tool",
+                "tool_call_id": id,
+                "content": response,
+            })),
+            LlmPart::InlineData { name, mime_type, data } => {
+                let url = format!("data:{};base64,{}", mime_type, data);
+                files.push(if mime_type.starts_with("image/") {
+                    json!({ "type": "image_url", "image_url": { "url": url } })
+                } else {
+                    json!({ "type": "file", "file": { "filename": name, "file_data": url } })
+                });
+            }
+        }
+    }
+
+    let mut messages = tool_results;
+    if text.is_empty() && files.is_empty() && tool_calls.is_empty() {
+        return messages;
+    }
+    let content = if files.is_empty() {
+        Value::String(text.join("\n"))
+    } else {
+        // Attachments require the array form of `content`.
+        let mut content: Vec<Value> = text.iter().map(|t| json!({ "type": "text", "text": t })).collect();
+        content.extend(files);
+        Value::Array(content)
+    };
+    let mut message = json!({ "role": role, "content": content });
+    if !tool_calls.is_empty() {
+        message["tool_calls"] = Value::Array(tool_calls);
+    }
+    messages.push(message);
+    messages
+}
+
+/// Converts the provider-neutral prompt into the chat completions request body.
+fn build_request_body(model: &str, prompt: &LlmPrompt, stream: bool) -> Value {
+    let mut messages = Vec::new();
+    if let Some(system_instruction) = &prompt.system_instruction {
+        messages.push(json!({ "role": "system", "content": system_instruction }));
+    }
+    messages.extend(prompt.messages.iter().flat_map(message_to_json));
+
+    let mut body = json!({
+        "model": model,
+        "messages": messages,
+        "stream": stream,
+    });
+    if stream {
+        body["stream_options"] = json!({ "include_usage": true });
+    }
+    // `top_k` and the thinking budget have no equivalent in the chat completions API.
+    let generation = &prompt.generation;
+    if let Some(temperature) = generation.temperature {
+        body["temperature"] = json!(temperature);
+    }
+    if let Some(top_p) = generation.top_p {
+        body["top_p"] = json!(top_p);
+    }
+    if let Some(max_output_tokens) = generation.max_output_tokens {
+        body["max_tokens"] = json!(max_output_tokens);
+    }
+    if !generation.stop_sequences.is_empty() {
+        body["stop"] = json!(generation.stop_sequences);
+    }
+    if let Some(schema) = &prompt.response_schema {
+        body["response_format"] = json!({
+            "type": "json_schema",
+            "json_schema": { "name": "response", "schema": schema },
+        });
+    }
+
+    let tools: Vec<Value> = prompt
+        .tools
+        .iter()
+        .flatten()
+        .flat_map(|tool| tool.function_declarations.iter())
+        .map(|declaration| {
+            json!({
+                "type": "function",
+                "function": {
+                    "name": declaration.get("name").cloned().unwrap_or(Value::Null),
+                    "description": declaration.get("description").cloned().unwrap_or_else(|| json!("")),
+                    "parameters": declaration.get("parameters").cloned().unwrap_or_else(|| json!({ "type": "object" })),
+                }
+            })
+        })
+        .collect();
+    if !tools.is_empty() {
+        body["tools"] = Value::Array(tools);
+    }
+    body
+}
+
+pub struct OpenAiProvider {
+    base_url: String,
+    api_key: Option<String>,
+    client: Client,
+}
+
+impl OpenAiProvider {
+    pub fn new(base_url: String, api_key: Option<String>) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+            client: super::build_http_client(),
+        }
+    }
+
+    fn request(&self, body: &Value) -> reqwest::RequestBuilder {
+        let request = self.client.post(format!("{}/chat/completions", self.base_url)).json(body);
+        match &self.api_key {
+            Some(api_key) if !api_key.is_empty() => request.bearer_auth(api_key),
+            _ => request,
+        }
+    }
+}
+
+impl LlmProvider for OpenAiProvider {
+    fn stream_chat(
+        &self,
+        model: String,
+        prompt: LlmPrompt,
+        tx: mpsc::UnboundedSender<StreamMessage>,
+        mcp_context: Option<McpContext>,
+    ) -> BoxFuture<'static, ()> {
+        tracing::info!("Using chat model: {} ({})", model, self.base_url);
+        let request = self.request(&build_request_body(&model, &prompt, true));
+        Box::pin(async move {
+            stream_chat_completions(request, tx, mcp_context).await;
+        })
+    }
+
+    fn complete(&self, model: String, prompt: LlmPrompt) -> BoxFuture<'static, Result<Completion, LlmError>> {
+        let request = self.request(&build_request_body(&model, &prompt, false));
+        Box::pin(async move {
+            let response = super::send_with_retry(request, error_message)
+                .await
+                .inspect_err(|e| tracing::error!("OpenAI API Error: {}", e))?;
+            let completion: ChatCompletion = response.json().await.map_err(super::request_error)?;
+            let choice = completion.choices.into_iter().next();
+            if choice.as_ref().and_then(|choice| choice.finish_reason.as_deref()) == Some("content_filter") {
+                return Err(LlmError::Safety { message: "The response was withheld (content_filter).".to_string() });
+            }
+            let text = choice
+                .and_then(|choice| choice.message.content)
+                .ok_or_else(|| LlmError::Parse { message: "Chat completion returned no content".to_string() })?;
+            Ok(Completion { text, usage: completion.usage.map(TokenUsage::from) })
+        })
+    }
+}
+
+fn error_message(body_text: &str) -> String {
+    match serde_json::from_str::<OpenAiErrorResponse>(body_text) {
+        Ok(error_response) => error_response.error.message,
+        Err(_) => body_text.to_string(),
+    }
+}
+
+/// Sends every fully assembled tool call to the UI and returns how many were sent. Returns
+/// `None` if the stream should end: the receiver is gone, or a call's arguments couldn't be
+/// parsed, which is reported as an error.
+fn flush_tool_calls(
+    pending: &mut BTreeMap<usize, PendingToolCall>,
+    mcp_context: Option<&McpContext>,
+    tx: &mpsc::UnboundedSender<StreamMessage>,
+) -> Option<usize> {
+    let mut sent = 0;
+    for (_, call) in std::mem::take(pending) {
+        let args = if call.arguments.trim().is_empty() {
+            json!({})
+        } else {
+            match serde_json::from_str(&call.arguments) {
+                Ok(args) => args,
+                Err(e) => {
+                    tracing::error!("Failed to parse arguments for tool '{}': {}. Arguments: '{}'", call.name, e, call.arguments);
+                    let _ = tx.send(StreamMessage::Error(LlmError::Parse {
+                        message: format!("The arguments for tool '{}' are not valid JSON: {}", call.name, e),
+                    }));
+                    return None;
+                }
+            }
+        };
+        match super::resolve_tool_call(mcp_context, &call.name, args) {
+            Some(tool_call) => {
+                if tx.send(StreamMessage::ToolCall(tool_call)).is_err() {
+                    return None;
+                }
+                sent += 1;
+            }
+            None => tracing::error!("LLM requested tool '{}' which was not found in the provided context.", call.name),
+        }
+    }
+    Some(sent)
+}
+
+async fn stream_chat_completions(
+    request: reqwest::RequestBuilder,
+    tx: mpsc::UnboundedSender<StreamMessage>,
+    mcp_context: Option<McpContext>,
+) {
+    let response = match super::send_with_retry(request, error_message).await {
+        Ok(r) => r,
+        Err(e) => {
+            tracing::error!("OpenAI API Error: {}", e);
+            let _ = tx.send(StreamMessage::Error(e));
+            return;
+        }
+    };
+
+    let mut stream = response.bytes_stream();
+    let mut lines = LineBuffer::default();
+    let mut pending_tool_calls: BTreeMap<usize, PendingToolCall> = BTreeMap::new();
+    let mut has_sent_data = false;
+    let mut finish_reason: Option<String> = None;
+    let mut usage: Option<Usage> = None;
+    let mut stream_error: Option<LlmError> = None;
+
+    'stream: while let Some(item) = stream.next().await {
+        let bytes = match item {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                tracing::error!("Error in stream: {}", e);
+                stream_error = Some(super::request_error(e));
+                break;
+            }
+        };
+        for line in lines.push(&bytes) {
+            let Some(json_str) = line.strip_prefix("data:").map(str::trim) else { continue };
+            if json_str.is_empty() {
+                continue;
+            }
+            if json_str == "[DONE]" {
+                break 'stream;
+            }
+            let chunk = match serde_json::from_str::<ChatCompletionChunk>(json_str) {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    tracing::error!("Failed to parse JSON chunk from stream: {}. Chunk: '{}'", e, json_str);
+                    stream_error = Some(e.into());
+                    break 'stream;
+                }
+            };
+            if chunk.usage.is_some() {
+                usage = chunk.usage;
+            }
+            for choice in chunk.choices {
+                if let Some(text) = choice.delta.content.filter(|t| !t.is_empty()) {
+                    if tx.send(StreamMessage::Text(text)).is_err() {
+                        return;
+                    }
+                    has_sent_data = true;
+                }
+                for delta in choice.delta.tool_calls {
+                    let pending = pending_tool_calls.entry(delta.index).or_default();
+                    if let Some(function) = delta.function {
+                        if let Some(name) = function.name {
+                            pending.name.push_str(&name);
+                        }
+                        if let Some(arguments) = function.arguments {
+                            pending.arguments.push_str(&arguments);
+                        }
+                    }
+                }
+                if let Some(reason) = choice.finish_reason {
+                    if reason != "stop" && reason != "tool_calls" {
+                        tracing::warn!("Chat completion stream finished with reason: {}", reason);
+                    }
+                    finish_reason = Some(reason);
+                }
+            }
+        }
+    }
+
+    if let Some(usage) = usage {
+        let _ = tx.send(StreamMessage::Usage(usage.into()));
+    }
+    if let Some(reason) = finish_reason.as_deref() {
+        let _ = tx.send(StreamMessage::Finish(FinishReason::from_provider(reason)));
+    }
+
+    if finish_reason.as_deref() == Some("content_filter") {
+        stream_error = Some(LlmError::Safety { message: "The response was withheld (content_filter).".to_string() });
+    }
+    if let Some(error) = stream_error {
+        let _ = tx.send(StreamMessage::Error(error));
+        return;
+    }
+
+    match flush_tool_calls(&mut pending_tool_calls, mcp_context.as_ref(), &tx) {
+        Some(sent) => has_sent_data |= sent > 0,
+        None => return,
+    }
+
+    if !has_sent_data {
+        let default_message = match finish_reason.as_deref() {
+            Some(reason) => format!("[Hobbes did not provide a response. Finish Reason: {}]", reason),
+            None => "[Hobbes did not provide a response due to an internal error.]".to_string(),
+        };
+        if tx.send(StreamMessage::Text(default_message)).is_err() {
+            tracing::error!("Failed to send default message to UI.");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attachments_keep_tool_calls() {
+        let message = LlmMessage {
+            role: LlmRole::Assistant,
+            parts: vec![
+                LlmPart::InlineData { name: "chart.png".to_string(), mime_type: "image/png".to_string(), data: "AAAA".to_string() },
+                LlmPart::ToolCall { id: "1".to_string(), name: "fetch".to_string(), args: json!({ "url": "https://example.com" }) },
+            ],
+        };
+
+        assert_eq!(
+            message_to_json(&message),
+            [json!({
+                "role": "assistant",
+                "content": [{ "type": "image_url", "image_url": { "url": "data:image/png;base64,AAAA" } }],
+                "tool_calls": [{ "id": "1", "type": "function", "function": { "name": "fetch", "arguments": "{\"url\":\"https://example.com\"}" } }],
+            })]
+        );
+    }
+
+    #[test]
+    fn test_unparseable_tool_arguments_are_reported() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut pending = BTreeMap::from([(0, PendingToolCall { name: "fetch".to_string(), arguments: "{\"url\":".to_string() })]);
+
+        assert_eq!(flush_tool_calls(&mut pending, None, &tx), None);
+        assert!(matches!(rx.try_recv(), Ok(StreamMessage::Error(LlmError::Parse { .. }))));
+    }
+}
@@ -0,0 +1,438 @@
+use dioxus::prelude::*;
+use hobbes_core::mcp::McpContext;
+use rmcp::model::Tool;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use crate::mcp::manager::{McpManager, McpServerConfig, RemoteTransport};
+use crate::settings::Settings;
+
+const INPUT_CLASS: &str = "mt-1 block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-sm shadow-sm placeholder-gray-400 focus:outline-none focus:border-indigo-500 focus:ring-1 focus:ring-indigo-500";
+const BUTTON_CLASS: &str = "px-2 py-1 text-xs rounded-md bg-gray-600 hover:bg-gray-500 disabled:opacity-50";
+
+/// A server being edited. The list fields hold one entry per line.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct ServerForm {
+    name: String,
+    description: String,
+    remote: bool,
+    command: String,
+    args: String,
+    cwd: String,
+    env: String,
+    url: String,
+    transport: RemoteTransport,
+    headers: String,
+    disabled: bool,
+}
+
+fn format_pairs(pairs: &HashMap<String, String>, separator: &str) -> String {
+    let mut lines: Vec<String> = pairs.iter().map(|(key, value)| format!("{}{}{}", key, separator, value)).collect();
+    lines.sort();
+    lines.join("\n")
+}
+
+/// Reads `KEY<separator>value` lines, ignoring blank ones.
+fn parse_pairs(text: &str, separator: char, what: &str) -> Result<HashMap<String, String>, String> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| match line.split_once(separator) {
+            Some((key, value)) if !key.trim().is_empty() => Ok((key.trim().to_string(), value.trim().to_string())),
+            _ => Err(format!("Each {} needs to look like `NAME{}value`: {}", what, separator, line.trim())),
+        })
+        .collect()
+}
+
+impl ServerForm {
+    fn from_config(config: &McpServerConfig) -> Self {
+        Self {
+            name: config.name.clone(),
+            description: config.description.clone(),
+            remote: config.url.is_some(),
+            command: config.command.clone(),
+            args: config.args.join("\n"),
+            cwd: config.cwd.as_ref().map(|cwd| cwd.display().to_string()).unwrap_or_default(),
+            env: format_pairs(&config.env, "="),
+            url: config.url.clone().unwrap_or_default(),
+            transport: config.transport,
+            headers: format_pairs(&config.headers, ": "),
+            disabled: config.disabled,
+        }
+    }
+
+    fn to_config(&self) -> Result<McpServerConfig, String> {
+        let name = self.name.trim();
+        if name.is_empty() {
+            return Err("A server needs a name".to_string());
+        }
+        if self.remote && self.url.trim().is_empty() {
+            return Err("A remote server needs a URL".to_string());
+        }
+        if !self.remote && self.command.trim().is_empty() {
+            return Err("A local server needs a command".to_string());
+        }
+        let config = McpServerConfig {
+            name: name.to_string(),
+            description: self.description.trim().to_string(),
+            disabled: self.disabled,
+            ..Default::default()
+        };
+        Ok(if self.remote {
+            McpServerConfig {
+                url: Some(self.url.trim().to_string()),
+                transport: self.transport,
+                headers: parse_pairs(&self.headers, ':', "header")?,
+                ..config
+            }
+        } else {
+            McpServerConfig {
+                command: self.command.trim().to_string(),
+                args: self.args.lines().map(str::trim).filter(|arg| !arg.is_empty()).map(str::to_string).collect(),
+                cwd: Some(self.cwd.trim()).filter(|cwd| !cwd.is_empty()).map(PathBuf::from),
+                env: parse_pairs(&self.env, '=', "variable")?,
+                ..config
+            }
+        })
+    }
+}
+
+/// Lists the servers in `mcp_servers.json` and lets them be added, edited, enabled, disabled
+/// and deleted. Changes are saved right away and the manager applies them.
+#[component]
+pub fn McpServerSettings() -> Element {
+    let mcp_manager = use_context::<Signal<McpManager>>();
+    let mcp_context = use_context::<Signal<McpContext>>();
+    // The server being edited: `Some(None)` for a new one.
+    let mut editing = use_signal(|| None::<Option<McpServerConfig>>);
+    let mut confirm_delete = use_signal(|| None::<String>);
+    let mut error = use_signal(|| None::<String>);
+
+    // The context is republished whenever the configuration is reloaded.
+    let context = mcp_context.read();
+    let configs = mcp_manager.peek().configs();
+
+    let save = move |original_name: String, config: McpServerConfig| {
+        // A new attempt replaces whatever failed before.
+        let mut error = error;
+        error.set(None);
+        let manager = mcp_manager.peek().clone();
+        spawn(async move {
+            if let Err(e) = manager.save_server(Some(&original_name), config).await {
+                error.set(Some(e));
+            }
+        });
+    };
+
+    rsx! {
+        div {
+            class: "mt-6 pt-4 border-t border-gray-700",
+            div {
+                class: "flex items-center justify-between mb-1",
+                h3 { class: "text-md font-semibold", "MCP Servers" }
+                button {
+                    class: BUTTON_CLASS,
+                    disabled: editing().is_some(),
+                    onclick: move |_| editing.set(Some(None)),
+                    "Add Server"
+                }
+            }
+            p {
+                class: "text-xs text-gray-400 mb-3",
+                "Saved to mcp_servers.json and applied right away."
+            }
+            if let Some(e) = error() {
+                p { class: "mb-2 text-xs text-red-400 break-words", "{e}" }
+            }
+            if let Some(original) = editing() {
+                McpServerEditor {
+                    original,
+                    onclose: move |_| editing.set(None),
+                }
+            }
+            for config in configs.into_iter() {
+                div {
+                    key: "{config.name}",
+                    class: "mt-3 p-2 rounded-md bg-gray-700",
+                    div {
+                        class: "flex items-center justify-between",
+                        span { class: "text-sm font-medium", "{config.name}" }
+                        label {
+                            class: "flex items-center gap-1 text-xs text-gray-300",
+                            input {
+                                r#type: "checkbox",
+                                checked: !config.disabled,
+                                onchange: {
+                                    let config = config.clone();
+                                    move |event: FormEvent| {
+                                        let config = McpServerConfig { disabled: !event.checked(), ..config.clone() };
+                                        save(config.name.clone(), config);
+                                    }
+                                }
+                            }
+                            "Enabled"
+                        }
+                    }
+                    p {
+                        class: "mt-1 text-xs font-mono text-gray-400 truncate",
+                        match &config.url {
+                            Some(url) => url.clone(),
+                            None => std::iter::once(config.command.as_str()).chain(config.args.iter().map(String::as_str)).collect::<Vec<_>>().join(" "),
+                        }
+                    }
+                    if !config.description.is_empty() {
+                        p { class: "mt-1 text-xs text-gray-400", "{config.description}" }
+                    }
+                    if let Some(server) = context.servers.iter().find(|server| server.name == config.name) {
+                        ToolList { tools: server.tools.clone() }
+                    }
+                    div {
+                        class: "mt-2 flex gap-2",
+                        button {
+                            class: BUTTON_CLASS,
+                            disabled: editing().is_some(),
+                            onclick: {
+                                let config = config.clone();
+                                move |_| editing.set(Some(Some(config.clone())))
+                            },
+                            "Edit"
+                        }
+                        if confirm_delete().as_deref() == Some(config.name.as_str()) {
+                            button {
+                                class: "px-2 py-1 text-xs rounded-md bg-red-700 hover:bg-red-600",
+                                onclick: {
+                                    let name = config.name.clone();
+                                    move |_| {
+                                        confirm_delete.set(None);
+                                        error.set(None);
+                                        let manager = mcp_manager.peek().clone();
+                                        let name = name.clone();
+                                        spawn(async move {
+                                            if let Err(e) = manager.delete_server(&name).await {
+                                                error.set(Some(e));
+                                            }
+                                        });
+                                    }
+                                },
+                                "Confirm Delete"
+                            }
+                            button {
+                                class: BUTTON_CLASS,
+                                onclick: move |_| confirm_delete.set(None),
+                                "Cancel"
+                            }
+                        } else {
+                            button {
+                                class: BUTTON_CLASS,
+                                onclick: {
+                                    let name = config.name.clone();
+                                    move |_| confirm_delete.set(Some(name.clone()))
+                                },
+                                "Delete"
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Edits one server. "Test connection" starts it as entered, without saving, and shows the
+/// tools it offers.
+#[component]
+fn McpServerEditor(original: Option<McpServerConfig>, onclose: EventHandler<()>) -> Element {
+    let mcp_manager = use_context::<Signal<McpManager>>();
+    let settings = use_context::<Signal<Settings>>();
+    let mut form = use_signal(|| original.as_ref().map(ServerForm::from_config).unwrap_or_default());
+    let mut error = use_signal(|| None::<String>);
+    let mut testing = use_signal(|| false);
+    let mut test_result = use_signal(|| None::<Result<Vec<Tool>, String>>);
+    let original_name = original.as_ref().map(|config| config.name.clone());
+
+    rsx! {
+        div {
+            class: "mt-3 p-3 rounded-md border border-gray-600 space-y-3 text-sm",
+            div {
+                label { "Name" }
+                input {
+                    class: INPUT_CLASS,
+                    value: "{form.read().name}",
+                    oninput: move |event| form.write().name = event.value(),
+                }
+            }
+            div {
+                label { "Description" }
+                input {
+                    class: INPUT_CLASS,
+                    value: "{form.read().description}",
+                    oninput: move |event| form.write().description = event.value(),
+                }
+            }
+            div {
+                label { "Runs" }
+                select {
+                    class: INPUT_CLASS,
+                    onchange: move |event| form.write().remote = event.value() == "remote",
+                    option { value: "local", selected: !form.read().remote, "Locally, as a command" }
+                    option { value: "remote", selected: form.read().remote, "Remotely, at a URL" }
+                }
+            }
+            if form.read().remote {
+                div {
+                    label { "URL" }
+                    input {
+                        class: INPUT_CLASS,
+                        placeholder: "https://example.com/mcp",
+                        value: "{form.read().url}",
+                        oninput: move |event| form.write().url = event.value(),
+                    }
+                }
+                div {
+                    label { "Transport" }
+                    select {
+                        class: INPUT_CLASS,
+                        onchange: move |event| {
+                            form.write().transport = if event.value() == "sse" { RemoteTransport::Sse } else { RemoteTransport::StreamableHttp };
+                        },
+                        option { value: "streamable_http", selected: form.read().transport == RemoteTransport::StreamableHttp, "Streamable HTTP" }
+                        option { value: "sse", selected: form.read().transport == RemoteTransport::Sse, "SSE" }
+                    }
+                }
+                div {
+                    label { "Headers" }
+                    textarea {
+                        class: "{INPUT_CLASS} font-mono",
+                        rows: 3,
+                        placeholder: "Authorization: Bearer ...",
+                        value: "{form.read().headers}",
+                        oninput: move |event| form.write().headers = event.value(),
+                    }
+                }
+            } else {
+                div {
+                    label { "Command" }
+                    input {
+                        class: "{INPUT_CLASS} font-mono",
+                        placeholder: "npx",
+                        value: "{form.read().command}",
+                        oninput: move |event| form.write().command = event.value(),
+                    }
+                }
+                div {
+                    label { "Arguments, one per line" }
+                    textarea {
+                        class: "{INPUT_CLASS} font-mono",
+                        rows: 3,
+                        value: "{form.read().args}",
+                        oninput: move |event| form.write().args = event.value(),
+                    }
+                }
+                div {
+                    label { "Working directory" }
+                    input {
+                        class: "{INPUT_CLASS} font-mono",
+                        value: "{form.read().cwd}",
+                        oninput: move |event| form.write().cwd = event.value(),
+                    }
+                }
+                div {
+                    label { "Environment" }
+                    textarea {
+                        class: "{INPUT_CLASS} font-mono",
+                        rows: 3,
+                        placeholder: "API_KEY=...",
+                        value: "{form.read().env}",
+                        oninput: move |event| form.write().env = event.value(),
+                    }
+                }
+            }
+            if let Some(e) = error() {
+                p { class: "text-xs text-red-400 break-words", "{e}" }
+            }
+            match test_result() {
+                Some(Ok(tools)) => rsx! {
+                    p { class: "text-xs text-green-400", "Connected. The server offers {tools.len()} tools." }
+                    ToolList { tools }
+                },
+                Some(Err(e)) => rsx! { p { class: "text-xs text-red-400 break-words", "Test failed: {e}" } },
+                None => rsx! {},
+            }
+            div {
+                class: "flex justify-end gap-2",
+                button {
+                    class: BUTTON_CLASS,
+                    disabled: testing(),
+                    onclick: move |_| {
+                        let config = match form.read().to_config() {
+                            Ok(config) => config,
+                            Err(e) => {
+                                error.set(Some(e));
+                                return;
+                            }
+                        };
+                        error.set(None);
+                        test_result.set(None);
+                        testing.set(true);
+                        let settings = settings.read().clone();
+                        spawn(async move {
+                            test_result.set(Some(McpManager::test_server(&config, &settings).await));
+                            testing.set(false);
+                        });
+                    },
+                    if testing() { "Testing..." } else { "Test Connection" }
+                }
+                button {
+                    class: BUTTON_CLASS,
+                    onclick: move |_| onclose.call(()),
+                    "Cancel"
+                }
+                button {
+                    class: "px-2 py-1 text-xs rounded-md bg-purple-600 hover:bg-purple-700",
+                    onclick: move |_| {
+                        let config = match form.read().to_config() {
+                            Ok(config) => config,
+                            Err(e) => {
+                                error.set(Some(e));
+                                return;
+                            }
+                        };
+                        error.set(None);
+                        let manager = mcp_manager.peek().clone();
+                        let original_name = original_name.clone();
+                        spawn(async move {
+                            match manager.save_server(original_name.as_deref(), config).await {
+                                Ok(()) => onclose.call(()),
+                                Err(e) => error.set(Some(e)),
+                            }
+                        });
+                    },
+                    "Save"
+                }
+            }
+        }
+    }
+}
+
+/// A server's tools, with their descriptions and input schemas.
+#[component]
+fn ToolList(tools: Vec<Tool>) -> Element {
+    rsx! {
+        details {
+            class: "mt-2 text-xs",
+            summary { class: "cursor-pointer text-gray-300", "{tools.len()} tools" }
+            for tool in tools.iter() {
+                div {
+                    key: "{tool.name}",
+                    class: "mt-2 pl-2 border-l border-gray-600",
+                    p { class: "font-mono text-gray-200", "{tool.name}" }
+                    if let Some(description) = &tool.description {
+                        p { class: "text-gray-400", "{description}" }
+                    }
+                    pre {
+                        class: "mt-1 p-2 overflow-x-auto rounded bg-gray-800 text-gray-400",
+                        {serde_json::to_string_pretty(&*tool.input_schema).unwrap_or_default()}
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use dioxus::prelude::*;
+use hobbes_core::mcp::{McpContext, McpServerState};
+use crate::mcp::manager::McpManager;
+
+/// How often uptimes are refreshed.
+const UPTIME_REFRESH: std::time::Duration = std::time::Duration::from_secs(30);
+
+fn format_uptime(since: chrono::DateTime<chrono::Utc>) -> String {
+    let minutes = (chrono::Utc::now() - since).num_minutes().max(0);
+    match minutes {
+        0 => "less than a minute".to_string(),
+        m if m < 60 => format!("{}m", m),
+        m => format!("{}h {}m", m / 60, m % 60),
+    }
+}
+
+/// The state of each started MCP server, with its last error and uptime. Any server can be
+/// restarted from here.
+#[component]
+pub fn McpStatusPanel() -> Element {
+    let mcp_context = use_context::<Signal<McpContext>>();
+    let mcp_manager = use_context::<Signal<McpManager>>();
+    let mut tick = use_signal(|| 0u64);
+    use_future(move || async move {
+        loop {
+            tokio::time::sleep(UPTIME_REFRESH).await;
+            tick += 1;
+        }
+    });
+    // Re-rendered on every tick so uptimes stay current.
+    let _ = tick();
+
+    let context = mcp_context.read();
+    rsx! {
+        div {
+            class: "mt-6 pt-4 border-t border-gray-700",
+            h3 {
+                class: "text-md font-semibold mb-1",
+                "MCP Server Status"
+            }
+            if context.status.is_empty() {
+                p { class: "text-xs text-gray-400", "No MCP servers are running." }
+            }
+            for status in context.status.iter() {
+                div {
+                    key: "{status.name}",
+                    class: "mt-3 p-2 rounded-md bg-gray-700",
+                    div {
+                        class: "flex items-center justify-between",
+                        span { class: "text-sm font-medium", "{status.name}" }
+                        span {
+                            class: match status.state {
+                                McpServerState::Ready => "text-xs text-green-400",
+                                McpServerState::Starting => "text-xs text-yellow-400",
+                                McpServerState::Failed | McpServerState::Crashed { .. } => "text-xs text-red-400",
+                            },
+                            "{status.state.label()}"
+                        }
+                    }
+                    if let Some(since) = status.ready_since {
+                        p {
+                            class: "mt-1 text-xs text-gray-400",
+                            {
+                                let tools = context.servers.iter().find(|s| s.name == status.name).map_or(0, |s| s.tools.len());
+                                format!("Up {} · {} tools", format_uptime(since), tools)
+                            }
+                        }
+                    }
+                    if let Some(error) = &status.last_error {
+                        p { class: "mt-1 text-xs text-red-400 break-words", "{error}" }
+                    }
+                    if status.restarts > 0 {
+                        p { class: "mt-1 text-xs text-gray-500", "Restarted {status.restarts} times" }
+                    }
+                    button {
+                        class: "mt-2 px-2 py-1 text-xs rounded-md bg-gray-600 hover:bg-gray-500 disabled:opacity-50",
+                        disabled: status.state == McpServerState::Starting,
+                        onclick: {
+                            let name = status.name.clone();
+                            move |_| {
+                                let manager = mcp_manager.peek().clone();
+                                let name = name.clone();
+                                spawn(async move {
+                                    if let Err(e) = manager.restart_server(&name).await {
+                                        tracing::error!("Failed to restart MCP server '{}': {}", name, e);
+                                    }
+                                });
+                            }
+                        },
+                        if status.state == McpServerState::Ready { "Restart" } else { "Retry" }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -5,4 +5,7 @@ pub mod settings_panel;
 pub mod stream_manager;
 pub mod tool_call_display;
 pub mod shared;
-pub mod link_with_controls;
\ No newline at end of file
+pub mod link_with_controls;
+pub mod recovery_prompt;
+pub mod mcp_status;
+pub mod mcp_settings;
\ No newline at end of file
@@ -0,0 +1,63 @@
+use dioxus::prelude::*;
+use dioxus_free_icons::{Icon, icons::fi_icons};
+use hobbes_core::session::SessionState;
+
+/// Shown when the saved sessions couldn't be read at startup. Offers to restore the newest
+/// backup, or to carry on with no sessions.
+#[component]
+pub fn RecoveryPrompt() -> Element {
+    let mut session_state = consume_context::<Signal<SessionState>>();
+    let mut restore_error = use_signal(|| None::<String>);
+    let Some(recovery) = session_state.read().recovery.clone() else { return rsx! {} };
+
+    let quarantined = recovery.quarantined.display().to_string();
+    let backup_time = recovery.backup.as_ref()
+        .map(|backup| backup.taken_at.with_timezone(&chrono::Local).format("%b %d, %Y at %I:%M %p").to_string());
+
+    rsx! {
+        div {
+            class: "fixed inset-0 z-50 flex items-center justify-center bg-black bg-opacity-60",
+            div {
+                class: "flex flex-col max-w-md p-4 border rounded-lg shadow-sm bg-gray-800 border-red-700 text-gray-200",
+                div {
+                    class: "flex items-center gap-2 text-lg font-semibold text-red-300",
+                    Icon { width: 20, height: 20, icon: fi_icons::FiAlertTriangle }
+                    "Your conversations couldn't be loaded"
+                }
+                div {
+                    class: "mt-4 pt-4 border-t border-gray-700 space-y-2 text-sm",
+                    p { "The saved sessions are damaged. They were moved aside, not deleted:" }
+                    p { class: "font-mono text-xs break-all text-gray-400", "{quarantined}" }
+                    p { class: "text-xs text-gray-500", "{recovery.error}" }
+                    match &backup_time {
+                        Some(time) => rsx! { p { "A backup from {time} is available." } },
+                        None => rsx! { p { "No backup is available." } },
+                    }
+                    if let Some(error) = restore_error() {
+                        p { class: "text-red-300", "Restoring failed: {error}" }
+                    }
+                }
+                div {
+                    class: "mt-4 flex justify-end gap-4",
+                    button {
+                        class: "px-4 py-2 rounded-md bg-gray-600 text-white hover:bg-gray-500",
+                        onclick: move |_| session_state.write().dismiss_recovery(),
+                        "Start Fresh"
+                    }
+                    if backup_time.is_some() {
+                        button {
+                            class: "px-4 py-2 rounded-md bg-purple-600 text-white hover:bg-purple-700",
+                            onclick: move |_| {
+                                if let Err(e) = session_state.write().restore_backup() {
+                                    tracing::error!("Failed to restore sessions from backup: {}", e);
+                                    restore_error.set(Some(e.to_string()));
+                                }
+                            },
+                            "Restore Backup"
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,7 +1,10 @@
 #![allow(non_snake_case)]
 use dioxus::prelude::*;
 use dioxus_free_icons::{Icon, icons::fi_icons};
-use crate::session::SessionState;
+use crate::components::settings_panel::GenerationParamsEditor;
+use hobbes_core::session::SessionState;
+use crate::settings::Settings;
+use hobbes_core::generation::GenerationParams;
 
 #[derive(Props, PartialEq, Clone)]
 pub struct SessionManagerProps {}
@@ -10,6 +13,8 @@ pub fn SessionManager(_props: SessionManagerProps) -> Element {
     let mut session_state = consume_context::<Signal<SessionState>>();
     let mut editing_session_id = use_signal(|| None::<String>);
     let mut temp_session_name = use_signal(String::new);
+    let mut tuning_session_id = use_signal(|| None::<String>);
+    let settings = use_context::<Signal<Settings>>();
 
     let sessions = session_state.read();
     let active_id = sessions.active_session_id.clone();
@@ -33,6 +38,7 @@ pub fn SessionManager(_props: SessionManagerProps) -> Element {
                             let id = &session.id;
                             let is_active = *id == active_id;
                             let is_editing = editing_session_id.read().as_ref() == Some(id);
+                            let is_tuning = tuning_session_id.read().as_ref() == Some(id);
 
                             let active_class = if is_active { "bg-purple-600" } else { "" };
                             let id_clone_for_click = id.clone();
@@ -40,60 +46,107 @@ pub fn SessionManager(_props: SessionManagerProps) -> Element {
                             let id_clone_for_keydown = id.clone();
                             let id_clone_for_blur = id.clone();
                             let id_clone_for_edit_button = id.clone();
+                            let id_clone_for_tune_button = id.clone();
+                            let id_clone_for_generation = id.clone();
+                            let id_clone_for_reset = id.clone();
+                            let has_overrides = session.generation != GenerationParams::default();
+                            let tune_class = if has_overrides { "text-purple-300" } else { "text-gray-400" };
                             let session_name = session.name.clone();
 
                             rsx! {
                                 li {
-                                    class: "flex items-center justify-between p-2 rounded-md cursor-pointer hover:bg-gray-700 {active_class}",
+                                    class: "rounded-md {active_class}",
                                     key: "{id}",
-                                    onclick: move |_| {
-                                        if editing_session_id.read().is_none() {
-                                            session_state.write().set_active_session(id_clone_for_click.clone());
-                                        }
-                                    },
-                                    if is_editing {
-                                        input {
-                                            class: "flex-grow bg-gray-700 text-white rounded-md p-1 focus:outline-none focus:ring-2 focus:ring-purple-500",
-                                            value: "{temp_session_name.read()}",
-                                            oninput: move |evt| temp_session_name.set(evt.value()),
-                                            onkeydown: move |evt| {
-                                                if evt.key() == Key::Enter {
-                                                    session_state.write().update_session_name(&id_clone_for_keydown, temp_session_name.read().clone());
-                                                    editing_session_id.set(None);
-                                                } else if evt.key() == Key::Escape {
+                                    div {
+                                        class: "flex items-center justify-between p-2 rounded-md cursor-pointer hover:bg-gray-700",
+                                        onclick: move |_| {
+                                            if editing_session_id.read().is_none() {
+                                                session_state.write().set_active_session(id_clone_for_click.clone());
+                                            }
+                                        },
+                                        if is_editing {
+                                            input {
+                                                class: "flex-grow bg-gray-700 text-white rounded-md p-1 focus:outline-none focus:ring-2 focus:ring-purple-500",
+                                                value: "{temp_session_name.read()}",
+                                                oninput: move |evt| temp_session_name.set(evt.value()),
+                                                onkeydown: move |evt| {
+                                                    if evt.key() == Key::Enter {
+                                                        session_state.write().update_session_name(&id_clone_for_keydown, temp_session_name.read().clone());
+                                                        editing_session_id.set(None);
+                                                    } else if evt.key() == Key::Escape {
+                                                        editing_session_id.set(None);
+                                                    }
+                                                },
+                                                onblur: move |_| {
+                                                    session_state.write().update_session_name(&id_clone_for_blur, temp_session_name.read().clone());
                                                     editing_session_id.set(None);
                                                 }
-                                            },
-                                            onblur: move |_| {
-                                                session_state.write().update_session_name(&id_clone_for_blur, temp_session_name.read().clone());
-                                                editing_session_id.set(None);
                                             }
-                                        }
-                                    } else {
-                                        span { class: "flex-grow select-none", "{session.name}" }
-                                    },
-                                    div {
-                                        class: "flex items-center",
-                                        button {
-                                            class: "px-2 py-1 rounded-md text-xs font-bold text-gray-400 hover:bg-gray-600 hover:text-white",
-                                            onclick: move |event| {
-                                                event.stop_propagation();
-                                                temp_session_name.set(session_name.clone());
-                                                editing_session_id.set(Some(id_clone_for_edit_button.clone()));
-                                            },
-                                            Icon {
-                                                icon: fi_icons::FiEdit2,
-                                                width: 16,
-                                                height: 16,
+                                        } else {
+                                            span { class: "flex-grow select-none", "{session.name}" }
+                                        },
+                                        div {
+                                            class: "flex items-center",
+                                            button {
+                                                class: "px-2 py-1 rounded-md text-xs font-bold text-gray-400 hover:bg-gray-600 hover:text-white",
+                                                onclick: move |event| {
+                                                    event.stop_propagation();
+                                                    temp_session_name.set(session_name.clone());
+                                                    editing_session_id.set(Some(id_clone_for_edit_button.clone()));
+                                                },
+                                                Icon {
+                                                    icon: fi_icons::FiEdit2,
+                                                    width: 16,
+                                                    height: 16,
+                                                }
+                                            }
+                                            button {
+                                                class: "px-2 py-1 rounded-md text-xs font-bold {tune_class} hover:bg-gray-600 hover:text-white",
+                                                title: "Generation settings for this chat",
+                                                onclick: move |event| {
+                                                    event.stop_propagation();
+                                                    let id = id_clone_for_tune_button.clone();
+                                                    let next = if tuning_session_id.read().as_ref() == Some(&id) { None } else { Some(id) };
+                                                    tuning_session_id.set(next);
+                                                },
+                                                Icon {
+                                                    icon: fi_icons::FiSliders,
+                                                    width: 16,
+                                                    height: 16,
+                                                }
+                                            }
+                                            button {
+                                                class: "px-2 py-1 rounded-md text-xs font-bold text-gray-400 hover:bg-red-600 hover:text-white",
+                                                onclick: move |event| {
+                                                    event.stop_propagation();
+                                                    session_state.write().delete_session(&id_clone_for_delete);
+                                                },
+                                                "X"
                                             }
                                         }
-                                        button {
-                                            class: "px-2 py-1 rounded-md text-xs font-bold text-gray-400 hover:bg-red-600 hover:text-white",
-                                            onclick: move |event| {
-                                                event.stop_propagation();
-                                                session_state.write().delete_session(&id_clone_for_delete);
-                                            },
-                                            "X"
+                                    }
+                                    if is_tuning {
+                                        div {
+                                            class: "p-2 bg-gray-900 rounded-b-md text-sm",
+                                            p {
+                                                class: "text-xs text-gray-400 mb-2",
+                                                "Overrides for this chat. Blank fields use the global settings."
+                                            }
+                                            GenerationParamsEditor {
+                                                params: session.generation.clone(),
+                                                inherited: settings.read().generation.clone(),
+                                                onchange: move |params| {
+                                                    session_state.write().update_session_generation(&id_clone_for_generation, params);
+                                                }
+                                            }
+                                            button {
+                                                class: "mt-2 px-2 py-1 bg-gray-600 rounded-md text-xs text-white hover:bg-gray-500 disabled:opacity-50",
+                                                disabled: !has_overrides,
+                                                onclick: move |_| {
+                                                    session_state.write().update_session_generation(&id_clone_for_reset, GenerationParams::default());
+                                                },
+                                                "Reset to Defaults"
+                                            }
                                         }
                                     }
                                 }
@@ -1,6 +1,9 @@
 use dioxus::prelude::*;
 use rfd;
+use crate::components::llm::{ollama, ProviderKind};
 use crate::settings::{Settings, SettingsManager};
+use hobbes_core::generation::GenerationParams;
+use crate::context::usage::BudgetResetSchedule;
 use crate::{context::permissions::ToolCategory, secure_storage};
 
 #[component]
@@ -9,6 +12,8 @@ pub fn SettingsPanel() -> Element {
     let settings_manager = use_context::<Signal<SettingsManager>>();
 
     let mut has_unsaved_changes = use_signal(|| false);
+    let mut save_error = use_signal(|| None::<String>);
+    use_context_provider(|| OllamaModelsChanged(Signal::new(0)));
 
     rsx! {
         div {
@@ -34,38 +39,149 @@ pub fn SettingsPanel() -> Element {
                     }
                 }
             }
+            div {
+                class: "mb-4",
+                label {
+                    class: "block text-sm font-medium text-gray-300",
+                    "Chat Provider"
+                }
+                select {
+                    class: "mt-1 block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-sm shadow-sm focus:outline-none focus:border-indigo-500 focus:ring-1 focus:ring-indigo-500",
+                    value: "{settings.read().chat_provider.id()}",
+                    onchange: move |event| {
+                        if let Some(kind) = ProviderKind::from_id(&event.value()) {
+                            settings.write().chat_provider = kind;
+                            has_unsaved_changes.set(true);
+                        }
+                    },
+                    for kind in ProviderKind::ALL {
+                        option { value: "{kind.id()}", selected: settings.read().chat_provider == kind, "{kind.label()}" }
+                    }
+                }
+            }
             div {
                 class: "mb-4",
                 label {
                     class: "block text-sm font-medium text-gray-300",
                     "Chat Model"
                 }
-                input {
-                    class: "mt-1 block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-sm shadow-sm placeholder-gray-400 focus:outline-none focus:border-indigo-500 focus:ring-1 focus:ring-indigo-500",
-                    r#type: "text",
-                    value: "{settings.read().chat_model}",
-                    oninput: move |event| {
-                        settings.write().chat_model = event.value();
+                ModelPicker {
+                    provider: settings.read().chat_provider,
+                    value: settings.read().chat_model.clone(),
+                    onchange: move |model| {
+                        settings.write().chat_model = model;
                         has_unsaved_changes.set(true);
                     }
                 }
             }
+            div {
+                class: "mb-4",
+                label {
+                    class: "block text-sm font-medium text-gray-300",
+                    "Summary Provider"
+                }
+                select {
+                    class: "mt-1 block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-sm shadow-sm focus:outline-none focus:border-indigo-500 focus:ring-1 focus:ring-indigo-500",
+                    value: "{settings.read().summary_provider.id()}",
+                    onchange: move |event| {
+                        if let Some(kind) = ProviderKind::from_id(&event.value()) {
+                            settings.write().summary_provider = kind;
+                            has_unsaved_changes.set(true);
+                        }
+                    },
+                    for kind in ProviderKind::ALL {
+                        option { value: "{kind.id()}", selected: settings.read().summary_provider == kind, "{kind.label()}" }
+                    }
+                }
+            }
             div {
                 class: "mb-4",
                 label {
                     class: "block text-sm font-medium text-gray-300",
                     "Summary Model"
                 }
-                input {
-                    class: "mt-1 block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-sm shadow-sm placeholder-gray-400 focus:outline-none focus:border-indigo-500 focus:ring-1 focus:ring-indigo-500",
-                    r#type: "text",
-                    value: "{settings.read().summary_model}",
-                    oninput: move |event| {
-                        settings.write().summary_model = event.value();
+                ModelPicker {
+                    provider: settings.read().summary_provider,
+                    value: settings.read().summary_model.clone(),
+                    onchange: move |model| {
+                        settings.write().summary_model = model;
                         has_unsaved_changes.set(true);
                     }
                 }
             }
+            if settings.read().chat_provider == ProviderKind::OpenAi || settings.read().summary_provider == ProviderKind::OpenAi {
+                div {
+                    class: "mb-4 pl-4 border-l-2 border-gray-700",
+                    label {
+                        class: "block text-sm font-medium text-gray-300",
+                        "OpenAI-Compatible Base URL"
+                    }
+                    input {
+                        class: "mt-1 block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-sm shadow-sm placeholder-gray-400 focus:outline-none focus:border-indigo-500 focus:ring-1 focus:ring-indigo-500",
+                        r#type: "text",
+                        placeholder: "http://localhost:8080/v1",
+                        value: "{settings.read().openai.base_url}",
+                        oninput: move |event| {
+                            settings.write().openai.base_url = event.value();
+                            has_unsaved_changes.set(true);
+                        }
+                    }
+                    label {
+                        class: "block mt-2 text-sm font-medium text-gray-300",
+                        "OpenAI-Compatible API Key"
+                    }
+                    input {
+                        class: "mt-1 block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-sm shadow-sm placeholder-gray-400 focus:outline-none focus:border-indigo-500 focus:ring-1 focus:ring-indigo-500",
+                        r#type: "password",
+                        placeholder: "Optional for local servers",
+                        value: "{settings.read().openai.api_key.as_deref().unwrap_or(\"\")}",
+                        oninput: move |event| {
+                            let value = event.value();
+                            settings.write().openai.api_key = if value.is_empty() { None } else { Some(value) };
+                            has_unsaved_changes.set(true);
+                        }
+                    }
+                }
+            }
+            if settings.read().chat_provider == ProviderKind::Ollama || settings.read().summary_provider == ProviderKind::Ollama {
+                div {
+                    class: "mb-4 pl-4 border-l-2 border-gray-700",
+                    label {
+                        class: "block text-sm font-medium text-gray-300",
+                        "Ollama Server URL"
+                    }
+                    input {
+                        class: "mt-1 block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-sm shadow-sm placeholder-gray-400 focus:outline-none focus:border-indigo-500 focus:ring-1 focus:ring-indigo-500",
+                        r#type: "text",
+                        placeholder: "http://localhost:11434",
+                        value: "{settings.read().ollama.base_url}",
+                        oninput: move |event| {
+                            settings.write().ollama.base_url = event.value();
+                            has_unsaved_changes.set(true);
+                        }
+                    }
+                    OllamaPullModel {}
+                }
+            }
+            if settings.read().chat_provider == ProviderKind::Anthropic || settings.read().summary_provider == ProviderKind::Anthropic {
+                div {
+                    class: "mb-4 pl-4 border-l-2 border-gray-700",
+                    label {
+                        class: "block text-sm font-medium text-gray-300",
+                        "Anthropic API Key"
+                    }
+                    input {
+                        class: "mt-1 block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-sm shadow-sm placeholder-gray-400 focus:outline-none focus:border-indigo-500 focus:ring-1 focus:ring-indigo-500",
+                        r#type: "password",
+                        value: "{settings.read().anthropic.api_key.as_deref().unwrap_or(\"\")}",
+                        oninput: move |event| {
+                            let value = event.value();
+                            settings.write().anthropic.api_key = if value.is_empty() { None } else { Some(value) };
+                            has_unsaved_changes.set(true);
+                        }
+                    }
+                }
+            }
             div {
                 class: "mb-4",
                 label {
@@ -84,6 +200,25 @@ pub fn SettingsPanel() -> Element {
                     }
                 }
             }
+            div {
+                class: "mt-4",
+                label {
+                    class: "block text-sm font-medium text-gray-300",
+                    "Max Tool Iterations"
+                }
+            }
+            input {
+                class: "mt-1 block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-sm shadow-sm placeholder-gray-400 focus:outline-none focus:border-indigo-500 focus:ring-1 focus:ring-indigo-500",
+                r#type: "number",
+                min: "1",
+                value: "{settings.read().max_tool_iterations}",
+                oninput: move |event| {
+                    if let Ok(val) = event.value().parse::<u32>() {
+                        settings.write().max_tool_iterations = val.max(1);
+                        has_unsaved_changes.set(true);
+                    }
+                }
+            }
             div {
                 class: "mt-4 mb-4 flex items-center justify-between",
                 label {
@@ -188,6 +323,26 @@ pub fn SettingsPanel() -> Element {
                     }
                 }
             }
+            // Generation Settings
+            div {
+                class: "mt-6 pt-4 border-t border-gray-700",
+                h3 {
+                    class: "text-md font-semibold mb-1",
+                    "Generation"
+                }
+                p {
+                    class: "text-xs text-gray-400 mb-3",
+                    "Defaults for every chat. Leave a field blank to use the provider's default."
+                }
+                GenerationParamsEditor {
+                    params: settings.read().generation.clone(),
+                    inherited: GenerationParams::default(),
+                    onchange: move |params| {
+                        settings.write().generation = params;
+                        has_unsaved_changes.set(true);
+                    }
+                }
+            }
             // Auto-Approval Settings
             div {
                 class: "mt-6 pt-4 border-t border-gray-700",
@@ -266,7 +421,7 @@ pub fn SettingsPanel() -> Element {
                         // Max Cost
                         div {
                             class: "mt-3",
-                            label { "Max Session Cost ($)" }
+                            label { "Max Cost per Budget Period ($)" }
                             input {
                                 class: "mt-1 block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-sm",
                                 r#type: "number",
@@ -280,9 +435,30 @@ pub fn SettingsPanel() -> Element {
                                 }
                             }
                         }
+
+                        // Budget Reset
+                        div {
+                            class: "mt-3",
+                            label { "Reset Budget" }
+                            select {
+                                class: "mt-1 block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-sm",
+                                value: "{settings.read().permission_settings.budget_reset.id()}",
+                                onchange: move |event| {
+                                    if let Some(schedule) = BudgetResetSchedule::from_id(&event.value()) {
+                                        settings.write().permission_settings.budget_reset = schedule;
+                                        has_unsaved_changes.set(true);
+                                    }
+                                },
+                                for schedule in BudgetResetSchedule::ALL {
+                                    option { value: "{schedule.id()}", selected: settings.read().permission_settings.budget_reset == schedule, "{schedule.label()}" }
+                                }
+                            }
+                        }
                     }
                 }
             }
+            crate::components::mcp_settings::McpServerSettings {}
+            crate::components::mcp_status::McpStatusPanel {}
             button {
                 class: if has_unsaved_changes() {
                     "mt-4 px-4 py-2 bg-purple-600 rounded-md text-white font-semibold hover:bg-purple-700 focus:outline-none focus:ring-2 focus:ring-purple-500 focus:ring-opacity-50 transition-colors"
@@ -298,14 +474,299 @@ pub fn SettingsPanel() -> Element {
                                 tracing::error!("Failed to save API key: {}", e);
                             }
                         }
-                        if let Err(e) = settings_manager.read().save(&settings_clone) {
-                            tracing::error!("Failed to save settings: {}", e);
+                        if let Some(api_key) = settings_clone.openai.api_key.take() {
+                            if let Err(e) = secure_storage::save_secret("openai_api_key", &api_key) {
+                                tracing::error!("Failed to save OpenAI API key: {}", e);
+                            }
+                        }
+                        if let Some(api_key) = settings_clone.anthropic.api_key.take() {
+                            if let Err(e) = secure_storage::save_secret("anthropic_api_key", &api_key) {
+                                tracing::error!("Failed to save Anthropic API key: {}", e);
+                            }
+                        }
+                        match settings_manager.read().save(&settings_clone) {
+                            Ok(()) => {
+                                save_error.set(None);
+                                has_unsaved_changes.set(false);
+                            }
+                            Err(e) => {
+                                tracing::error!("Failed to save settings: {}", e);
+                                save_error.set(Some(format!("Failed to save settings: {}", e)));
+                            }
                         }
-                        has_unsaved_changes.set(false);
                     }
                 },
                 "Save Settings"
             }
+            if let Some(e) = save_error() {
+                p { class: "mt-2 text-xs text-red-400 break-words", "{e}" }
+            }
+        }
+    }
+}
+/// Edits generation parameters. Blank fields are `None`; their placeholders show the
+/// value from `inherited` that applies instead.
+#[component]
+pub fn GenerationParamsEditor(params: GenerationParams, inherited: GenerationParams, onchange: EventHandler<GenerationParams>) -> Element {
+    fn text<T: ToString>(value: Option<T>) -> String {
+        value.map(|v| v.to_string()).unwrap_or_default()
+    }
+    fn placeholder<T: ToString>(value: Option<T>) -> String {
+        value.map(|v| v.to_string()).unwrap_or_else(|| "Default".to_string())
+    }
+    let input_class = "mt-1 block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-sm shadow-sm placeholder-gray-400 focus:outline-none focus:border-indigo-500 focus:ring-1 focus:ring-indigo-500";
+    let stop_placeholder = if inherited.stop_sequences.is_empty() {
+        "One per line".to_string()
+    } else {
+        inherited.stop_sequences.join("\n")
+    };
+
+    let p = params.clone();
+    let set_temperature = move |event: FormEvent| onchange.call(GenerationParams { temperature: event.value().trim().parse().ok(), ..p.clone() });
+    let p = params.clone();
+    let set_top_p = move |event: FormEvent| onchange.call(GenerationParams { top_p: event.value().trim().parse().ok(), ..p.clone() });
+    let p = params.clone();
+    let set_top_k = move |event: FormEvent| onchange.call(GenerationParams { top_k: event.value().trim().parse().ok(), ..p.clone() });
+    let p = params.clone();
+    let set_max_output_tokens =
+        move |event: FormEvent| onchange.call(GenerationParams { max_output_tokens: event.value().trim().parse().ok(), ..p.clone() });
+    let p = params.clone();
+    let set_thinking_budget =
+        move |event: FormEvent| onchange.call(GenerationParams { thinking_budget: event.value().trim().parse().ok(), ..p.clone() });
+    let p = params.clone();
+    let set_stop_sequences = move |event: FormEvent| {
+        let stop_sequences = event.value().lines().filter(|line| !line.is_empty()).map(str::to_string).collect();
+        onchange.call(GenerationParams { stop_sequences, ..p.clone() })
+    };
+
+    rsx! {
+        div {
+            class: "grid grid-cols-2 gap-3",
+            div {
+                label { class: "block text-sm font-medium text-gray-300", "Temperature" }
+                input {
+                    class: input_class,
+                    r#type: "number",
+                    min: "0",
+                    max: "2",
+                    step: "0.1",
+                    placeholder: placeholder(inherited.temperature),
+                    value: text(params.temperature),
+                    oninput: set_temperature
+                }
+            }
+            div {
+                label { class: "block text-sm font-medium text-gray-300", "Top P" }
+                input {
+                    class: input_class,
+                    r#type: "number",
+                    min: "0",
+                    max: "1",
+                    step: "0.05",
+                    placeholder: placeholder(inherited.top_p),
+                    value: text(params.top_p),
+                    oninput: set_top_p
+                }
+            }
+            div {
+                label { class: "block text-sm font-medium text-gray-300", "Top K" }
+                input {
+                    class: input_class,
+                    r#type: "number",
+                    min: "1",
+                    placeholder: placeholder(inherited.top_k),
+                    value: text(params.top_k),
+                    oninput: set_top_k
+                }
+            }
+            div {
+                label { class: "block text-sm font-medium text-gray-300", "Max Output Tokens" }
+                input {
+                    class: input_class,
+                    r#type: "number",
+                    min: "1",
+                    placeholder: placeholder(inherited.max_output_tokens),
+                    value: text(params.max_output_tokens),
+                    oninput: set_max_output_tokens
+                }
+            }
+            div {
+                label {
+                    class: "block text-sm font-medium text-gray-300",
+                    title: "Tokens the model may spend reasoning before answering. 0 turns thinking off.",
+                    "Thinking Budget"
+                }
+                input {
+                    class: input_class,
+                    r#type: "number",
+                    min: "0",
+                    placeholder: placeholder(inherited.thinking_budget),
+                    value: text(params.thinking_budget),
+                    oninput: set_thinking_budget
+                }
+            }
+            div {
+                label { class: "block text-sm font-medium text-gray-300", "Stop Sequences" }
+                textarea {
+                    class: input_class,
+                    rows: "2",
+                    placeholder: stop_placeholder,
+                    value: params.stop_sequences.join("\n"),
+                    oninput: set_stop_sequences
+                }
+            }
+        }
+    }
+}
+
+/// Chooses the model for `provider`. Models for Ollama are listed from the local server;
+/// the other providers take a free-text model name.
+#[component]
+fn ModelPicker(provider: ProviderKind, value: String, onchange: EventHandler<String>) -> Element {
+    if provider == ProviderKind::Ollama {
+        return rsx! { OllamaModelSelect { value, onchange } };
+    }
+    rsx! {
+        input {
+            class: "mt-1 block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-sm shadow-sm placeholder-gray-400 focus:outline-none focus:border-indigo-500 focus:ring-1 focus:ring-indigo-500",
+            r#type: "text",
+            value: "{value}",
+            oninput: move |event| onchange.call(event.value())
+        }
+    }
+}
+
+/// Bumped when a model has been pulled, so the model pickers list it.
+#[derive(Clone, Copy)]
+struct OllamaModelsChanged(Signal<u32>);
+
+/// How long the Ollama URL has to stay unchanged before its models are listed, so typing a URL
+/// doesn't send a request per keystroke.
+const OLLAMA_URL_SETTLE_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+
+#[component]
+fn OllamaModelSelect(value: String, onchange: EventHandler<String>) -> Element {
+    let settings = use_context::<Signal<Settings>>();
+    let models_changed = use_context::<OllamaModelsChanged>();
+    let base_url = use_memo(move || settings.read().ollama.base_url.clone());
+    let mut models = use_resource(move || async move {
+        let base_url = base_url();
+        let _ = (models_changed.0)();
+        // A newer URL restarts the resource, which drops this wait along with the request.
+        tokio::time::sleep(OLLAMA_URL_SETTLE_DELAY).await;
+        ollama::list_models(&base_url).await
+    });
+
+    rsx! {
+        div {
+            class: "flex items-center mt-1 space-x-2",
+            match &*models.read() {
+                Some(Ok(names)) => rsx! {
+                    select {
+                        class: "block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-sm shadow-sm focus:outline-none focus:border-indigo-500 focus:ring-1 focus:ring-indigo-500",
+                        value: "{value}",
+                        onchange: move |event| onchange.call(event.value()),
+                        // Keep the saved model selectable even if it is no longer installed.
+                        if !names.contains(&value) {
+                            option { value: "{value}", selected: true, disabled: value.is_empty(), "{value}" }
+                        }
+                        for name in names.iter() {
+                            option { value: "{name}", selected: *name == value, "{name}" }
+                        }
+                    }
+                },
+                Some(Err(e)) => rsx! {
+                    div {
+                        class: "w-full",
+                        input {
+                            class: "block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-sm shadow-sm placeholder-gray-400 focus:outline-none focus:border-indigo-500 focus:ring-1 focus:ring-indigo-500",
+                            r#type: "text",
+                            value: "{value}",
+                            oninput: move |event| onchange.call(event.value())
+                        }
+                        p { class: "mt-1 text-xs text-red-400", "Could not reach Ollama: {e}" }
+                    }
+                },
+                None => rsx! {
+                    p { class: "w-full text-sm text-gray-400", "Loading models..." }
+                },
+            }
+            button {
+                class: "px-2 py-2 bg-gray-600 rounded-md text-xs text-white hover:bg-gray-500",
+                title: "Refresh model list",
+                onclick: move |_| models.restart(),
+                "Refresh"
+            }
+        }
+    }
+}
+
+/// Downloads a model to the Ollama server, showing the progress Ollama reports.
+#[component]
+fn OllamaPullModel() -> Element {
+    let settings = use_context::<Signal<Settings>>();
+    let mut models_changed = use_context::<OllamaModelsChanged>();
+    let mut model = use_signal(String::new);
+    let mut progress = use_signal(|| None::<ollama::PullProgress>);
+    let mut pulling = use_signal(|| false);
+    let mut result = use_signal(|| None::<Result<String, String>>);
+
+    rsx! {
+        label {
+            class: "block mt-3 text-sm font-medium text-gray-300",
+            "Download a Model"
+        }
+        div {
+            class: "flex items-center mt-1 space-x-2",
+            input {
+                class: "block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-sm shadow-sm placeholder-gray-400 focus:outline-none focus:border-indigo-500 focus:ring-1 focus:ring-indigo-500",
+                r#type: "text",
+                placeholder: "llama3.2:3b",
+                value: "{model}",
+                disabled: pulling(),
+                oninput: move |event| model.set(event.value())
+            }
+            button {
+                class: "px-2 py-2 bg-gray-600 rounded-md text-xs text-white hover:bg-gray-500 disabled:opacity-50",
+                disabled: pulling() || model.read().trim().is_empty(),
+                onclick: move |_| {
+                    let name = model.read().trim().to_string();
+                    let base_url = settings.read().ollama.base_url.clone();
+                    pulling.set(true);
+                    result.set(None);
+                    progress.set(None);
+                    spawn(async move {
+                        let outcome = ollama::pull_model(&base_url, &name, |update| progress.set(Some(update))).await;
+                        if outcome.is_ok() {
+                            models_changed.0 += 1;
+                        }
+                        result.set(Some(outcome.map(|_| name)));
+                        progress.set(None);
+                        pulling.set(false);
+                    });
+                },
+                "Pull"
+            }
+        }
+        if let Some(update) = progress() {
+            div {
+                class: "mt-2",
+                p { class: "text-xs text-gray-400", "{update.status}" }
+                if let Some(fraction) = update.fraction() {
+                    div {
+                        class: "mt-1 h-1.5 w-full rounded-full bg-gray-700",
+                        div {
+                            class: "h-1.5 rounded-full bg-indigo-500",
+                            style: "width: {fraction * 100.0:.0}%;",
+                        }
+                    }
+                }
+            }
+        }
+        match result() {
+            Some(Ok(name)) => rsx! { p { class: "mt-1 text-xs text-green-400", "Downloaded {name}." } },
+            Some(Err(e)) => rsx! { p { class: "mt-1 text-xs text-red-400", "Download failed: {e}" } },
+            None => rsx! {},
         }
     }
-}
\ No newline at end of file
+}
@@ -1,66 +1,14 @@
-use serde::{Deserialize, Serialize};
-
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
-pub enum MessageContent {
-    Text(String),
-    ToolCall(ToolCall),
-    PermissionRequest(ToolCall),
-}
-
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Default)]
-pub struct ToolCall {
-    pub execution_id: String,
-    pub server_name: String,
-    pub tool_name: String,
-    pub arguments: String,
-    pub status: ToolCallStatus,
-    pub response: String,
-}
+use hobbes_core::error::LlmError;
+use hobbes_core::message::{TokenUsage, ToolCall};
+use hobbes_core::models::FinishReason;
 
 pub enum StreamMessage {
     Text(String),
     ToolCall(ToolCall),
+    /// Token counts for the whole response, sent once the stream has finished.
+    Usage(TokenUsage),
+    /// Why the model stopped, sent once the stream has finished.
+    Finish(FinishReason),
+    /// The request failed, or the response was cut short. Always the last message sent.
+    Error(LlmError),
 }
-
-impl ToolCall {
-    pub fn new(server_name: String, tool_name: String, args: serde_json::Value) -> Self {
-        Self {
-            execution_id: uuid::Uuid::new_v4().to_string(),
-            server_name,
-            tool_name,
-            arguments: args.to_string(),
-            status: ToolCallStatus::Running,
-            response: String::new(),
-        }
-    }
-}
-
-#[derive(Clone, Serialize, Deserialize, PartialEq, Copy, Debug, Default)]
-pub enum ToolCallStatus {
-    #[default]
-    Running,
-    Completed,
-    Error,
-}
-
-impl std::fmt::Display for ToolCallStatus {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ToolCallStatus::Running => write!(f, "Running"),
-            ToolCallStatus::Completed => write!(f, "Completed"),
-            ToolCallStatus::Error => write!(f, "Error"),
-        }
-    }
-}
-
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
-pub struct ToolResult {
-    pub status: ToolCallStatus,
-    pub response: String,
-}
-
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
-pub struct ToolCallRecord {
-    pub call: ToolCall,
-    pub result: ToolResult,
-}
\ No newline at end of file
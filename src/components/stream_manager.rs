@@ -2,13 +2,28 @@ use crate::services::tool_call_summarizer::ToolCallSummarizer;
 use dioxus::prelude::*;
 use std::collections::HashMap;
 use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tokio::sync::watch;
 use uuid::Uuid;
-use crate::session::SessionState;
+use hobbes_core::session::SessionState;
 use crate::components::llm;
-use crate::components::shared::{StreamMessage, ToolCallStatus};
+use crate::components::shared::StreamMessage;
+use crate::context::prompt_builder::{AttachmentData, PromptBuilder};
+use hobbes_core::message::{TokenUsage, ToolCallStatus};
+use hobbes_core::models::LlmMetadata;
 use crate::services::document_store::DocumentStore;
 use std::sync::Arc;
 use crate::settings::Settings;
+use crate::context::permissions::RequestCounter;
+
+/// The result of a single model call within the agent loop.
+struct StepOutcome {
+    /// The tools the model called, in the order it requested them.
+    records: Vec<hobbes_core::message::ToolCallRecord>,
+    /// Whether any of those calls is waiting on the user to approve it.
+    awaiting_permission: bool,
+    /// Whether the user stopped the response during this step.
+    cancelled: bool,
+}
 
 #[derive(Clone, Copy)]
 pub struct StreamManagerContext {
@@ -18,6 +33,8 @@ pub struct StreamManagerContext {
     document_store: Signal<Option<Arc<DocumentStore>>>,
     tool_call_summarizer: Signal<ToolCallSummarizer>,
     settings: Signal<Settings>,
+    /// Signals cancellation to each turn in flight, keyed by the message its stream started in.
+    cancel_txs: Signal<HashMap<Uuid, watch::Sender<bool>>>,
 }
 
 impl StreamManagerContext {
@@ -31,7 +48,7 @@ impl StreamManagerContext {
         message_id: Uuid,
         prompt_data: crate::context::prompt_builder::LlmPrompt,
         on_complete: impl FnOnce() + Send + 'static,
-        mcp_context: Option<crate::mcp::manager::McpContext>,
+        mcp_context: Option<hobbes_core::mcp::McpContext>,
     ) {
         tracing::info!(message_id = %message_id, "'start_stream' entered.");
         // Create a channel for the MessageBubble to receive chunks.
@@ -40,196 +57,94 @@ impl StreamManagerContext {
         // Store the receiver for the MessageBubble to pick up.
         self.stream_receivers.write().insert(message_id, stream_rx);
 
-        // Spawn a master task to manage the LLM call and state updates.
+        let (cancel_tx, cancel_rx) = watch::channel(false);
+        self.cancel_txs.write().insert(message_id, cancel_tx);
+        // The whole turn, including its usage, belongs to the session it started in, even if the
+        // user switches to another chat while it runs.
+        let session_id = self.session_state.read().active_session_id.clone();
+
+        // Spawn a master task to run the agent loop and manage state updates.
         spawn(async move {
             tracing::info!(message_id = %message_id, "Stream master task SPAWNED.");
-            let (llm_tx, mut llm_rx) = mpsc::unbounded_channel::<StreamMessage>();
-
             let settings = self.settings.read().clone();
-            let api_key = settings.api_key.clone().unwrap_or_else(|| {
-                std::env::var("GEMINI_API_KEY").expect("GEMINI_API_KEY must be set in settings or environment")
-            });
-            spawn(async move {
-                llm::generate_content_stream(api_key, model, prompt_data, llm_tx, mcp_context).await;
-            });
-
-            let mut is_first_message = true;
-            // MPSC channel to collect results from all spawned tool-call tasks.
-            let (tool_results_tx, mut tool_results_rx) = mpsc::unbounded_channel::<crate::components::shared::ToolCallRecord>();
-            let mut tool_call_count = 0;
-
-            while let Some(message) = llm_rx.recv().await {
-                match message {
-                    StreamMessage::Text(chunk) => {
-                        let mut state = self.session_state.write();
-                        if let Some(msg) = state.get_message_mut(&message_id) {
-                             if let crate::components::shared::MessageContent::Text(t) = &mut msg.content {
-                                t.push_str(&chunk);
-                            }
-                        }
-                        if stream_tx.send(StreamMessage::Text(chunk)).is_err() {
-                            break;
-                        }
-                        is_first_message = false;
-                    }
-                    StreamMessage::ToolCall(tool_call) => {
-                        tool_call_count += 1;
-                        let tool_call_message_id = {
-                            let mut state = self.session_state.write();
-                            if is_first_message {
-                                if let Some(msg) = state.get_message_mut(&message_id) {
-                                    msg.content = crate::components::shared::MessageContent::ToolCall(tool_call.clone());
-                                }
-                                message_id
-                            } else {
-                                let new_id = Uuid::new_v4();
-                                if let Some(session) = state.get_active_session_mut() {
-                                    session.messages.push(crate::components::chat::Message {
-                                        id: new_id,
-                                        author: "Hobbes".to_string(),
-                                        content: crate::components::shared::MessageContent::ToolCall(tool_call.clone()),
-                                    });
-                                }
-                                new_id
-                            }
-                        };
-
-                        // Each tool call runs in its own spawned task.
-                        // It owns a sender to the results channel.
-                        let mcp_manager = self.mcp_manager;
-                        let mut session_state = self.session_state;
-                        let document_store = self.document_store;
-                        let tool_results_tx = tool_results_tx.clone(); // Clone sender for the task
-                        spawn(async move {
-                            let args_json: serde_json::Value = serde_json::from_str(&tool_call.arguments).unwrap_or(serde_json::Value::Null);
-                            let result = mcp_manager.read().use_mcp_tool(&tool_call.server_name, &tool_call.tool_name, args_json, false).await;
-
-                            let mut state = session_state.write();
-                            let (status, response_str) = match result {
-                                Ok(response) => (ToolCallStatus::Completed, serde_json::to_string_pretty(&response).unwrap_or_default()),
-                                Err(e) => {
-                                    if let Ok(tool_call_req) = serde_json::from_str::<crate::components::shared::ToolCall>(&e) {
-                                        if let Some(msg) = state.get_message_mut(&tool_call_message_id) {
-                                            msg.content = crate::components::shared::MessageContent::PermissionRequest(tool_call_req);
-                                        }
-                                        (ToolCallStatus::Error, e)
-                                    } else {
-                                        (ToolCallStatus::Error, e)
-                                    }
-                                }
-                            };
-
-                            if let Some(msg) = state.get_message_mut(&tool_call_message_id) {
-                                if let crate::components::shared::MessageContent::ToolCall(tc) = &mut msg.content {
-                                    tc.status = status;
-                                    tc.response = response_str.clone();
-                                }
-                            }
-
-                            let record = crate::components::shared::ToolCallRecord {
-                                call: tool_call.clone(),
-                                result: crate::components::shared::ToolResult {
-                                    status,
-                                    response: response_str,
-                                },
-                            };
-                            if let Some(store) = document_store.read().as_ref().cloned() {
-                                let record_for_store = record.clone();
-                                spawn(async move {
-                                    if let Err(e) = store.upsert_tool_result(&record_for_store).await {
-                                        tracing::error!("Failed to upsert tool result: {}", e);
-                                    }
-                                });
-                            }
-                            let _ = tool_results_tx.send(record);
-                        });
-                        is_first_message = false;
-                    }
+            let permission_manager = self.mcp_manager.read().permission_manager();
+            // The request budget covers every model and tool request made while answering this turn.
+            let requests = RequestCounter::default();
+
+            let mut step_message_id = message_id;
+            let mut step_tx = stream_tx;
+            let mut step_prompt = prompt_data;
+            let mut step_mcp_context = mcp_context;
+            let mut iteration: u32 = 0;
+
+            // The agent loop: call the model, run the tools it requests, and re-prompt with
+            // the results until it answers in text or a limit is reached.
+            loop {
+                requests.record();
+                let outcome = self.run_step(model.clone(), &session_id, step_message_id, step_prompt, step_mcp_context, step_tx, &requests, cancel_rx.clone()).await;
+                self.session_state.write().tool_call_history.extend(outcome.records.iter().cloned());
+                if outcome.cancelled {
+                    tracing::info!(message_id = %step_message_id, "Agent loop cancelled by user.");
+                    break;
+                }
+                if outcome.records.is_empty() {
+                    break;
                 }
-            }
-
-            // The master task will collect all results from the channel.
-            // We drop the original sender here. The loop will only complete
-            // once all the spawned tool-call tasks have finished and dropped their sender clones.
-            // This is a robust way to await an unknown number of concurrent tasks.
-            drop(tool_results_tx);
-            let mut collected_records = Vec::new();
-            while let Some(record) = tool_results_rx.recv().await {
-                collected_records.push(record);
-            }
-
-            // Centralize all SessionState mutations to occur sequentially after results are collected.
-            if tool_call_count > 0 {
-                assert_eq!(collected_records.len(), tool_call_count, "Mismatch between tool calls dispatched and results received.");
-                self.session_state.write().tool_call_history.extend(collected_records.clone());
-            }
 
-            // If tools were called, we now feed the results back to the LLM to get a final,
-            // natural-language response. This is the core of the feedback loop.
-            if !self.session_state.read().tool_call_history.is_empty() {
-                let new_hobbes_message_id = Uuid::new_v4();
-                let settings = self.settings.read().clone();
-                
-                // Create the new, empty message bubble that will display the final response.
-                {
-                    let mut state = self.session_state.write();
-                    if let Some(session) = state.get_active_session_mut() {
-                        session.messages.push(crate::components::chat::Message {
-                            id: new_hobbes_message_id,
-                            author: "Hobbes".to_string(),
-                            content: crate::components::shared::MessageContent::Text("".to_string()),
-                        });
-                    }
+                if outcome.awaiting_permission {
+                    tracing::info!(message_id = %step_message_id, "Agent loop paused for a permission request.");
+                    break;
+                }
+                iteration += 1;
+                if iteration >= settings.max_tool_iterations {
+                    tracing::warn!("Agent loop reached max_tool_iterations ({}).", settings.max_tool_iterations);
+                    self.push_hobbes_message(&session_id, format!(
+                        "[Hobbes stopped after {} tool steps. Increase \"Max Tool Iterations\" in Settings to allow longer tasks.]",
+                        iteration
+                    ));
+                    break;
+                }
+                if permission_manager.read().request_limit_reached(&requests) {
+                    tracing::warn!("Agent loop reached max_requests.");
+                    self.push_hobbes_message(&session_id, "[Hobbes stopped because the request limit for this turn was reached.]".to_string());
+                    break;
+                }
+                if permission_manager.read().cost_limit_reached() {
+                    tracing::warn!("Agent loop reached max_cost.");
+                    self.push_hobbes_message(&session_id, "[Hobbes stopped because the cost budget has been reached.]".to_string());
+                    break;
                 }
 
-                // Build the new prompt that includes the tool call history.
-                let (prompt_data, mcp_context_for_next_call) = {
+                // Each step gets its own message bubble, so the UI shows the loop as it progresses.
+                // It stops if the session was deleted while the tools ran.
+                let Some(next_message_id) = self.push_hobbes_message(&session_id, String::new()) else {
+                    tracing::info!(session_id = %session_id, "Agent loop stopped: its session was deleted.");
+                    break;
+                };
+                step_message_id = next_message_id;
+                let Some(read_attachments) = self.session_state.read().sessions.get(&session_id).map(|session| AttachmentData::read(session, &settings)) else { break };
+                let attachments = read_attachments.await;
+                let (prompt, mcp_context_for_next_call) = {
                     let current_state = self.session_state.read();
-                    if let Some(session) = current_state.get_active_session() {
-                        let builder = crate::context::prompt_builder::PromptBuilder::new(session, &settings, &current_state);
-                        let prompt = builder.build_prompt("".to_string(), None); // Empty message, context is now in history
-                        (Some(prompt), session.active_context.mcp_tools.clone())
-                    } else {
-                        (None, None)
-                    }
+                    let Some(session) = current_state.sessions.get(&session_id) else { break };
+                    let builder = PromptBuilder::new(session, &settings, &current_state, &attachments);
+                    // Empty message, context is now in history
+                    (builder.build_prompt("".to_string(), None), session.active_context.mcp_tools.clone())
                 };
-
-                // Execute the second LLM call.
-                if let Some(prompt) = prompt_data {
-                    let (final_answer_tx, final_answer_rx) = mpsc::unbounded_channel::<StreamMessage>();
-                    self.stream_receivers.write().insert(new_hobbes_message_id, final_answer_rx);
-
-                    let (llm_tx, mut llm_rx) = mpsc::unbounded_channel::<StreamMessage>();
-                    let api_key = settings.api_key.clone().unwrap_or_else(|| {
-                        std::env::var("GEMINI_API_KEY").expect("GEMINI_API_KEY must be set in settings or environment")
-                    });
-                    let model = settings.chat_model.clone();
-
-                    spawn(async move {
-                        llm::generate_content_stream(api_key, model, prompt, llm_tx, mcp_context_for_next_call).await;
-                    });
-
-                    // Stream the final response to the new message bubble.
-                    while let Some(message) = llm_rx.recv().await {
-                        if let StreamMessage::Text(chunk) = message {
-                            if let Some(msg) = self.session_state.write().get_message_mut(&new_hobbes_message_id) {
-                                if let crate::components::shared::MessageContent::Text(t) = &mut msg.content {
-                                    t.push_str(&chunk);
-                                }
-                            }
-                            if final_answer_tx.send(StreamMessage::Text(chunk)).is_err() {
-                                break;
-                            }
-                        }
-                    }
-                }
+                let (next_tx, next_rx) = mpsc::unbounded_channel::<StreamMessage>();
+                self.stream_receivers.write().insert(step_message_id, next_rx);
+                step_tx = next_tx;
+                step_prompt = prompt;
+                step_mcp_context = mcp_context_for_next_call;
             }
 
             tracing::info!(message_id = %message_id, "LLM stream COMPLETE.");
+            self.cancel_txs.write().remove(&message_id);
             {
                 let mut state = self.session_state.write();
-                state.touch_active_session();
-                if let Err(e) = state.save() {
+                state.touch_session(&session_id);
+                // `save` only writes the active session, which may no longer be this turn's.
+                if let Err(e) = state.save().and_then(|()| state.save_session(&session_id)) {
                     tracing::error!("Failed to save session state after stream: {}", e);
                 } else {
                     tracing::info!(message_id = %message_id, "Session state SAVED successfully.");
@@ -244,6 +159,289 @@ impl StreamManagerContext {
         });
     }
 
+    /// Adds a response's token usage and cost to the session `session_id` and to the daily
+    /// totals that `max_cost` is enforced against.
+    pub fn record_usage(mut self, session_id: &str, settings: &Settings, model: &str, usage: TokenUsage) {
+        let cost = crate::context::usage::cost_for(&settings.model_prices, model, &usage);
+        tracing::info!(
+            "Usage for {}: {} prompt + {} completion tokens (${:.4})",
+            model, usage.prompt_tokens, usage.completion_tokens, cost
+        );
+        self.session_state.write().add_session_usage(session_id, &usage, cost);
+        let permission_manager = self.mcp_manager.read().permission_manager();
+        permission_manager.read().record_usage(&usage, cost);
+    }
+
+    /// Records how the model produced a message, creating its metadata on first use.
+    fn update_metadata(mut self, session_id: &str, message_id: Uuid, model: &str, update: impl FnOnce(&mut LlmMetadata)) {
+        if let Some(msg) = self.session_state.write().get_message_mut(session_id, &message_id) {
+            let metadata = msg.metadata.get_or_insert_with(|| LlmMetadata {
+                model_name: model.to_string(),
+                ..Default::default()
+            });
+            update(metadata);
+        }
+    }
+
+    /// Marks the step's message as failed. If the step already turned into a tool call, the
+    /// error gets a message of its own.
+    fn record_error(mut self, session_id: &str, message_id: Uuid, error: llm::LlmError) {
+        let mut state = self.session_state.write();
+        if let Some(msg) = state.get_message_mut(session_id, &message_id) {
+            if let hobbes_core::message::MessageContent::Text(_) = msg.content {
+                msg.error = Some(error);
+                return;
+            }
+        }
+        if let Some(session) = state.sessions.get_mut(session_id) {
+            session.push_message(hobbes_core::message::Message {
+                id: Uuid::new_v4(),
+                author: "Hobbes".to_string(),
+                content: hobbes_core::message::MessageContent::Text(String::new()),
+                interrupted: false,
+                error: Some(error),
+                parent_message_id: None,
+                created_at: Some(chrono::Utc::now()),
+                metadata: None,
+            });
+        }
+    }
+
+    /// Appends a Hobbes message to the session `session_id` and returns its id, or `None` if
+    /// the session no longer exists.
+    fn push_hobbes_message(mut self, session_id: &str, text: String) -> Option<Uuid> {
+        let id = Uuid::new_v4();
+        let mut state = self.session_state.write();
+        let session = state.sessions.get_mut(session_id)?;
+        session.push_message(hobbes_core::message::Message {
+            id,
+            author: "Hobbes".to_string(),
+            content: hobbes_core::message::MessageContent::Text(text),
+            interrupted: false,
+            error: None,
+            parent_message_id: None,
+            created_at: Some(chrono::Utc::now()),
+            metadata: None,
+        });
+        Some(id)
+    }
+
+    /// Streams one model response into `message_id` and runs every tool it requests concurrently.
+    #[allow(clippy::too_many_arguments)]
+    async fn run_step(
+        mut self,
+        model: String,
+        session_id: &str,
+        message_id: Uuid,
+        prompt_data: crate::context::prompt_builder::LlmPrompt,
+        mcp_context: Option<hobbes_core::mcp::McpContext>,
+        stream_tx: mpsc::UnboundedSender<StreamMessage>,
+        requests: &RequestCounter,
+        mut cancel_rx: watch::Receiver<bool>,
+    ) -> StepOutcome {
+        let (llm_tx, mut llm_rx) = mpsc::unbounded_channel::<StreamMessage>();
+
+        let settings = self.settings.read().clone();
+        let provider = llm::provider_for(&settings, settings.chat_provider);
+        let step_model = model.clone();
+        self.update_metadata(session_id, message_id, &step_model, |_| {});
+        let provider_task = spawn(async move {
+            provider.stream_chat(model, prompt_data, llm_tx, mcp_context).await;
+        });
+
+        let mut is_first_message = true;
+        let mut cancelled = false;
+        // MPSC channel to collect results from all spawned tool-call tasks, tagged with
+        // the order in which the model requested them.
+        let (tool_results_tx, mut tool_results_rx) = mpsc::unbounded_channel::<(usize, hobbes_core::message::ToolCallRecord, bool)>();
+        // Every dispatched call and the message showing it, by `call_index`.
+        let mut dispatched_calls = Vec::new();
+
+        loop {
+            let message = tokio::select! {
+                message = llm_rx.recv() => match message {
+                    Some(message) => message,
+                    None => break,
+                },
+                Ok(_) = cancel_rx.wait_for(|cancelled| *cancelled) => {
+                    // Dropping the provider task drops its HTTP response, closing the stream.
+                    provider_task.cancel();
+                    cancelled = true;
+                    if let Some(msg) = self.session_state.write().get_message_mut(session_id, &message_id) {
+                        msg.interrupted = true;
+                    }
+                    break;
+                }
+            };
+            match message {
+                StreamMessage::Usage(usage) => {
+                    self.update_metadata(session_id, message_id, &step_model, |metadata| {
+                        metadata.prompt_token_count = usage.prompt_tokens;
+                        metadata.token_count = usage.completion_tokens;
+                    });
+                    self.record_usage(session_id, &settings, &step_model, usage);
+                }
+                StreamMessage::Finish(reason) => {
+                    self.update_metadata(session_id, message_id, &step_model, |metadata| metadata.stop_reason = Some(reason));
+                }
+                StreamMessage::Error(error) => {
+                    self.record_error(session_id, message_id, error);
+                }
+                StreamMessage::Text(chunk) => {
+                    let mut state = self.session_state.write();
+                    if let Some(msg) = state.get_message_mut(session_id, &message_id) {
+                         if let hobbes_core::message::MessageContent::Text(t) = &mut msg.content {
+                            t.push_str(&chunk);
+                        }
+                    }
+                    if stream_tx.send(StreamMessage::Text(chunk)).is_err() {
+                        break;
+                    }
+                    is_first_message = false;
+                }
+                StreamMessage::ToolCall(tool_call) => {
+                    let call_index = dispatched_calls.len();
+                    let tool_call_message_id = {
+                        let mut state = self.session_state.write();
+                        if is_first_message {
+                            if let Some(msg) = state.get_message_mut(session_id, &message_id) {
+                                msg.content = hobbes_core::message::MessageContent::ToolCall(tool_call.clone());
+                            }
+                            message_id
+                        } else {
+                            let new_id = Uuid::new_v4();
+                            if let Some(session) = state.sessions.get_mut(session_id) {
+                                session.push_message(hobbes_core::message::Message {
+                                    id: new_id,
+                                    author: "Hobbes".to_string(),
+                                    content: hobbes_core::message::MessageContent::ToolCall(tool_call.clone()),
+                                    interrupted: false,
+                                    error: None,
+                                    parent_message_id: None,
+                                    created_at: Some(chrono::Utc::now()),
+                                    metadata: None,
+                                });
+                            }
+                            new_id
+                        }
+                    };
+
+                    dispatched_calls.push((tool_call.clone(), tool_call_message_id));
+
+                    // Each tool call runs in its own spawned task, so parallel calls execute
+                    // concurrently. It owns a sender to the results channel.
+                    let mcp_manager = self.mcp_manager;
+                    let mut session_state = self.session_state;
+                    let document_store = self.document_store;
+                    let tool_results_tx = tool_results_tx.clone(); // Clone sender for the task
+                    let cancel_rx = cancel_rx.clone();
+                    let session_id = session_id.to_string();
+                    let requests = requests.clone();
+                    spawn(async move {
+                        let args_json: serde_json::Value = serde_json::from_str(&tool_call.arguments).unwrap_or(serde_json::Value::Null);
+                        // Clone the manager out of the signal so the read guard isn't held across the call.
+                        let manager = mcp_manager.read().clone();
+                        let result = manager.use_mcp_tool(&tool_call.server_name, &tool_call.tool_name, args_json, false, &requests, Some(cancel_rx)).await;
+
+                        let mut state = session_state.write();
+                        let mut awaiting_permission = false;
+                        let (status, response_str) = match result {
+                            Ok(response) => (ToolCallStatus::Completed, serde_json::to_string_pretty(&response).unwrap_or_default()),
+                            Err(e) => {
+                                if let Ok(tool_call_req) = serde_json::from_str::<hobbes_core::message::ToolCall>(&e) {
+                                    if let Some(msg) = state.get_message_mut(&session_id, &tool_call_message_id) {
+                                        msg.content = hobbes_core::message::MessageContent::PermissionRequest(tool_call_req);
+                                    }
+                                    awaiting_permission = true;
+                                    (ToolCallStatus::Error, e)
+                                } else {
+                                    (ToolCallStatus::Error, e)
+                                }
+                            }
+                        };
+
+                        if let Some(msg) = state.get_message_mut(&session_id, &tool_call_message_id) {
+                            if let hobbes_core::message::MessageContent::ToolCall(tc) = &mut msg.content {
+                                tc.status = status;
+                                tc.response = response_str.clone();
+                            }
+                        }
+
+                        let record = hobbes_core::message::ToolCallRecord {
+                            call: tool_call.clone(),
+                            result: hobbes_core::message::ToolResult {
+                                status,
+                                response: response_str,
+                            },
+                            batch_id: Some(message_id),
+                        };
+                        if let Some(store) = document_store.read().as_ref().cloned() {
+                            let record_for_store = record.clone();
+                            spawn(async move {
+                                if let Err(e) = store.upsert_tool_result(&record_for_store).await {
+                                    tracing::error!("Failed to upsert tool result: {}", e);
+                                }
+                            });
+                        }
+                        let _ = tool_results_tx.send((call_index, record, awaiting_permission));
+                    });
+                    is_first_message = false;
+                }
+            }
+        }
+
+        // Collect all results from the channel.
+        // We drop the original sender here. The loop will only complete
+        // once all the spawned tool-call tasks have finished and dropped their sender clones.
+        // This is a robust way to await an unknown number of concurrent tasks.
+        drop(tool_results_tx);
+        let mut collected_records = Vec::new();
+        while let Some(indexed_record) = tool_results_rx.recv().await {
+            collected_records.push(indexed_record);
+        }
+        // A task dropped before it reported (cancelled with its scope, or panicked) still gets
+        // an error result, so the batched response turn answers every call.
+        for (index, (call, tool_call_message_id)) in dispatched_calls.into_iter().enumerate() {
+            if collected_records.iter().any(|(collected, _, _)| *collected == index) {
+                continue;
+            }
+            tracing::error!("Tool call '{}' ended without reporting a result.", call.tool_name);
+            let response = "The tool call ended without returning a result.".to_string();
+            if let Some(msg) = self.session_state.write().get_message_mut(session_id, &tool_call_message_id) {
+                if let hobbes_core::message::MessageContent::ToolCall(tc) = &mut msg.content {
+                    tc.status = ToolCallStatus::Error;
+                    tc.response = response.clone();
+                }
+            }
+            let record = hobbes_core::message::ToolCallRecord {
+                call,
+                result: hobbes_core::message::ToolResult { status: ToolCallStatus::Error, response },
+                batch_id: Some(message_id),
+            };
+            collected_records.push((index, record, false));
+        }
+
+        // Restore the requested order so results line up with the calls in the batched turn.
+        collected_records.sort_by_key(|(index, _, _)| *index);
+        let awaiting_permission = collected_records.iter().any(|(_, _, awaiting)| *awaiting);
+        // Stop may have been pressed after the model stream ended, while tools were running.
+        // The tools abort on their own, but the loop mustn't re-prompt with their results.
+        let cancelled = cancelled || *cancel_rx.borrow();
+        StepOutcome {
+            records: collected_records.into_iter().map(|(_, record, _)| record).collect(),
+            awaiting_permission,
+            cancelled,
+        }
+    }
+
+    /// Stops every in-flight response: aborts the model streams and cancels pending tool calls.
+    pub fn cancel(self) {
+        for (message_id, cancel_tx) in self.cancel_txs.read().iter() {
+            tracing::info!(message_id = %message_id, "Cancelling in-flight response.");
+            let _ = cancel_tx.send(true);
+        }
+    }
+
     pub fn take_stream(mut self, message_id: &Uuid) -> Option<UnboundedReceiver<StreamMessage>> {
         self.stream_receivers.write().remove(message_id)
     }
@@ -270,6 +468,7 @@ pub fn StreamManager(props: StreamManagerProps) -> Element {
         document_store,
         tool_call_summarizer: Signal::new(ToolCallSummarizer::new()),
         settings,
+        cancel_txs: Signal::new(HashMap::new()),
     });
 
     // Provide the context to children.
@@ -301,6 +500,7 @@ mod tests {
                 document_store,
                 tool_call_summarizer: Signal::new(ToolCallSummarizer::new()),
                 settings,
+                cancel_txs: Signal::new(HashMap::new()),
             });
 
             let message_id = Uuid::new_v4();
@@ -344,6 +544,7 @@ mod tests {
                 document_store,
                 tool_call_summarizer: Signal::new(ToolCallSummarizer::new()),
                 settings,
+                cancel_txs: Signal::new(HashMap::new()),
             });
 
             let message_id = Uuid::new_v4();
@@ -2,8 +2,9 @@
 use dioxus::prelude::*;
 use dioxus_free_icons::{icons::fi_icons, Icon};
 use super::chat::CodeBlock;
-use super::shared::{ToolCall, ToolCallStatus};
+use hobbes_core::message::{ToolCall, ToolCallStatus};
 use crate::mcp::manager::McpManager;
+use crate::context::permissions::RequestCounter;
 
 #[derive(Props, Clone, PartialEq)]
 pub struct ToolCallDisplayProps {
@@ -118,7 +119,7 @@ pub struct PermissionPromptProps {
 #[component]
 pub fn PermissionPrompt(props: PermissionPromptProps) -> Element {
     let mut mcp_manager = consume_context::<Signal<McpManager>>();
-    let mut session_state = consume_context::<Signal<crate::session::SessionState>>();
+    let mut session_state = consume_context::<Signal<hobbes_core::session::SessionState>>();
     let tool_call = props.tool_call.clone();
     let tool_call_deny = tool_call.clone();
 
@@ -152,11 +153,11 @@ pub fn PermissionPrompt(props: PermissionPromptProps) -> Element {
                     onclick: move |_| {
                         let mut state = session_state.write();
                         if let Some(msg) = state.get_message_mut_by_execution_id(&tool_call_deny.execution_id) {
-                            if let super::shared::MessageContent::PermissionRequest(tc) = &mut msg.content {
+                            if let hobbes_core::message::MessageContent::PermissionRequest(tc) = &mut msg.content {
                                 tc.status = ToolCallStatus::Error;
                                 tc.response = "Denied by user.".to_string();
                                 // We need to convert it back to a ToolCall to be displayed correctly
-                                msg.content = super::shared::MessageContent::ToolCall(tc.clone());
+                                msg.content = hobbes_core::message::MessageContent::ToolCall(tc.clone());
                             }
                         }
                     },
@@ -169,11 +170,11 @@ pub fn PermissionPrompt(props: PermissionPromptProps) -> Element {
                             let tool_call = tool_call.clone();
                             async move {
                                 let args_json: serde_json::Value = serde_json::from_str(&tool_call.arguments).unwrap_or(serde_json::Value::Null);
-                                let result = mcp_manager.write().use_mcp_tool(&tool_call.server_name, &tool_call.tool_name, args_json, true).await;
+                                let result = mcp_manager.write().use_mcp_tool(&tool_call.server_name, &tool_call.tool_name, args_json, true, &RequestCounter::default(), None).await;
 
                                 let mut state = session_state.write();
                                 if let Some(msg) = state.get_message_mut_by_execution_id(&tool_call.execution_id) {
-                                     if let super::shared::MessageContent::PermissionRequest(tc) = &mut msg.content {
+                                     if let hobbes_core::message::MessageContent::PermissionRequest(tc) = &mut msg.content {
                                         let mut updated_tc = tc.clone();
                                         match result {
                                             Ok(response) => {
@@ -185,7 +186,7 @@ pub fn PermissionPrompt(props: PermissionPromptProps) -> Element {
                                                 updated_tc.response = e;
                                             }
                                         }
-                                        msg.content = super::shared::MessageContent::ToolCall(updated_tc);
+                                        msg.content = hobbes_core::message::MessageContent::ToolCall(updated_tc);
                                     }
                                 }
                             }
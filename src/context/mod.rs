@@ -1,3 +1,4 @@
 pub mod security;
 pub mod prompt_builder;
-pub mod permissions;
\ No newline at end of file
+pub mod permissions;
+pub mod usage;
\ No newline at end of file
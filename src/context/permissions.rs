@@ -1,8 +1,13 @@
+use hobbes_core::message::TokenUsage;
+use crate::context::usage::{BudgetResetSchedule, UsageLedger};
+use hobbes_core::usage::UsageTotals;
 use crate::settings::Settings;
 use dioxus::prelude::Signal;
-use dioxus_signals::Readable;
+use dioxus_signals::{Readable, Writable};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ToolCategory {
@@ -20,11 +25,15 @@ pub enum PermissionStatus {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
 pub struct PermissionSettings {
     pub auto_approval_enabled: bool,
     pub granular_permissions: HashMap<ToolCategory, bool>,
     pub max_requests: u32,
     pub max_cost: f64,
+    /// When the spend counted against `max_cost` starts over.
+    #[serde(default)]
+    pub budget_reset: BudgetResetSchedule,
 }
 
 impl Default for PermissionSettings {
@@ -34,33 +43,48 @@ impl Default for PermissionSettings {
             granular_permissions: HashMap::new(),
             max_requests: 10,
             max_cost: 0.50,
+            budget_reset: BudgetResetSchedule::Daily,
         }
     }
 }
 
+/// The model and tool requests made while answering one user turn, which `max_requests`
+/// limits. Each turn owns its own counter, so turns running side by side don't share a count.
+#[derive(Debug, Clone, Default)]
+pub struct RequestCounter(Arc<AtomicU32>);
+
+impl RequestCounter {
+    pub fn record(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> u32 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct PermissionManager {
     settings: Signal<Settings>,
-    request_count: Signal<u32>,
-    current_cost: Signal<f64>,
+    usage_ledger: Signal<UsageLedger>,
 }
 
 impl PermissionManager {
     pub fn new(settings: Signal<Settings>) -> Self {
         Self {
             settings,
-            request_count: Signal::new(0),
-            current_cost: Signal::new(0.0),
+            usage_ledger: Signal::new(UsageLedger::load()),
         }
     }
 
-    pub fn check_permission(&self, category: &ToolCategory) -> PermissionStatus {
+    /// Whether a tool in `category` may run as part of the turn counted by `requests`.
+    pub fn check_permission(&self, category: &ToolCategory, requests: &RequestCounter) -> PermissionStatus {
         let settings = self.settings.read();
-        if *self.request_count.read() >= settings.permission_settings.max_requests {
+        if requests.count() >= settings.permission_settings.max_requests {
             return PermissionStatus::Denied("Request limit reached".to_string());
         }
 
-        if *self.current_cost.read() >= settings.permission_settings.max_cost {
+        if self.current_cost() >= settings.permission_settings.max_cost {
             return PermissionStatus::Denied("Cost limit reached".to_string());
         }
 
@@ -83,4 +107,31 @@ impl PermissionManager {
         }
     }
 
+    pub fn request_limit_reached(&self, requests: &RequestCounter) -> bool {
+        requests.count() >= self.settings.read().permission_settings.max_requests
+    }
+
+    /// Adds a model response's usage to today's totals and persists them.
+    pub fn record_usage(&self, usage: &TokenUsage, cost: f64) {
+        let mut usage_ledger = self.usage_ledger;
+        let mut ledger = usage_ledger.write();
+        ledger.record(usage, cost);
+        if let Err(e) = ledger.save() {
+            tracing::error!("Failed to save usage ledger: {}", e);
+        }
+    }
+
+    /// The spend in the current budget period, as configured by `budget_reset`.
+    pub fn current_cost(&self) -> f64 {
+        let schedule = self.settings.read().permission_settings.budget_reset;
+        self.usage_ledger.read().period_cost(schedule, chrono::Local::now().date_naive())
+    }
+
+    pub fn cost_limit_reached(&self) -> bool {
+        self.current_cost() >= self.settings.read().permission_settings.max_cost
+    }
+
+    pub fn today_usage(&self) -> UsageTotals {
+        self.usage_ledger.read().today()
+    }
 }
\ No newline at end of file
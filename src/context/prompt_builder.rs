@@ -1,56 +1,143 @@
-use crate::components::llm::{Content, Part, SystemInstruction};
-use crate::session::{Session, Tool};
+use crate::components::llm::{LlmMessage, LlmPart, LlmRole};
+use hobbes_core::session::{Session, Tool};
 use crate::settings::Settings;
+use hobbes_core::generation::GenerationParams;
 use chrono::Utc;
 use serde_json::{self, json};
-use crate::components::chat::Message;
-use crate::components::shared::MessageContent;
-
-impl From<Message> for Content {
-    fn from(msg: Message) -> Self {
-        let role = if msg.author == "User" { "user" } else { "model" }.to_string();
-        match msg.content {
-            MessageContent::Text(text) => Content {
-                role,
-                parts: vec![Part { text }],
-            },
+use hobbes_core::message::Message;
+use hobbes_core::message::{Attachment, MessageContent};
+use base64::prelude::*;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+impl LlmMessage {
+    fn from_message(msg: &Message, attachments: &AttachmentData) -> Self {
+        let role = if msg.author == "User" { LlmRole::User } else { LlmRole::Assistant };
+        match msg.content.clone() {
+            MessageContent::Text(text) => LlmMessage::text(role, text),
             MessageContent::ToolCall(_) => {
                 // We don't want to include tool calls in the history this way,
                 // as they are handled specially below. Return an empty part to be filtered out.
-                Content {
-                    role,
-                    parts: vec![Part { text: String::new() }],
-                }
+                LlmMessage::text(role, String::new())
             },
             MessageContent::PermissionRequest(_) => {
                 // Permission requests are UI-only and should not be in the prompt history.
-                Content {
-                    role,
-                    parts: vec![Part { text: String::new() }],
+                LlmMessage::text(role, String::new())
+            }
+            MessageContent::Attachment(attachment) => {
+                let part = attachments.0.get(&attachment.path).cloned().unwrap_or_else(|| missing_attachment(&attachment));
+                LlmMessage { role, parts: vec![part] }
+            }
+        }
+    }
+}
+
+/// The attachments a prompt includes, read from disk and encoded. Files can be large, so they
+/// are prepared by `AttachmentData::read` off the UI thread before the prompt is built.
+#[derive(Default)]
+pub struct AttachmentData(HashMap<PathBuf, LlmPart>);
+
+impl AttachmentData {
+    /// Reads the attachments `PromptBuilder` will send for `session`. The future doesn't
+    /// borrow the session, so the state doesn't stay locked while the files are read.
+    pub fn read(session: &Session, settings: &Settings) -> impl std::future::Future<Output = Self> + 'static {
+        let attachments: Vec<Attachment> = prompt_history(session, settings.chat_history_length)
+            .into_iter()
+            .filter_map(|message| match &message.content {
+                MessageContent::Attachment(attachment) => Some(attachment.clone()),
+                _ => None,
+            })
+            .collect();
+        async move {
+            if attachments.is_empty() {
+                return Self::default();
+            }
+            let read = tokio::task::spawn_blocking(move || {
+                attachments.iter().map(|attachment| (attachment.path.clone(), attachment_part(attachment))).collect()
+            });
+            match read.await {
+                Ok(parts) => Self(parts),
+                Err(e) => {
+                    tracing::error!("Failed to read attachments: {}", e);
+                    Self::default()
                 }
             }
         }
     }
 }
 
-/// A structured container for all components of an LLM prompt.
+fn missing_attachment(attachment: &Attachment) -> LlmPart {
+    LlmPart::Text(format!("[The attached file `{}` is no longer available.]", attachment.file_name))
+}
+
+/// Reads an attachment back from disk. Text files are inlined as text, which every provider
+/// accepts; images and PDFs are sent as inline data.
+fn attachment_part(attachment: &Attachment) -> LlmPart {
+    let data = match std::fs::read(&attachment.path) {
+        Ok(data) => data,
+        Err(e) => {
+            tracing::error!("Failed to read attachment {:?}: {}", attachment.path, e);
+            return missing_attachment(attachment);
+        }
+    };
+    if attachment.is_text() {
+        LlmPart::Text(format!(
+            "Attached file `{}`:\n```\n{}\n```",
+            attachment.file_name,
+            String::from_utf8_lossy(&data)
+        ))
+    } else {
+        LlmPart::InlineData {
+            name: attachment.file_name.clone(),
+            mime_type: attachment.mime_type.clone(),
+            data: BASE64_STANDARD.encode(data),
+        }
+    }
+}
+
+/// A structured, provider-neutral container for all components of an LLM prompt.
 #[derive(Debug)]
 pub struct LlmPrompt {
-    pub system_instruction: Option<SystemInstruction>,
-    pub contents: Vec<Content>,
+    pub system_instruction: Option<String>,
+    pub messages: Vec<LlmMessage>,
     pub tools: Option<Vec<Tool>>,
+    /// When set, the response must be a JSON value matching this JSON schema. Providers
+    /// enforce it with their structured-output mode where they have one.
+    pub response_schema: Option<serde_json::Value>,
+    pub generation: GenerationParams,
+}
+
+/// The messages of `session` that are sent to the model: the first user message, to keep
+/// the original intent, and then the last `history_len` messages of the active branch.
+fn prompt_history(session: &Session, history_len: usize) -> Vec<&Message> {
+    // Only the active branch is sent; other branches are alternatives the user moved away from.
+    let messages = session.active_path();
+    let first_message = messages.iter().copied().find(|m| m.author == "User" && matches!(m.content, MessageContent::Text(_)));
+    let start_index = messages.len().saturating_sub(history_len);
+    // Avoid duplicating the first message if it's within the recent window
+    first_message
+        .into_iter()
+        .chain(messages[start_index..].iter().copied().filter(|m| Some(m.id) != first_message.map(|first| first.id)))
+        .collect()
 }
 
 /// Builds a structured `LlmPrompt` object for the LLM.
 pub struct PromptBuilder<'a> {
     session: &'a Session,
     settings: &'a Settings,
-    session_state: &'a crate::session::SessionState,
+    session_state: &'a hobbes_core::session::SessionState,
+    attachments: &'a AttachmentData,
 }
 
 impl<'a> PromptBuilder<'a> {
-    pub fn new(session: &'a Session, settings: &'a Settings, session_state: &'a crate::session::SessionState) -> Self {
-        Self { session, settings, session_state }
+    /// `attachments` should come from `AttachmentData::read` for the same session.
+    pub fn new(
+        session: &'a Session,
+        settings: &'a Settings,
+        session_state: &'a hobbes_core::session::SessionState,
+        attachments: &'a AttachmentData,
+    ) -> Self {
+        Self { session, settings, session_state, attachments }
     }
 
     /// Builds the structured `LlmPrompt` with system instructions, tools, and conversation history.
@@ -90,7 +177,7 @@ impl<'a> PromptBuilder<'a> {
             persona = format!("{}\n\nCRITICAL INSTRUCTION: {}", persona, instruction);
         }
 
-        if self.session_state.tool_call_history.iter().any(|r| matches!(r.result.status, crate::components::shared::ToolCallStatus::Error)) {
+        if self.session_state.tool_call_history.iter().any(|r| matches!(r.result.status, hobbes_core::message::ToolCallStatus::Error)) {
             let recovery_instruction = "\n\nCRITICAL RECOVERY INSTRUCTION: A previous tool call failed. Analyze the error message in the `<tool_response>` and attempt a different tool call to accomplish the user's goal. Do not repeat the failed tool call.";
             persona.push_str(recovery_instruction);
         }
@@ -125,83 +212,68 @@ impl<'a> PromptBuilder<'a> {
 
         let instruction_text = serde_json::to_string(&system_context_map).unwrap_or_default();
         let system_instruction = if !instruction_text.is_empty() && instruction_text != "{}" {
-            Some(SystemInstruction {
-                parts: vec![Part { text: instruction_text }],
-            })
+            Some(instruction_text)
         } else {
             None
         };
 
         // 3. Construct the conversational contents.
         let mut contents = Vec::new();
-        let history_len = self.settings.chat_history_length;
-        let messages = &self.session.messages;
-        let mut first_message_id = None;
-
-        // 1. Add the first user message to preserve the original intent.
-        if let Some(first_message) = messages.iter().find(|m| m.author == "User") {
-            if let MessageContent::Text(_) = &first_message.content {
-                 contents.push(first_message.clone().into());
-                 first_message_id = Some(first_message.id);
+        for message in prompt_history(self.session, self.settings.chat_history_length) {
+            let content = LlmMessage::from_message(message, self.attachments);
+            // Only add non-empty text messages
+            if !content.parts.iter().any(LlmPart::is_empty) {
+                contents.push(content);
             }
         }
-
-        // 2. Add the last `history_len` messages.
-        let start_index = messages.len().saturating_sub(history_len);
         
-        for message in messages.iter().skip(start_index) {
-            // Avoid duplicating the first message if it's within the recent window
-            if Some(message.id) != first_message_id {
-                let content: Content = message.clone().into();
-                // Only add non-empty text messages
-                if !content.parts.iter().any(|p| p.text.is_empty()) {
-                    contents.push(content);
-                }
-            }
-        }
-        
-        // 4. Append the tool call history, correctly formatted for the model.
-        for record in &self.session_state.tool_call_history {
-            // First, add the model's tool call request to the history.
-            contents.push(Content {
-                role: "model".to_string(),
-                parts: vec![Part {
-                    // TODO: Refactor the `Part` struct to natively support `functionCall` objects
-                    // instead of serializing to a string. This is a temporary workaround to match
-                    // the Gemini API's expected structure.
-                    text: format!(
-                        r#"{{"functionCall": {{"name": "{}", "args": {}}}}}"#,
-                        record.call.tool_name, record.call.arguments
-                    ),
-                }],
+        // 4. Append the tool call history, correctly formatted for the model. Calls made in
+        // the same turn are sent as one batched call turn followed by one batched response turn.
+        let batches = self
+            .session_state
+            .tool_call_history
+            .chunk_by(|a, b| a.batch_id.is_some() && a.batch_id == b.batch_id);
+        for batch in batches {
+            // First, add the model's tool call requests to the history.
+            contents.push(LlmMessage {
+                role: LlmRole::Assistant,
+                parts: batch
+                    .iter()
+                    .map(|record| LlmPart::ToolCall {
+                        id: record.call.execution_id.clone(),
+                        name: record.call.tool_name.clone(),
+                        args: serde_json::from_str(&record.call.arguments).unwrap_or_else(|_| json!({})),
+                    })
+                    .collect(),
             });
 
-            // Then, add our response containing the tool's result.
-            contents.push(Content {
-                role: "user".to_string(), // As per docs, the tool response is from the 'user'
-                parts: vec![Part {
-                    // TODO: Refactor the `Part` struct to natively support `functionResponse` objects.
-                    text: format!(
-                        r#"{{"functionResponse": {{"name": "{}", "response": {{"result": {}}}}}}}"#,
-                        record.call.tool_name, record.result.response
-                    ),
-                }],
+            // Then, add our responses containing the tools' results.
+            contents.push(LlmMessage {
+                role: LlmRole::User, // As per docs, the tool response is from the 'user'
+                parts: batch
+                    .iter()
+                    .map(|record| LlmPart::ToolResult {
+                        id: record.call.execution_id.clone(),
+                        name: record.call.tool_name.clone(),
+                        response: record.result.response.clone(),
+                        is_error: matches!(record.result.status, hobbes_core::message::ToolCallStatus::Error),
+                    })
+                    .collect(),
             });
         }
 
         // 5. Add the current user message, only if it's not empty.
         if !user_message.is_empty() {
-            contents.push(Content {
-                role: "user".to_string(),
-                parts: vec![Part { text: user_message }],
-            });
+            contents.push(LlmMessage::text(LlmRole::User, user_message));
         }
 
         // 6. Assemble and return the final LlmPrompt object.
         LlmPrompt {
             system_instruction,
-            contents,
+            messages: contents,
             tools,
+            response_schema: None,
+            generation: self.settings.generation.merged_with(&self.session.generation),
         }
     }
 }
@@ -231,8 +303,8 @@ fn recursively_remove_keys(value: &mut serde_json::Value, keys_to_remove: &[&str
 mod tests {
     use super::*;
     use crate::context::prompt_builder::{recursively_remove_keys, PromptBuilder};
-    use crate::mcp::manager::{McpContext, McpServerContext};
-    use crate::session::{ActiveContext, ConversationSummary, ConversationSummaryEntities, Session};
+    use hobbes_core::mcp::{McpContext, McpServerContext};
+    use hobbes_core::session::{ActiveContext, ConversationSummary, ConversationSummaryEntities, Session};
     use crate::settings::Settings;
     use chrono::Utc;
     use rmcp::model::Tool;
@@ -302,6 +374,7 @@ mod tests {
 
         let mcp_context = McpContext {
             servers: vec![server],
+            ..Default::default()
         };
 
         let active_context = ActiveContext {
@@ -321,8 +394,11 @@ mod tests {
             id: "test_session".to_string(),
             name: "Test Session".to_string(),
             messages: vec![],
+            active_leaf_id: None,
             active_context,
             last_updated: Utc::now(),
+            usage: Default::default(),
+            generation: Default::default(),
         }
     }
 
@@ -330,8 +406,9 @@ mod tests {
     fn test_build_prompt_renames_schema_and_removes_keys() {
         let session = create_mock_session_with_tools();
         let settings = Settings::default();
-        let session_state = crate::session::SessionState::default();
-        let builder = PromptBuilder::new(&session, &settings, &session_state);
+        let session_state = hobbes_core::session::SessionState::default();
+        let attachments = AttachmentData::default();
+        let builder = PromptBuilder::new(&session, &settings, &session_state, &attachments);
 
         let prompt = builder.build_prompt("What's the weather?".to_string(), None);
 
@@ -354,4 +431,20 @@ mod tests {
         assert!(parameters.get("$schema").is_none());
         assert!(parameters.get("additionalProperties").is_none());
     }
+
+    #[test]
+    fn test_session_generation_overrides_global_settings() {
+        let mut session = create_mock_session_with_tools();
+        session.generation = GenerationParams { temperature: Some(1.2), ..Default::default() };
+        let settings = Settings {
+            generation: GenerationParams { temperature: Some(0.2), max_output_tokens: Some(1024), ..Default::default() },
+            ..Default::default()
+        };
+        let session_state = hobbes_core::session::SessionState::default();
+
+        let prompt = PromptBuilder::new(&session, &settings, &session_state, &AttachmentData::default()).build_prompt("Brainstorm".to_string(), None);
+
+        assert_eq!(prompt.generation.temperature, Some(1.2));
+        assert_eq!(prompt.generation.max_output_tokens, Some(1024));
+    }
 }
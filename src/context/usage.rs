@@ -0,0 +1,199 @@
+use chrono::{Datelike, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+
+use hobbes_core::message::TokenUsage;
+use hobbes_core::usage::UsageTotals;
+
+/// The price of a model in USD per million tokens.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ModelPrice {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+/// How often the `max_cost` budget starts over.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetResetSchedule {
+    #[default]
+    Daily,
+    Weekly,
+    Monthly,
+    Never,
+}
+
+impl BudgetResetSchedule {
+    pub const ALL: [BudgetResetSchedule; 4] = [
+        BudgetResetSchedule::Daily,
+        BudgetResetSchedule::Weekly,
+        BudgetResetSchedule::Monthly,
+        BudgetResetSchedule::Never,
+    ];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            BudgetResetSchedule::Daily => "daily",
+            BudgetResetSchedule::Weekly => "weekly",
+            BudgetResetSchedule::Monthly => "monthly",
+            BudgetResetSchedule::Never => "never",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            BudgetResetSchedule::Daily => "Every day",
+            BudgetResetSchedule::Weekly => "Every week (Monday)",
+            BudgetResetSchedule::Monthly => "Every month",
+            BudgetResetSchedule::Never => "Never",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|schedule| schedule.id() == id)
+    }
+
+    /// The first day of the budget period containing `today`.
+    pub fn period_start(&self, today: NaiveDate) -> NaiveDate {
+        match self {
+            BudgetResetSchedule::Daily => today,
+            BudgetResetSchedule::Weekly => today - chrono::Duration::days(today.weekday().num_days_from_monday() as i64),
+            BudgetResetSchedule::Monthly => today.with_day(1).unwrap_or(today),
+            BudgetResetSchedule::Never => NaiveDate::MIN,
+        }
+    }
+}
+
+/// The default price table. Models that aren't listed (e.g. local Ollama models) are free.
+pub fn default_model_prices() -> HashMap<String, ModelPrice> {
+    [
+        ("gemini-2.5-pro", 1.25, 10.00),
+        ("gemini-2.5-flash-lite", 0.10, 0.40),
+        ("gemini-2.5-flash", 0.30, 2.50),
+        ("gemini-1.5-pro", 1.25, 5.00),
+        ("gemini-1.5-flash", 0.075, 0.30),
+        ("gpt-4o-mini", 0.15, 0.60),
+        ("gpt-4o", 2.50, 10.00),
+        ("gpt-4.1-mini", 0.40, 1.60),
+        ("gpt-4.1", 2.00, 8.00),
+        ("claude-opus-4", 15.00, 75.00),
+        ("claude-sonnet-4", 3.00, 15.00),
+        ("claude-3-5-haiku", 0.80, 4.00),
+    ]
+    .into_iter()
+    .map(|(model, input, output)| {
+        (model.to_string(), ModelPrice { input_per_million: input, output_per_million: output })
+    })
+    .collect()
+}
+
+/// Looks up the price for `model`, matching either the exact name or the longest listed
+/// prefix, so that e.g. `gemini-2.5-flash-latest` uses the `gemini-2.5-flash` price.
+pub fn price_for<'a>(prices: &'a HashMap<String, ModelPrice>, model: &str) -> Option<&'a ModelPrice> {
+    prices
+        .iter()
+        .filter(|(name, _)| model.starts_with(name.as_str()))
+        .max_by_key(|(name, _)| name.len())
+        .map(|(_, price)| price)
+}
+
+pub fn cost_for(prices: &HashMap<String, ModelPrice>, model: &str, usage: &TokenUsage) -> f64 {
+    match price_for(prices, model) {
+        Some(price) => {
+            (usage.prompt_tokens as f64 * price.input_per_million
+                + usage.completion_tokens as f64 * price.output_per_million)
+                / 1_000_000.0
+        }
+        None => {
+            tracing::debug!("No price configured for model '{}'; counting its usage as free.", model);
+            0.0
+        }
+    }
+}
+
+fn get_usage_path() -> Option<PathBuf> {
+    dirs::config_dir().and_then(|mut path| {
+        path.push("cai-hobbes");
+        fs::create_dir_all(&path).ok()?;
+        path.push("usage.json");
+        Some(path)
+    })
+}
+
+/// Per-day usage totals, persisted to `usage.json` alongside the sessions.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct UsageLedger {
+    pub days: BTreeMap<NaiveDate, UsageTotals>,
+}
+
+impl UsageLedger {
+    /// Reads `usage.json`. A file that can't be parsed is quarantined, so the spend it records
+    /// isn't overwritten by the next save, and an empty ledger is used instead.
+    pub fn load() -> Self {
+        let Some(path) = get_usage_path() else { return Self::default() };
+        match fs::read_to_string(&path) {
+            Ok(data) => serde_json::from_str(&data).unwrap_or_else(|e| {
+                tracing::error!("Failed to parse usage.json, starting a new ledger: {}", e);
+                if let Err(e) = hobbes_core::persist::quarantine(&path) {
+                    tracing::error!("Failed to move the unreadable usage.json aside: {}", e);
+                }
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), std::io::Error> {
+        let path = get_usage_path().ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Could not find usage path"))?;
+        let data = serde_json::to_string_pretty(self).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        hobbes_core::persist::write_atomic(&path, data.as_bytes())
+    }
+
+    pub fn record(&mut self, usage: &TokenUsage, cost: f64) {
+        self.days.entry(Local::now().date_naive()).or_default().add(usage, cost);
+    }
+
+    pub fn today(&self) -> UsageTotals {
+        self.days.get(&Local::now().date_naive()).copied().unwrap_or_default()
+    }
+
+    /// The total cost of the budget period that contains `today`.
+    pub fn period_cost(&self, schedule: BudgetResetSchedule, today: NaiveDate) -> f64 {
+        self.days.range(schedule.period_start(today)..).map(|(_, totals)| totals.cost).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_price_lookup_prefers_longest_prefix() {
+        let prices = default_model_prices();
+        assert_eq!(price_for(&prices, "gemini-2.5-flash-lite-preview").unwrap().input_per_million, 0.10);
+        assert_eq!(price_for(&prices, "gemini-2.5-flash-latest").unwrap().input_per_million, 0.30);
+        assert!(price_for(&prices, "llama3.2:3b").is_none());
+
+        let usage = TokenUsage { prompt_tokens: 1_000_000, completion_tokens: 100_000, total_tokens: 1_100_000 };
+        assert!((cost_for(&prices, "gpt-4o", &usage) - 3.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_period_cost_resets_on_schedule() {
+        let day = |d: u32| NaiveDate::from_ymd_opt(2025, 3, d).unwrap();
+        let mut ledger = UsageLedger::default();
+        // 2025-03-01 is a Saturday; 2025-03-03 is the following Monday.
+        for (d, cost) in [(1, 1.0), (2, 2.0), (3, 4.0), (5, 8.0)] {
+            ledger.days.insert(day(d), UsageTotals { cost, ..Default::default() });
+        }
+        ledger.days.insert(NaiveDate::from_ymd_opt(2025, 2, 28).unwrap(), UsageTotals { cost: 16.0, ..Default::default() });
+
+        assert_eq!(ledger.period_cost(BudgetResetSchedule::Daily, day(5)), 8.0);
+        assert_eq!(ledger.period_cost(BudgetResetSchedule::Weekly, day(5)), 12.0);
+        assert_eq!(ledger.period_cost(BudgetResetSchedule::Monthly, day(5)), 15.0);
+        assert_eq!(ledger.period_cost(BudgetResetSchedule::Never, day(5)), 31.0);
+    }
+}
@@ -13,7 +13,6 @@ mod hotkey;
 mod permissions;
 mod menu;
 mod tray;
-mod session;
 mod settings;
 mod context;
 mod processing;
@@ -30,8 +29,9 @@ fn main() {
     permissions::check_and_prompt_for_accessibility();
 
 
-    // Load session state to get window size
-    let initial_state = session::SessionState::load().unwrap_or_default();
+    // Loaded once here for the window size, then handed to the app so a recovery offer
+    // made while loading reaches the UI.
+    let initial_state = SessionState::new();
     let initial_width = initial_state.window_width;
     let initial_height = initial_state.window_height;
 
@@ -56,11 +56,12 @@ fn main() {
                 )
                 .with_custom_head(r#"<style>html, body { height: 100%; margin: 0; padding: 0; background-color: #111827; }</style>"#.to_string() + r#"<style>"# + include_str!("../assets/output.css") + r#"</style>"#)
         )
+        .with_context(initial_state)
         .launch(app);
 }
 
 use crate::context::permissions::PermissionManager;
-use crate::session::SessionState;
+use hobbes_core::session::SessionState;
 use crate::settings::SettingsManager;
 use crate::{components::stream_manager::StreamManager, mcp::manager::McpManager, services::document_store::DocumentStore};
 use std::path::PathBuf;
@@ -81,18 +82,24 @@ fn get_mcp_config_path() -> PathBuf {
 
 fn app() -> Element {
     let window = use_window();
-    let session_state = use_context_provider(|| Signal::new(SessionState::new()));
+    let session_state = use_context_provider(|| Signal::new(consume_context::<SessionState>()));
     let settings_manager = use_context_provider(|| Signal::new(SettingsManager::new(get_settings_path())));
     let mut settings = use_context_provider(|| {
         let mut settings = settings_manager.read().load();
         if let Ok(api_key) = crate::secure_storage::retrieve_secret("api_key") {
             settings.api_key = Some(api_key);
         }
+        if let Ok(api_key) = crate::secure_storage::retrieve_secret("openai_api_key") {
+            settings.openai.api_key = Some(api_key);
+        }
+        if let Ok(api_key) = crate::secure_storage::retrieve_secret("anthropic_api_key") {
+            settings.anthropic.api_key = Some(api_key);
+        }
         Signal::new(settings)
     });
     let permission_manager = use_context_provider(|| Signal::new(PermissionManager::new(settings)));
     let mcp_manager = use_context_provider(|| Signal::new(McpManager::new(get_mcp_config_path(), permission_manager.clone())));
-    let mcp_context = use_context_provider(|| Signal::new(mcp::manager::McpContext { servers: Vec::new() }));
+    let mcp_context = use_context_provider(|| Signal::new(hobbes_core::mcp::McpContext::default()));
         let document_store = use_context_provider(|| Signal::new(None));
     
         use_effect(move || {
@@ -112,12 +119,7 @@ fn app() -> Element {
         });
 
     use_effect(move || {
-        let manager = mcp_manager.read().clone();
-        let mcp_context_signal = mcp_context.clone();
-        let settings_clone = settings.read().clone();
-        spawn(async move {
-            manager.launch_servers(mcp_context_signal, settings_clone).await;
-        });
+        mcp_manager.peek().launch_servers(mcp_context, settings);
     });
 
     let mut show_session_manager = use_signal(|| false);
@@ -311,7 +313,9 @@ fn app() -> Element {
                         }
                     }
                 }
-                
+
+                components::recovery_prompt::RecoveryPrompt {}
+
                 // Mouse move handler for resizing
                 if *is_dragging.read() {
                     div {
@@ -1,40 +1,244 @@
-use dioxus::prelude::spawn;
+use dioxus::prelude::{spawn, Task};
 use dioxus_signals::{Readable, Writable};
-use rmcp::model::{CallToolRequestParam, Tool};
-use rmcp::service::{RoleClient, RunningService, ServiceExt};
-use rmcp::transport::child_process::TokioChildProcess;
+use rmcp::model::{CallToolRequest, CallToolRequestParam, CancelledNotificationParam, ClientRequest, ServerResult, Tool};
+use rmcp::service::{Peer, PeerRequestOptions, RequestHandle, RoleClient, RunningService, ServiceError, ServiceExt};
+use rmcp::transport::sse_client::{SseClientConfig, SseClientTransport};
+use rmcp::transport::streamable_http_client::{StreamableHttpClientTransport, StreamableHttpClientTransportConfig};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::cell::{OnceCell, RefCell};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::sync::Arc;
-use tokio::process::Command;
-use crate::context::permissions::{PermissionManager, PermissionStatus, ToolCategory};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::{Child, Command};
+use crate::context::permissions::{PermissionManager, PermissionStatus, RequestCounter, ToolCategory};
+use crate::settings::Settings;
+use hobbes_core::mcp::{McpContext, McpServerContext, McpServerState, McpServerStatus};
 use dioxus::prelude::Signal;
-use tokio::sync::Mutex;
+use notify::{RecursiveMode, Watcher};
+use tokio::sync::{watch, Mutex};
 
-#[derive(Deserialize, Debug, Clone)]
+/// A server as written in `mcp_servers.json`. Serializing gives its entry in the `mcpServers`
+/// map, where the name is the key.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
 pub struct McpServerConfig {
+    #[serde(skip_serializing)]
     pub name: String,
+    /// The program that starts a local server. Unused when `url` is set.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub command: String,
-    #[serde(default)]
+    /// Arguments passed to `command` as they are, without going through a shell.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub description: String,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub env: HashMap<String, String>,
-    #[serde(default)]
+    /// The working directory for `command`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<PathBuf>,
+    /// The endpoint of a server that runs as an HTTP service.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Headers sent with every request to `url`, e.g. `Authorization`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+    /// How to talk to the server at `url`.
+    #[serde(default, skip_serializing_if = "RemoteTransport::is_default")]
+    pub transport: RemoteTransport,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
     pub disabled: bool,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
-pub struct McpContext {
-    pub servers: Vec<McpServerContext>,
+/// The fields of a server entry that `McpServerConfig` owns. Any others, such as settings only
+/// other MCP clients understand, are kept when Hobbes rewrites the entry.
+const CONFIG_FIELDS: &[&str] = &["command", "args", "description", "env", "cwd", "url", "headers", "transport", "disabled"];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RemoteTransport {
+    #[default]
+    StreamableHttp,
+    /// The older HTTP+SSE transport: a long-lived event stream plus a POST endpoint.
+    Sse,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
-pub struct McpServerContext {
-    pub name: String,
-    pub description: String,
-    pub tools: Vec<Tool>,
+impl RemoteTransport {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Characters that make a command line a shell script rather than a program and its arguments.
+const SHELL_SYNTAX: &[char] = &['|', '&', ';', '<', '>', '$', '`', '(', ')'];
+
+impl McpServerConfig {
+    /// Legacy entries give the whole command line as one string, which used to run through
+    /// `sh -c`. Splits it into a program and arguments the way a shell would, expanding a
+    /// leading `~` to the home directory. Command lines that need a shell are rejected.
+    fn split_legacy_command(mut self) -> Result<Self, String> {
+        if self.url.is_some() || !self.args.is_empty() {
+            return Ok(self);
+        }
+        if self.command.contains(SHELL_SYNTAX) {
+            return Err(format!(
+                "its command `{}` uses shell syntax, which Hobbes no longer runs through a shell. Move it to the `mcpServers` format with the program in `command` and its arguments in `args`.",
+                self.command
+            ));
+        }
+        let mut argv: Vec<String> = shlex::split(&self.command)
+            .filter(|argv| !argv.is_empty())
+            .ok_or_else(|| format!("can't parse command `{}`", self.command))?
+            .into_iter()
+            .map(expand_home)
+            .collect();
+        self.command = argv.remove(0);
+        self.args = argv;
+        Ok(self)
+    }
+
+    /// Whether a running server for `self` can stay up when its entry becomes `other`. Only
+    /// the fields used to launch or reach the server count; a new description doesn't need
+    /// a restart.
+    fn runs_as(&self, other: &McpServerConfig) -> bool {
+        self.name == other.name
+            && self.disabled == other.disabled
+            && self.command == other.command
+            && self.args == other.args
+            && self.env == other.env
+            && self.cwd == other.cwd
+            && self.url == other.url
+            && self.headers == other.headers
+            && self.transport == other.transport
+    }
+}
+
+fn expand_home(word: String) -> String {
+    match (word.strip_prefix('~'), dirs::home_dir()) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => format!("{}{}", home.display(), rest),
+        _ => word,
+    }
+}
+
+/// Reads `mcp_servers.json`. It is either the `{"mcpServers": {"<name>": {...}}}` map other
+/// MCP clients use, or the array of named servers Hobbes used to read. Servers that can't be
+/// read are logged and skipped.
+pub fn parse_configs(content: &str) -> Result<Vec<McpServerConfig>, String> {
+    let entries: Vec<(String, Value, bool)> = match serde_json::from_str(content).map_err(|e| e.to_string())? {
+        Value::Array(entries) => entries
+            .into_iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let name = entry.get("name").and_then(Value::as_str).map(str::to_string).unwrap_or_else(|| format!("#{}", i + 1));
+                (name, entry, true)
+            })
+            .collect(),
+        Value::Object(mut file) => match file.remove("mcpServers") {
+            Some(Value::Object(servers)) => servers
+                .into_iter()
+                .map(|(name, mut entry)| {
+                    if let Value::Object(fields) = &mut entry {
+                        fields.insert("name".to_string(), Value::String(name.clone()));
+                    }
+                    (name, entry, false)
+                })
+                .collect(),
+            _ => return Err("expected an `mcpServers` object".to_string()),
+        },
+        _ => return Err("expected an `mcpServers` object or an array of servers".to_string()),
+    };
+
+    Ok(entries
+        .into_iter()
+        .filter_map(|(name, entry, legacy)| {
+            let config = serde_json::from_value::<McpServerConfig>(entry).map_err(|e| e.to_string());
+            match config.and_then(|config| if legacy { config.split_legacy_command() } else { Ok(config) }) {
+                Ok(config) => Some(config),
+                Err(e) => {
+                    tracing::error!("Skipping MCP server '{}' in mcp_servers.json: {}", name, e);
+                    None
+                }
+            }
+        })
+        .collect())
+}
+
+/// The entry for `config` in the `mcpServers` map, keeping the fields of `existing` that
+/// Hobbes doesn't know about.
+fn server_entry(config: &McpServerConfig, existing: Option<Value>) -> Value {
+    let mut entry = match existing {
+        Some(Value::Object(entry)) => entry,
+        _ => Map::new(),
+    };
+    for field in CONFIG_FIELDS {
+        entry.remove(*field);
+    }
+    if let Ok(Value::Object(fields)) = serde_json::to_value(config) {
+        entry.extend(fields);
+    }
+    Value::Object(entry)
+}
+
+/// Applies `edit` to the `mcpServers` map in the contents of `mcp_servers.json` and returns the
+/// new contents. A file in the legacy array format is converted.
+fn edit_config_file(content: &str, edit: impl FnOnce(&mut Map<String, Value>) -> Result<(), String>) -> Result<String, String> {
+    let mut file = if content.trim().is_empty() {
+        Value::Object(Map::new())
+    } else {
+        serde_json::from_str(content).map_err(|e| format!("Failed to parse mcp_servers.json: {}", e))?
+    };
+    if let Value::Array(entries) = &file {
+        let configs = parse_configs(content)?;
+        if configs.len() != entries.len() {
+            return Err("Some servers in mcp_servers.json can't be read, and converting the file would drop them. Fix or remove them first.".to_string());
+        }
+        let servers = configs
+            .into_iter()
+            .map(|config| (config.name.clone(), server_entry(&config, None)))
+            .collect();
+        file = serde_json::json!({ "mcpServers": Value::Object(servers) });
+    }
+    let Value::Object(root) = &mut file else {
+        return Err("mcp_servers.json isn't a JSON object".to_string());
+    };
+    let Value::Object(servers) = root.entry("mcpServers").or_insert_with(|| Value::Object(Map::new())) else {
+        return Err("`mcpServers` in mcp_servers.json isn't an object".to_string());
+    };
+    edit(servers)?;
+    serde_json::to_string_pretty(&file).map_err(|e| e.to_string())
+}
+
+fn read_configs(config_path: &Path) -> Result<Vec<McpServerConfig>, String> {
+    let content = fs::read_to_string(config_path).map_err(|e| format!("Failed to read mcp_servers.json: {}", e))?;
+    parse_configs(&content).map_err(|e| format!("Failed to parse mcp_servers.json: {}", e))
+}
+
+/// How long "Test connection" waits for a server to start and list its tools.
+const TEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long to wait after `mcp_servers.json` changes before reloading it, since editors often
+/// save a file in several steps.
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// The delay before restarting a server that failed or crashed. It doubles with each failed
+/// attempt, up to `MAX_RESTART_DELAY`.
+const INITIAL_RESTART_DELAY: Duration = Duration::from_secs(1);
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(60);
+/// A server that stays ready this long counts as healthy again, so its next crash restarts it
+/// without delay.
+const STABLE_UPTIME: Duration = Duration::from_secs(60);
+/// How many times in a row a local server is restarted before it is left failed. Remote servers
+/// are retried until they're stopped, since they are usually down for reasons outside Hobbes.
+const MAX_RESTART_ATTEMPTS: u32 = 5;
+/// How often a running server is checked for a closed connection.
+const CONNECTION_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+fn restart_delay(attempt: u32) -> Duration {
+    INITIAL_RESTART_DELAY.saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1))).min(MAX_RESTART_DELAY)
 }
 
 pub struct ActiveMcpClient {
@@ -43,10 +247,29 @@ pub struct ActiveMcpClient {
     pub tools: Vec<Tool>,
 }
 
+/// The process of a local server, and the last line it wrote to stderr.
+struct LocalProcess {
+    child: Child,
+    last_stderr: Arc<std::sync::Mutex<Option<String>>>,
+}
+
+/// What `launch_servers` was given: where to publish the context, and the settings servers
+/// are started with.
+#[derive(Clone, Copy)]
+struct Launch {
+    mcp_context: Signal<McpContext>,
+    settings: Signal<Settings>,
+}
+
 #[derive(Clone)]
 pub struct McpManager {
-    configs: Vec<McpServerConfig>,
+    config_path: PathBuf,
+    configs: Arc<std::sync::Mutex<Vec<McpServerConfig>>>,
     pub servers: Arc<Mutex<HashMap<String, ActiveMcpClient>>>,
+    statuses: Arc<std::sync::Mutex<HashMap<String, McpServerStatus>>>,
+    /// The task starting, and for remote servers reconnecting, each started server.
+    tasks: Rc<RefCell<HashMap<String, Task>>>,
+    launch: Rc<OnceCell<Launch>>,
     permission_manager: Signal<PermissionManager>,
 }
 
@@ -60,93 +283,419 @@ impl McpManager {
                     }
                 }
             }
-            if let Err(e) = fs::write(&config_path, "[]") {
+            if let Err(e) = fs::write(&config_path, "{\"mcpServers\": {}}") {
                 tracing::error!("Failed to write default mcp_servers.json: {}", e);
             }
         }
 
-        let configs = match fs::read_to_string(config_path) {
-            Ok(content) => {
-                let configs_vec: Vec<McpServerConfig> = serde_json::from_str(&content).unwrap_or_else(|e| {
-                    tracing::error!("Failed to parse mcp_servers.json: {}", e);
-                    Vec::new()
-                });
-                tracing::info!("Successfully parsed {} MCP server configs.", configs_vec.len());
-                configs_vec
-            },
+        let configs = match read_configs(&config_path) {
+            Ok(configs) => {
+                tracing::info!("Successfully parsed {} MCP server configs.", configs.len());
+                configs
+            }
             Err(e) => {
-                tracing::error!("Failed to read mcp_servers.json: {}", e);
+                tracing::error!("{}", e);
                 Vec::new()
             }
         };
 
         Self {
-            configs,
+            config_path,
+            configs: Arc::new(std::sync::Mutex::new(configs)),
             servers: Arc::new(Mutex::new(HashMap::new())),
+            statuses: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            tasks: Rc::new(RefCell::new(HashMap::new())),
+            launch: Rc::new(OnceCell::new()),
             permission_manager,
         }
     }
 
-    pub async fn launch_servers(&self, mcp_context_signal: dioxus::prelude::Signal<McpContext>, settings: crate::settings::Settings) {
-        for server_config in self.configs.iter().filter(|sc| !sc.disabled) {
-            let server_config_clone = server_config.clone();
-            let servers_map = self.servers.clone();
-            let mut mcp_context_signal_clone = mcp_context_signal.clone();
-            let self_clone = self.clone();
-            let settings_clone = settings.clone();
+    /// Starts every enabled server and reloads them whenever `mcp_servers.json` changes. Only
+    /// the first call does anything.
+    pub fn launch_servers(&self, mcp_context: Signal<McpContext>, settings: Signal<Settings>) {
+        if self.launch.set(Launch { mcp_context, settings }).is_err() {
+            return;
+        }
+        let enabled: Vec<String> = self.configs.lock().unwrap().iter().filter(|sc| !sc.disabled).map(|sc| sc.name.clone()).collect();
+        for name in enabled {
+            if let Err(e) = self.start_server(&name) {
+                tracing::error!("Failed to start MCP server '{}': {}", name, e);
+            }
+        }
+        self.watch_config();
+        tracing::info!("All MCP server launch tasks initiated.");
+    }
+
+    /// Starts a configured server unless it has already been started. A local server that kept
+    /// failing stays started until it is stopped, so use `restart_server` to retry it.
+    pub fn start_server(&self, name: &str) -> Result<(), String> {
+        let launch = *self.launch.get().ok_or("MCP servers haven't been launched yet")?;
+        let config = self.configs.lock().unwrap().iter().find(|sc| sc.name == name).cloned()
+            .ok_or_else(|| format!("No MCP server named '{}'", name))?;
+        if config.disabled {
+            return Err(format!("MCP server '{}' is disabled", name));
+        }
+        let mut tasks = self.tasks.borrow_mut();
+        if !tasks.contains_key(name) {
+            let manager = self.clone();
+            tasks.insert(name.to_string(), spawn(async move { manager.run_server(config, launch).await }));
+        }
+        Ok(())
+    }
+
+    /// Stops a server and removes its tools from the context. Its tool calls that are still
+    /// running fail; other servers aren't affected.
+    pub async fn stop_server(&self, name: &str) {
+        let task = self.tasks.borrow_mut().remove(name);
+        if let Some(task) = task {
+            task.cancel();
+        }
+        let client = self.servers.lock().await.remove(name);
+        if let Some(client) = client {
+            if let Err(e) = client.service.cancel().await {
+                tracing::warn!("MCP server '{}' didn't shut down cleanly: {}", name, e);
+            }
+            tracing::info!("Stopped MCP server: {}", name);
+        }
+        self.statuses.lock().unwrap().remove(name);
+        if let Some(launch) = self.launch.get() {
+            self.publish_context(launch.mcp_context).await;
+        }
+    }
+
+    pub async fn restart_server(&self, name: &str) -> Result<(), String> {
+        self.stop_server(name).await;
+        self.start_server(name)
+    }
+
+    /// The configured servers, in the order they were read.
+    pub fn configs(&self) -> Vec<McpServerConfig> {
+        self.configs.lock().unwrap().clone()
+    }
+
+    /// Adds a server to `mcp_servers.json`, or replaces the one named `original_name`, and
+    /// applies the change.
+    pub async fn save_server(&self, original_name: Option<&str>, config: McpServerConfig) -> Result<(), String> {
+        if config.name.trim().is_empty() {
+            return Err("A server needs a name".to_string());
+        }
+        self.edit_config(|servers| {
+            if original_name != Some(config.name.as_str()) && servers.contains_key(&config.name) {
+                return Err(format!("There already is a server named '{}'", config.name));
+            }
+            let existing = original_name.and_then(|name| servers.remove(name));
+            servers.insert(config.name.clone(), server_entry(&config, existing));
+            Ok(())
+        })?;
+        self.reload_config().await;
+        Ok(())
+    }
+
+    /// Removes a server from `mcp_servers.json` and stops it.
+    pub async fn delete_server(&self, name: &str) -> Result<(), String> {
+        self.edit_config(|servers| {
+            servers.remove(name);
+            Ok(())
+        })?;
+        self.reload_config().await;
+        Ok(())
+    }
+
+    fn edit_config(&self, edit: impl FnOnce(&mut Map<String, Value>) -> Result<(), String>) -> Result<(), String> {
+        let content = match fs::read_to_string(&self.config_path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("Failed to read mcp_servers.json: {}", e)),
+        };
+        let content = edit_config_file(&content, edit)?;
+        hobbes_core::persist::write_atomic(&self.config_path, content.as_bytes())
+            .map_err(|e| format!("Failed to save mcp_servers.json: {}", e))
+    }
+
+    /// Starts a server that isn't saved yet, lists its tools and shuts it down again.
+    pub async fn test_server(config: &McpServerConfig, settings: &Settings) -> Result<Vec<Tool>, String> {
+        let (active_client, _process) = tokio::time::timeout(TEST_TIMEOUT, Self::connect(config, settings))
+            .await
+            .map_err(|_| format!("No response after {} seconds", TEST_TIMEOUT.as_secs()))??;
+        if let Err(e) = active_client.service.cancel().await {
+            tracing::warn!("Test launch of MCP server '{}' didn't shut down cleanly: {}", config.name, e);
+        }
+        Ok(active_client.tools)
+    }
+
+    /// Rereads `mcp_servers.json` and applies what changed: removed and disabled servers are
+    /// stopped, ones whose launch settings were edited restarted and new ones started. The
+    /// rest keep running. If the file
+    /// can't be read, the current servers are kept.
+    pub async fn reload_config(&self) {
+        let configs = match read_configs(&self.config_path) {
+            Ok(configs) => configs,
+            Err(e) => {
+                tracing::error!("{}; keeping the current MCP servers", e);
+                return;
+            }
+        };
+        let previous = std::mem::replace(&mut *self.configs.lock().unwrap(), configs.clone());
+        for old in previous.iter().filter(|old| !configs.iter().any(|sc| old.runs_as(sc))) {
+            if !configs.iter().any(|sc| sc.name == old.name && !sc.disabled) {
+                self.stop_server(&old.name).await;
+            }
+        }
+        for config in configs.iter().filter(|sc| !sc.disabled && !previous.iter().any(|old| old.runs_as(sc))) {
+            let result = if previous.iter().any(|old| old.name == config.name) {
+                self.restart_server(&config.name).await
+            } else {
+                self.start_server(&config.name)
+            };
+            if let Err(e) = result {
+                tracing::error!("Failed to start MCP server '{}': {}", config.name, e);
+            }
+        }
+        // Lets the settings show the new configuration even if no server had to change.
+        if let Some(launch) = self.launch.get() {
+            self.publish_context(launch.mcp_context).await;
+        }
+    }
+
+    /// Reloads the configuration whenever `mcp_servers.json` changes. Its directory is watched
+    /// rather than the file itself, since editors often save by replacing the file.
+    fn watch_config(&self) {
+        let Some(dir) = self.config_path.parent() else { return };
+        let file_name = self.config_path.file_name().map(|name| name.to_os_string());
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let Ok(event) = event else { return };
+            if !event.kind.is_access() && event.paths.iter().any(|path| path.file_name() == file_name.as_deref()) {
+                let _ = tx.send(());
+            }
+        });
+        let watcher = match watcher.and_then(|mut watcher| watcher.watch(dir, RecursiveMode::NonRecursive).map(|_| watcher)) {
+            Ok(watcher) => watcher,
+            Err(e) => {
+                tracing::error!("Failed to watch mcp_servers.json for changes: {}", e);
+                return;
+            }
+        };
+
+        let manager = self.clone();
+        spawn(async move {
+            // Watching stops when the watcher is dropped, so the task keeps it.
+            let _watcher = watcher;
+            while rx.recv().await.is_some() {
+                tokio::time::sleep(RELOAD_DEBOUNCE).await;
+                while rx.try_recv().is_ok() {}
+                tracing::info!("mcp_servers.json changed, reloading MCP servers.");
+                manager.reload_config().await;
+            }
+        });
+    }
+
+    /// Keeps a server running: connects to it, adds its tools to the context, waits for it to
+    /// exit or drop its connection, then restarts it with backoff. Each step is reflected in
+    /// the server's status.
+    async fn run_server(&self, config: McpServerConfig, launch: Launch) {
+        let server_name = config.name.clone();
+        let mut failed_attempts = 0;
+        loop {
+            tracing::info!("Launching MCP server: {}", server_name);
+            self.update_status(&server_name, |status| status.state = McpServerState::Starting);
+            self.publish_context(launch.mcp_context).await;
 
-            spawn(async move {
-                let server_name = server_config_clone.name.clone();
-                tracing::info!("Launching MCP server: {}", server_name);
-                let mut cmd = Command::new("sh");
-                let mut command_string = server_config_clone.command.clone();
+            let settings = launch.settings.read().clone();
+            let (state, error) = match Self::connect(&config, &settings).await {
+                Ok((active_client, process)) => {
+                    let peer = active_client.service.peer().clone();
+                    self.servers.lock().await.insert(server_name.clone(), active_client);
+                    self.update_status(&server_name, |status| {
+                        status.state = McpServerState::Ready;
+                        status.ready_since = Some(chrono::Utc::now());
+                    });
+                    self.publish_context(launch.mcp_context).await;
+                    tracing::info!("Successfully added '{}' and updated MCP context.", server_name);
 
-                if server_name == "filesystem" {
-                    if let Some(project_folder) = &settings_clone.project_folder {
-                        command_string.push_str(&format!(" \"{}\"", project_folder));
-                        tracing::info!("Appending project folder to filesystem MCP command: {}", command_string);
+                    let ready_at = Instant::now();
+                    let (exit_code, error) = Self::wait_for_exit(&peer, process).await;
+                    self.servers.lock().await.remove(&server_name);
+                    if ready_at.elapsed() >= STABLE_UPTIME {
+                        failed_attempts = 0;
                     }
+                    (McpServerState::Crashed { exit_code }, error)
                 }
+                Err(e) => (McpServerState::Failed, e),
+            };
+
+            failed_attempts += 1;
+            let give_up = config.url.is_none() && failed_attempts > MAX_RESTART_ATTEMPTS;
+            tracing::error!("MCP server '{}': {}: {}", server_name, state.label(), error);
+            self.update_status(&server_name, |status| {
+                status.state = state;
+                status.ready_since = None;
+                status.last_error = Some(error);
+            });
+            self.publish_context(launch.mcp_context).await;
+            if give_up {
+                tracing::error!("Giving up on MCP server '{}' after {} attempts.", server_name, MAX_RESTART_ATTEMPTS);
+                return;
+            }
+            tokio::time::sleep(restart_delay(failed_attempts)).await;
+            self.update_status(&server_name, |status| status.restarts += 1);
+        }
+    }
+
+    /// Waits until a server stops. Returns its exit code, if it had one, and what went wrong.
+    async fn wait_for_exit(peer: &Peer<RoleClient>, process: Option<LocalProcess>) -> (Option<i32>, String) {
+        let Some(mut process) = process else {
+            Self::transport_closed(peer).await;
+            return (None, "Connection lost".to_string());
+        };
+        let status = tokio::select! {
+            status = process.child.wait() => status,
+            _ = Self::transport_closed(peer) => {
+                // The server closed its output without exiting, so it can't be used any more.
+                let _ = process.child.start_kill();
+                process.child.wait().await
+            }
+        };
+        let exit_code = status.ok().and_then(|status| status.code());
+        let error = match exit_code {
+            Some(code) => format!("Exited with code {}", code),
+            None => "Exited".to_string(),
+        };
+        let last_stderr = process.last_stderr.lock().unwrap().take();
+        match last_stderr {
+            Some(line) => (exit_code, format!("{}: {}", error, line)),
+            None => (exit_code, error),
+        }
+    }
+
+    async fn transport_closed(peer: &Peer<RoleClient>) {
+        while !peer.is_transport_closed() {
+            tokio::time::sleep(CONNECTION_CHECK_INTERVAL).await;
+        }
+    }
 
-                cmd.arg("-c")
-                    .arg(&command_string)
-                    .envs(&server_config_clone.env)
-                    .stdin(std::process::Stdio::piped())
-                    .stdout(std::process::Stdio::piped())
-                    .stderr(std::process::Stdio::piped());
-
-                match TokioChildProcess::new(cmd) {
-                    Ok(transport) => match ().serve(transport).await {
-                        Ok(service) => {
-                            tracing::info!("Connected to MCP server: {}", server_name);
-                            match service.list_tools(Default::default()).await {
-                                Ok(result) => {
-                                    tracing::info!("Discovered capabilities for MCP server: {}", server_name);
-                                    let active_client = ActiveMcpClient {
-                                        config: server_config_clone,
-                                        service,
-                                        tools: result.tools,
-                                    };
-                                    {
-                                        let mut servers = servers_map.lock().await;
-                                        servers.insert(server_name.clone(), active_client);
-                                    }
-
-                                    let new_context = self_clone.get_mcp_context().await;
-                                    mcp_context_signal_clone.set(new_context);
-                                    tracing::info!("Successfully added '{}' and updated MCP context.", server_name);
-                                }
-                                Err(e) => tracing::error!("Failed to list tools for '{}': {}", server_name, e),
-                            }
-                        }
-                        Err(e) => tracing::error!("Failed to serve MCP server '{}': {}", server_name, e),
-                    },
-                    Err(e) => tracing::error!("Failed to launch MCP server '{}': {}", server_name, e),
+    /// Changes a server's status, creating it if the server has none yet.
+    fn update_status(&self, name: &str, update: impl FnOnce(&mut McpServerStatus)) {
+        let mut statuses = self.statuses.lock().unwrap();
+        let status = statuses.entry(name.to_string()).or_insert_with(|| McpServerStatus {
+            name: name.to_string(),
+            state: McpServerState::Starting,
+            ready_since: None,
+            last_error: None,
+            restarts: 0,
+        });
+        update(status);
+    }
+
+    /// Starts or connects to a server and lists its tools.
+    async fn connect(config: &McpServerConfig, settings: &Settings) -> Result<(ActiveMcpClient, Option<LocalProcess>), String> {
+        let (service, process) = match &config.url {
+            Some(url) => (Self::connect_remote(config, url).await?, None),
+            None => {
+                let (service, process) = Self::start_local(config, settings).await?;
+                (service, Some(process))
+            }
+        };
+        tracing::info!("Connected to MCP server: {}", config.name);
+        let result = service.list_tools(Default::default()).await
+            .map_err(|e| format!("Failed to list tools: {}", e))?;
+        tracing::info!("Discovered capabilities for MCP server: {}", config.name);
+        let active_client = ActiveMcpClient {
+            config: config.clone(),
+            service,
+            tools: result.tools,
+        };
+        Ok((active_client, process))
+    }
+
+    async fn start_local(config: &McpServerConfig, settings: &Settings) -> Result<(RunningService<RoleClient, ()>, LocalProcess), String> {
+        let mut cmd = Command::new(&config.command);
+        cmd.args(&config.args);
+
+        if config.name == "filesystem" {
+            if let Some(project_folder) = &settings.project_folder {
+                cmd.arg(project_folder);
+                tracing::info!("Appending project folder to filesystem MCP command: {}", project_folder);
+            }
+        }
+        if let Some(cwd) = &config.cwd {
+            cmd.current_dir(cwd);
+        }
+
+        cmd.envs(&config.env)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true);
+
+        let mut child = cmd.spawn().map_err(|e| format!("Failed to launch: {}", e))?;
+        let (Some(stdout), Some(stdin)) = (child.stdout.take(), child.stdin.take()) else {
+            return Err("Failed to launch: no stdio".to_string());
+        };
+        // Kept so a crash can be explained. Reading it also keeps the pipe from filling up.
+        let last_stderr = Arc::new(std::sync::Mutex::new(None));
+        if let Some(stderr) = child.stderr.take() {
+            let last_stderr = last_stderr.clone();
+            let server_name = config.name.clone();
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    tracing::debug!("MCP server '{}': {}", server_name, line);
+                    if !line.trim().is_empty() {
+                        *last_stderr.lock().unwrap() = Some(line);
+                    }
                 }
             });
         }
-        tracing::info!("All MCP server launch tasks initiated.");
+
+        let service = ().serve((stdout, stdin)).await.map_err(|e| format!("Failed to serve: {}", e))?;
+        Ok((service, LocalProcess { child, last_stderr }))
+    }
+
+    async fn connect_remote(config: &McpServerConfig, url: &str) -> Result<RunningService<RoleClient, ()>, String> {
+        let mut headers = reqwest::header::HeaderMap::new();
+        for (name, value) in &config.headers {
+            let name = reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| format!("Invalid header name '{}': {}", name, e))?;
+            let value = reqwest::header::HeaderValue::from_str(value)
+                .map_err(|e| format!("Invalid value for header '{}': {}", name, e))?;
+            headers.insert(name, value);
+        }
+        let client = reqwest::Client::builder()
+            .default_headers(headers)
+            .build()
+            .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+
+        match config.transport {
+            RemoteTransport::StreamableHttp => {
+                let transport = StreamableHttpClientTransport::with_client(
+                    client,
+                    StreamableHttpClientTransportConfig::with_uri(url),
+                );
+                ().serve(transport).await.map_err(|e| format!("Failed to connect: {}", e))
+            }
+            RemoteTransport::Sse => {
+                let transport = SseClientTransport::start_with_client(
+                    client,
+                    SseClientConfig { sse_endpoint: url.into(), ..Default::default() },
+                )
+                .await
+                .map_err(|e| format!("Failed to open event stream: {}", e))?;
+                ().serve(transport).await.map_err(|e| format!("Failed to connect: {}", e))
+            }
+        }
+    }
+
+    /// Rebuilds the MCP context from the connected servers and hands it to the UI.
+    async fn publish_context(&self, mut mcp_context_signal: Signal<McpContext>) {
+        let new_context = self.get_mcp_context().await;
+        mcp_context_signal.set(new_context);
     }
+
+    pub fn permission_manager(&self) -> Signal<PermissionManager> {
+        self.permission_manager
+    }
+
     fn map_tool_to_category(_tool_name: &str) -> ToolCategory {
         // All tools loaded via MCP are considered MCP tools for permission purposes.
         // The Browser/Execute categories are commented out as they are not currently used
@@ -160,14 +709,16 @@ impl McpManager {
         tool_name: &str,
         args: serde_json::Value,
         bypass_permission_check: bool,
+        requests: &RequestCounter,
+        cancel: Option<watch::Receiver<bool>>,
     ) -> Result<serde_json::Value, String> {
         if !bypass_permission_check {
             let category = Self::map_tool_to_category(tool_name);
             let pm = self.permission_manager.read();
-            match pm.check_permission(&category) {
-                PermissionStatus::Allowed => { /* Continue */ }
+            match pm.check_permission(&category, requests) {
+                PermissionStatus::Allowed => requests.record(),
                 PermissionStatus::RequiresPrompt => {
-                    let tool_call = crate::components::shared::ToolCall::new(
+                    let tool_call = hobbes_core::message::ToolCall::new(
                         server_name.to_string(),
                         tool_name.to_string(),
                         args,
@@ -179,27 +730,61 @@ impl McpManager {
                 }
             }
         }
-        let servers = self.servers.lock().await;
-        if let Some(client) = servers.get(server_name) {
-            if let Some(tool) = client.tools.iter().find(|t| t.name == tool_name) {
-                let arguments = if let serde_json::Value::Object(map) = args {
-                    map
-                } else {
-                    return Err("Tool arguments must be a JSON object".to_string());
-                };
-                let request = CallToolRequestParam {
-                    name: tool.name.clone(),
-                    arguments: Some(arguments),
-                };
-                match client.service.call_tool(request).await {
-                    Ok(result) => Ok(serde_json::to_value(result.content).unwrap()),
-                    Err(e) => Err(format!("Failed to use tool: {}", e)),
+        // Only hold the lock long enough to find the server, so calls to different tools can run concurrently.
+        let (peer, tool_name) = {
+            let servers = self.servers.lock().await;
+            let client = servers.get(server_name).ok_or_else(|| self.unavailable_reason(server_name))?;
+            let tool = client.tools.iter().find(|t| t.name == tool_name).ok_or_else(|| format!("Tool not found: {}", tool_name))?;
+            (client.service.peer().clone(), tool.name.clone())
+        };
+        let arguments = if let serde_json::Value::Object(map) = args {
+            map
+        } else {
+            return Err("Tool arguments must be a JSON object".to_string());
+        };
+        let request = ClientRequest::CallToolRequest(CallToolRequest::new(CallToolRequestParam {
+            name: tool_name,
+            arguments: Some(arguments),
+        }));
+        let handle = peer
+            .send_cancellable_request(request, PeerRequestOptions::no_options())
+            .await
+            .map_err(|e| format!("Failed to use tool: {}", e))?;
+
+        let response = match cancel {
+            Some(mut cancel) => {
+                let RequestHandle { rx, peer, id, .. } = handle;
+                tokio::select! {
+                    response = rx => response.unwrap_or(Err(ServiceError::TransportClosed)),
+                    Ok(_) = cancel.wait_for(|cancelled| *cancelled) => {
+                        // Let the server know so it can stop working on the request.
+                        let _ = peer
+                            .notify_cancelled(CancelledNotificationParam {
+                                request_id: id,
+                                reason: Some("Cancelled by user".to_string()),
+                            })
+                            .await;
+                        return Err("Tool call cancelled by user.".to_string());
+                    }
                 }
-            } else {
-                Err(format!("Tool not found: {}", tool_name))
             }
-        } else {
-            Err(format!("Server not found: {}", server_name))
+            None => handle.await_response().await,
+        };
+        match response {
+            Ok(ServerResult::CallToolResult(result)) => Ok(serde_json::to_value(result.content).unwrap()),
+            Ok(_) => Err("Failed to use tool: unexpected response from server".to_string()),
+            Err(e) => Err(format!("Failed to use tool: {}", e)),
+        }
+    }
+
+    /// Why a server's tools can't be used, for a server that isn't connected.
+    fn unavailable_reason(&self, server_name: &str) -> String {
+        match self.statuses.lock().unwrap().get(server_name) {
+            Some(status) => match &status.last_error {
+                Some(error) => format!("MCP server '{}' is not available ({}): {}", server_name, status.state.label(), error),
+                None => format!("MCP server '{}' is not available ({})", server_name, status.state.label()),
+            },
+            None => format!("Server not found: {}", server_name),
         }
     }
 
@@ -216,8 +801,90 @@ impl McpManager {
             server_contexts.push(server_context);
         }
 
+        let mut status: Vec<McpServerStatus> = self.statuses.lock().unwrap().values().cloned().collect();
+        status.sort_by(|a, b| a.name.cmp(&b.name));
+
         McpContext {
             servers: server_contexts,
+            status,
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_both_config_formats_spawn_without_a_shell() {
+        let servers = parse_configs(r#"{
+            "mcpServers": {
+                "files": { "command": "/opt/My Tools/server", "args": ["--root", "a b"], "cwd": "/tmp", "env": { "X": "1" } },
+                "remote": { "url": "https://example.com/mcp", "headers": { "Authorization": "Bearer t" } },
+                "broken": { "command": 3 }
+            }
+        }"#).unwrap();
+        assert_eq!(servers.len(), 2);
+        let files = servers.iter().find(|s| s.name == "files").unwrap();
+        assert_eq!(files.command, "/opt/My Tools/server");
+        assert_eq!(files.args, ["--root", "a b"]);
+        assert_eq!(files.cwd, Some(PathBuf::from("/tmp")));
+
+        let legacy = parse_configs(r#"[
+            { "name": "conport", "command": "uvx conport-mcp --workspace \"/My Projects\"" },
+            { "name": "piped", "command": "server | tee log" },
+            { "name": "globbed", "command": "server --log *.txt" }
+        ]"#).unwrap();
+        // Command lines that need a shell are rejected rather than run through one.
+        assert_eq!(legacy.len(), 2);
+        assert_eq!(legacy[0].command, "uvx");
+        assert_eq!(legacy[0].args, ["conport-mcp", "--workspace", "/My Projects"]);
+        assert_eq!(legacy[1].command, "server");
+        assert_eq!(legacy[1].args, ["--log", "*.txt"]);
+    }
+
+    #[test]
+    fn test_only_launch_settings_require_a_restart() {
+        let servers = parse_configs(r#"{ "mcpServers": { "files": { "command": "files-server", "args": ["--verbose"] } } }"#).unwrap();
+        let running = &servers[0];
+        let described = McpServerConfig { description: "Reads files".to_string(), ..running.clone() };
+        assert!(running.runs_as(&described));
+        let relaunched = McpServerConfig { args: vec!["--quiet".to_string()], ..running.clone() };
+        assert!(!running.runs_as(&relaunched));
+        let disabled = McpServerConfig { disabled: true, ..running.clone() };
+        assert!(!running.runs_as(&disabled));
+    }
+
+    #[test]
+    fn test_editing_a_server_keeps_what_hobbes_does_not_own() {
+        let file = r#"{
+            "globalShortcut": "Ctrl+Space",
+            "mcpServers": {
+                "files": { "command": "files-server", "args": ["--verbose"], "alwaysAllow": ["read"] },
+                "broken": { "command": 3 }
+            }
+        }"#;
+        let mut files = parse_configs(file).unwrap().remove(0);
+        files.name = "workspace".to_string();
+        files.args.clear();
+        files.disabled = true;
+
+        let edited = edit_config_file(file, |servers| {
+            let existing = servers.remove("files");
+            servers.insert(files.name.clone(), server_entry(&files, existing));
+            Ok(())
+        }).unwrap();
+        let edited: Value = serde_json::from_str(&edited).unwrap();
+        assert_eq!(edited["globalShortcut"], "Ctrl+Space");
+        assert_eq!(edited["mcpServers"]["broken"]["command"], 3);
+        assert_eq!(edited["mcpServers"]["workspace"], serde_json::json!({
+            "command": "files-server",
+            "alwaysAllow": ["read"],
+            "disabled": true
+        }));
+
+        let converted = edit_config_file(r#"[{ "name": "conport", "command": "uvx conport-mcp" }]"#, |_| Ok(())).unwrap();
+        let converted: Value = serde_json::from_str(&converted).unwrap();
+        assert_eq!(converted["mcpServers"]["conport"], serde_json::json!({ "command": "uvx", "args": ["conport-mcp"] }));
+    }
+}
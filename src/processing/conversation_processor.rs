@@ -1,7 +1,7 @@
-use crate::session::{ConversationSummary, Session};
+use hobbes_core::session::{ConversationSummary, Session};
 use crate::components::llm;
 use crate::settings::Settings;
-use crate::components::shared::{MessageContent};
+use hobbes_core::message::{MessageContent, TokenUsage};
 
 /// Processes conversation history to extract and update short-term context.
 pub struct ConversationProcessor {
@@ -14,27 +14,30 @@ impl ConversationProcessor {
     }
 
     /// Takes the last few messages, generates a context summary using a fast LLM,
-    /// and updates the session's active context.
-    pub async fn process_and_respond(&self, session: &mut Session, settings: &Settings) -> String {
+    /// and updates the session's active context. Returns the prompt along with the token
+    /// usage of the summary model's responses, for the caller to record.
+    pub async fn process_and_respond(&self, session: &mut Session, settings: &Settings) -> (String, Vec<TokenUsage>) {
         // For now, we will just generate the summary and not call tools.
         // The logic for tool calling will be added here later.
-        if let Some(summary) = self.generate_summary(session, settings).await {
+        let mut usage = Vec::new();
+        if let Some(summary) = self.generate_summary(session, settings, &mut usage).await {
             session.active_context.conversation_summary = summary;
         }
 
         // This part will be replaced with logic that decides whether to call a tool
         // or to send the user's message to the LLM.
-        let last_message = session.messages.last().unwrap();
-        if let MessageContent::Text(text) = &last_message.content {
+        let last_message = *session.active_path().last().unwrap();
+        let prompt = if let MessageContent::Text(text) = &last_message.content {
             text.clone()
         } else {
             "".to_string()
-        }
+        };
+        (prompt, usage)
     }
 
     /// Takes the last few messages, generates a context summary using a fast LLM,
     /// and updates the session's active context.
-    async fn generate_summary(&self, session: &Session, settings: &Settings) -> Option<ConversationSummary> {
+    async fn generate_summary(&self, session: &Session, settings: &Settings, usage: &mut Vec<TokenUsage>) -> Option<ConversationSummary> {
         // 1. Get the previous summary from the active context by serializing the struct
         let previous_summary = serde_json::to_string(&session.active_context.conversation_summary)
             .unwrap_or_else(|e| {
@@ -44,8 +47,8 @@ impl ConversationProcessor {
 
         // 2. Get the last 5 messages and format them
         let recent_history: String = session
-            .messages
-            .iter()
+            .active_path()
+            .into_iter()
            .rev()
            .take(5)
            .rev()
@@ -54,6 +57,7 @@ impl ConversationProcessor {
                    MessageContent::Text(text) => text.clone(),
                    MessageContent::ToolCall(tc) => format!("[Tool Call: {}]", tc.tool_name),
                    MessageContent::PermissionRequest(tc) => format!("[Permission Request for Tool: {}]", tc.tool_name),
+                   MessageContent::Attachment(attachment) => format!("[Attachment: {}]", attachment.file_name),
                };
                format!("{}: {}", m.author, content_str)
            })
@@ -64,31 +68,22 @@ impl ConversationProcessor {
             return None;
         }
 
-        // 3. Get API key, prioritizing settings, then environment variable
-        let api_key = settings.api_key.clone().unwrap_or_else(|| {
-            std::env::var("GEMINI_API_KEY").expect("GEMINI_API_KEY must be set in settings or environment")
-        });
+        // 3. Resolve the provider configured for the summary model
+        let provider = llm::provider_for(settings, settings.summary_provider);
 
         // 4. Call the LLM to refine the summary
         match llm::summarize_conversation(
-            api_key,
+            provider.as_ref(),
             settings.summary_model.clone(),
             previous_summary,
             recent_history,
+            |response_usage| usage.push(response_usage),
         )
         .await
         {
-            Ok(summary_json) => {
-                match serde_json::from_value::<ConversationSummary>(summary_json) {
-                    Ok(summary) => {
-                        tracing::info!("Successfully deserialized new conversation summary.");
-                        Some(summary)
-                    }
-                    Err(e) => {
-                        tracing::error!("Failed to deserialize conversation summary: {}", e);
-                        None
-                    }
-                }
+            Ok(summary) => {
+                tracing::info!("Successfully generated new conversation summary.");
+                Some(summary)
             }
             Err(e) => {
                 tracing::error!("Failed to summarize conversation: {}", e);
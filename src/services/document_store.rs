@@ -24,7 +24,7 @@ impl DocumentStore {
 
     pub async fn upsert_tool_result(
         &self,
-        record: &crate::components::shared::ToolCallRecord,
+        record: &hobbes_core::message::ToolCallRecord,
     ) -> Result<(), Box<dyn Error>> {
         let collection_name = "tool_results";
         let queries = &[record.result.response.as_str()];
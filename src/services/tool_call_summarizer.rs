@@ -1,4 +1,4 @@
-use crate::session::SessionState;
+use hobbes_core::session::SessionState;
 use crate::settings::Settings;
 
 pub struct ToolCallSummarizer {
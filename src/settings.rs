@@ -2,14 +2,27 @@ use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
 
+use crate::components::llm::ProviderKind;
 use crate::context::permissions::{PermissionSettings, ToolCategory};
+use crate::context::usage::{self, BudgetResetSchedule, ModelPrice};
+use hobbes_core::generation::GenerationParams;
+use hobbes_core::schema::{self, Migration};
+use serde_json::Value;
 use std::collections::HashMap;
 
+/// Fields missing from `settings.json` take their default, so adding a field never resets the
+/// rest of the configuration.
 #[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
 pub struct Settings {
+    pub schema_version: u32,
     pub api_key: Option<String>,
     pub chat_model: String,
     pub summary_model: String,
+    #[serde(default)]
+    pub chat_provider: ProviderKind,
+    #[serde(default)]
+    pub summary_provider: ProviderKind,
     pub persona: String,
     pub force_tool_use_instruction: Option<String>,
     pub project_folder: Option<String>,
@@ -18,6 +31,68 @@ pub struct Settings {
     pub show_tray_icon: bool,
     pub global_hotkey: String,
     pub permission_settings: PermissionSettings,
+    /// How many rounds of tool calls the agent loop may make before it must answer.
+    #[serde(default = "default_max_tool_iterations")]
+    pub max_tool_iterations: u32,
+    /// Per-model prices used to turn reported token usage into cost.
+    #[serde(default = "usage::default_model_prices")]
+    pub model_prices: HashMap<String, ModelPrice>,
+    #[serde(default)]
+    pub openai: OpenAiSettings,
+    #[serde(default)]
+    pub anthropic: AnthropicSettings,
+    #[serde(default)]
+    pub ollama: OllamaSettings,
+    /// Defaults for every chat request. Sessions can override individual values.
+    #[serde(default)]
+    pub generation: GenerationParams,
+    /// Fields this version doesn't know, kept so that saving doesn't drop them.
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, Value>,
+}
+
+/// Connection settings for an OpenAI-compatible chat completions server.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct OpenAiSettings {
+    /// The API root, including the `/v1` suffix (e.g. `http://localhost:8080/v1`).
+    pub base_url: String,
+    pub api_key: Option<String>,
+}
+
+impl Default for OpenAiSettings {
+    fn default() -> Self {
+        Self {
+            base_url: "http://localhost:8080/v1".to_string(),
+            api_key: None,
+        }
+    }
+}
+
+/// Credentials for the Anthropic Messages API.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct AnthropicSettings {
+    pub api_key: Option<String>,
+}
+
+/// Connection settings for a local Ollama server.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct OllamaSettings {
+    pub base_url: String,
+}
+
+impl Default for OllamaSettings {
+    fn default() -> Self {
+        Self {
+            base_url: "http://localhost:11434".to_string(),
+        }
+    }
+}
+
+fn default_max_tool_iterations() -> u32 {
+    5
 }
 
 impl Default for Settings {
@@ -26,9 +101,12 @@ impl Default for Settings {
         granular_permissions.insert(ToolCategory::Mcp, true);
 
         Self {
+            schema_version: SETTINGS_MIGRATIONS.len() as u32,
             api_key: None,
             chat_model: "gemini-2.5-pro".to_string(),
             summary_model: "gemini-1.5-flash-latest".to_string(),
+            chat_provider: ProviderKind::Gemini,
+            summary_provider: ProviderKind::Gemini,
             persona: "You are Hobbes, a helpful AI assistant.".to_string(),
             force_tool_use_instruction: Some("You must always use the provided tools to answer the user's request, even if you think you know the answer. Do not answer from your own knowledge base when tools are available. When using the fetch tool, you MUST provide markdown links as sources.".to_string()),
             project_folder: None,
@@ -41,11 +119,41 @@ impl Default for Settings {
                 granular_permissions,
                 max_requests: 25,
                 max_cost: 1.00,
+                budget_reset: BudgetResetSchedule::Daily,
             },
+            max_tool_iterations: default_max_tool_iterations(),
+            model_prices: usage::default_model_prices(),
+            openai: OpenAiSettings::default(),
+            anthropic: AnthropicSettings::default(),
+            ollama: OllamaSettings::default(),
+            generation: GenerationParams::default(),
+            extra: serde_json::Map::new(),
         }
     }
 }
 
+/// The migrations `settings.json` goes through when it is read. See `hobbes_core::schema`.
+const SETTINGS_MIGRATIONS: &[Migration] = &[first_versioned_format];
+
+/// Version 1: the first versioned format. Its fields are those of version 0; the file only
+/// gains its `schema_version`.
+fn first_versioned_format(_settings: &mut Value) {}
+
+/// Upgrades and parses the contents of `settings.json`.
+fn parse_settings(content: &str) -> Result<Settings, serde_json::Error> {
+    let mut value: Value = serde_json::from_str(content)?;
+    if let Err(e) = schema::upgrade(&mut value, SETTINGS_MIGRATIONS, "settings.json") {
+        // Read what we can. `SettingsManager::save` won't write over the newer file.
+        tracing::warn!("{}", e);
+    }
+    let (settings, _): (Settings, _) = schema::from_value(value, "settings.json")?;
+    // Unknown top-level fields land in `extra` rather than being reported as ignored.
+    for key in settings.extra.keys() {
+        tracing::warn!("Keeping unknown field `{}` in settings.json", key);
+    }
+    Ok(settings)
+}
+
 pub struct SettingsManager {
     settings_path: PathBuf,
 }
@@ -55,22 +163,75 @@ impl SettingsManager {
         Self { settings_path }
     }
 
+    /// Reads `settings.json`. A file that can't be parsed is quarantined, not overwritten, and
+    /// the defaults are used instead.
     pub fn load(&self) -> Settings {
-        if !self.settings_path.exists() {
+        let Ok(content) = fs::read_to_string(&self.settings_path) else {
             return Settings::default();
-        }
-
-        fs::read_to_string(&self.settings_path)
-            .ok()
-            .and_then(|content| serde_json::from_str(&content).ok())
-            .unwrap_or_default()
+        };
+        parse_settings(&content).unwrap_or_else(|e| {
+            tracing::error!("Failed to read settings.json, using defaults: {}", e);
+            if let Err(e) = hobbes_core::persist::quarantine(&self.settings_path) {
+                tracing::error!("Failed to move the unreadable settings.json aside: {}", e);
+            }
+            Settings::default()
+        })
     }
 
+    /// Writes `settings.json`, unless the file on disk comes from a newer version of Hobbes, in
+    /// which case writing it in this version's shape could lose data.
     pub fn save(&self, settings: &Settings) -> Result<(), std::io::Error> {
-        let content = serde_json::to_string_pretty(settings)?;
-        if let Some(parent) = self.settings_path.parent() {
-            fs::create_dir_all(parent)?;
+        let current = SETTINGS_MIGRATIONS.len() as u32;
+        if let Some(version) = fs::read_to_string(&self.settings_path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<Value>(&content).ok())
+            .map(|value| schema::version_of(&value))
+        {
+            if version > current {
+                return Err(std::io::Error::other(format!(
+                    "settings.json has schema version {}, newer than {}; not overwriting it",
+                    version, current
+                )));
+            }
         }
-        fs::write(&self.settings_path, content)
+        let content = serde_json::to_string_pretty(settings)?;
+        hobbes_core::persist::write_atomic(&self.settings_path, content.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_from_before_versioning_keep_their_values() {
+        let settings = parse_settings(include_str!("../fixtures/settings_v0.json")).unwrap();
+        assert_eq!(settings.schema_version, SETTINGS_MIGRATIONS.len() as u32);
+        assert_eq!(settings.persona, "You are Hobbes, a terse assistant.");
+        assert_eq!(settings.chat_history_length, 12);
+        assert!(!settings.show_tray_icon);
+        assert_eq!(settings.permission_settings.max_requests, 40);
+        assert_eq!(settings.permission_settings.granular_permissions.get(&ToolCategory::Mcp), Some(&false));
+        // Fields added since then take their defaults.
+        assert_eq!(settings.max_tool_iterations, default_max_tool_iterations());
+        assert_eq!(settings.ollama.base_url, OllamaSettings::default().base_url);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_unknown_settings_fields_survive_a_save() {
+        let dir = std::env::temp_dir().join(format!("hobbes-settings-{}", uuid::Uuid::new_v4()));
+        let manager = SettingsManager::new(dir.join("settings.json"));
+        let settings = parse_settings(include_str!("../fixtures/settings_v0.json")).unwrap();
+        assert_eq!(settings.extra.get("theme"), Some(&Value::from("dark")));
+
+        manager.save(&settings).unwrap();
+        let saved: Value = serde_json::from_str(&fs::read_to_string(dir.join("settings.json")).unwrap()).unwrap();
+        assert_eq!(saved["theme"], "dark");
+        assert_eq!(manager.load().extra.get("theme"), Some(&Value::from("dark")));
+
+        fs::write(dir.join("settings.json"), r#"{ "schema_version": 99, "persona": "newer" }"#).unwrap();
+        assert!(manager.save(&settings).is_err());
+        assert!(fs::read_to_string(dir.join("settings.json")).unwrap().contains("newer"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}